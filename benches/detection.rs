@@ -0,0 +1,99 @@
+//! Benchmarks for the detection hot paths on synthetic sheets.
+//!
+//! Run with `cargo bench`; compare runs before and after changing thresholds
+//! or the detection algorithms.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use image::{DynamicImage, Rgba, RgbaImage};
+use spritesheet_cutter::{CutterConfig, SpritesheetCutter};
+
+/// Sheet sizes to benchmark, from a small strip to a large atlas
+const SHEET_SIZES: [u32; 3] = [256, 1024, 2048];
+
+/// Size of the sheet the serial and parallel boundary scans are compared on
+const LARGE_SHEET_SIZE: u32 = 4096;
+
+/// Cell size of the synthetic sheets; each cell holds one sprite with a
+/// transparent gutter around it
+const CELL: u32 = 64;
+
+/// A `size`x`size` sheet of shaded sprites on `background`
+fn synthetic_sheet(size: u32, background: Rgba<u8>) -> DynamicImage {
+    let mut sheet = RgbaImage::from_pixel(size, size, background);
+    for cell_y in (0..size).step_by(CELL as usize) {
+        for cell_x in (0..size).step_by(CELL as usize) {
+            for y in cell_y + 8..(cell_y + CELL - 8).min(size) {
+                for x in cell_x + 8..(cell_x + CELL - 8).min(size) {
+                    let shade = ((x * 7 + y * 3) % 128) as u8;
+                    sheet.put_pixel(x, y, Rgba([120 + shade, 60, 200 - shade, 255]));
+                }
+            }
+        }
+    }
+    DynamicImage::ImageRgba8(sheet)
+}
+
+fn detection(c: &mut Criterion) {
+    let cutter = SpritesheetCutter::new(CutterConfig::default());
+    let mut group = c.benchmark_group("detect_sprite_frames");
+    for size in SHEET_SIZES {
+        let sheet = synthetic_sheet(size, Rgba([0, 0, 0, 0]));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &sheet, |b, sheet| {
+            b.iter(|| cutter.detect_sprite_frames(black_box(sheet)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn vertical_boundaries(c: &mut Criterion) {
+    let cutter = SpritesheetCutter::new(CutterConfig::default());
+    let mut group = c.benchmark_group("find_vertical_boundaries");
+    for size in SHEET_SIZES {
+        let sheet = synthetic_sheet(size, Rgba([0, 0, 0, 0]));
+        let (gray, rgba) = (sheet.to_luma16(), sheet.to_rgba8());
+        group.bench_with_input(BenchmarkId::new("luma", size), &gray, |b, gray| {
+            b.iter(|| cutter.find_vertical_boundaries(black_box(gray), None))
+        });
+        group.bench_with_input(BenchmarkId::new("alpha", size), &gray, |b, gray| {
+            b.iter(|| cutter.find_vertical_boundaries(black_box(gray), Some(&rgba)))
+        });
+    }
+    group.finish();
+}
+
+/// Boundary scanning on one thread against the whole rayon pool, on a sheet
+/// large enough for the per-column work to be worth spreading out
+fn serial_vs_parallel(c: &mut Criterion) {
+    let cutter = SpritesheetCutter::new(CutterConfig::default());
+    let sheet = synthetic_sheet(LARGE_SHEET_SIZE, Rgba([0, 0, 0, 0]));
+    let (gray, rgba) = (sheet.to_luma16(), sheet.to_rgba8());
+    let serial = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+    // Both must find the same boundaries for the comparison to mean anything
+    assert_eq!(serial.install(|| cutter.find_vertical_boundaries(&gray, Some(&rgba))),
+               cutter.find_vertical_boundaries(&gray, Some(&rgba)));
+
+    let mut group = c.benchmark_group("find_vertical_boundaries_threads");
+    group.sample_size(10);
+    group.bench_function(BenchmarkId::new("serial", LARGE_SHEET_SIZE), |b| {
+        b.iter(|| serial.install(|| cutter.find_vertical_boundaries(black_box(&gray), Some(&rgba))))
+    });
+    group.bench_function(BenchmarkId::new("parallel", LARGE_SHEET_SIZE), |b| {
+        b.iter(|| cutter.find_vertical_boundaries(black_box(&gray), Some(&rgba)))
+    });
+    group.finish();
+}
+
+fn background_removal(c: &mut Criterion) {
+    let cutter = SpritesheetCutter::new(CutterConfig::default());
+    let mut group = c.benchmark_group("remove_background");
+    for size in SHEET_SIZES {
+        let sheet = synthetic_sheet(size, Rgba([255, 0, 255, 255]));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &sheet, |b, sheet| {
+            b.iter(|| cutter.remove_background(black_box(sheet)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, detection, vertical_boundaries, serial_vs_parallel, background_removal);
+criterion_main!(benches);
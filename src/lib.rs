@@ -0,0 +1,8623 @@
+//! Automatic sprite frame extraction from spritesheets.
+//!
+//! Build a [`CutterConfig`], hand it to [`SpritesheetCutter::new`] and either
+//! process whole folders with [`SpritesheetCutter::process_directory`] or call
+//! the individual stages ([`SpritesheetCutter::detect`] and
+//! [`SpritesheetCutter::extract_all`] or its streaming counterpart
+//! [`SpritesheetCutter::extract_iter`], or the finer-grained
+//! [`SpritesheetCutter::extract_frame`] and [`SpritesheetCutter::remove_background`])
+//! on images you have already loaded, inspecting or editing the frames in between.
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::imageops::FilterType;
+use image::{AnimationDecoder, Delay, DynamicImage, Frame, GenericImageView, Rgba, RgbaImage};
+use imageproc::definitions::Image;
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, error, info, warn, Level};
+use notify_debouncer_mini::notify::{self, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use walkdir::WalkDir;
+
+/// Errors returned by the cutter
+#[derive(Debug, thiserror::Error)]
+pub enum CutterError {
+    /// The source image could not be read or decoded
+    #[error("failed to open image {}", path.display())]
+    OpenImage {
+        path: PathBuf,
+        #[source]
+        source: image::ImageError,
+    },
+    /// An extracted frame or copied sprite could not be written
+    #[error("failed to save {}", path.display())]
+    SaveFrame {
+        path: PathBuf,
+        #[source]
+        source: image::ImageError,
+    },
+    /// An output folder could not be created
+    #[error("failed to create output directory {}", path.display())]
+    CreateOutputDir {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// Detection found nothing to cut on this sheet
+    #[error("no frames detected in {}", path.display())]
+    NoFramesDetected { path: PathBuf },
+    /// A metadata file (atlas, montage, summary, preview) could not be written
+    #[error("failed to write {}", path.display())]
+    WriteFile {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// A preview animation could not be encoded
+    #[error("failed to encode {}", path.display())]
+    EncodeImage {
+        path: PathBuf,
+        #[source]
+        source: image::ImageError,
+    },
+    /// Metadata could not be serialized
+    #[error("failed to serialize metadata")]
+    Serialize(#[from] serde_json::Error),
+    /// The working directory could not be determined
+    #[error("failed to get current directory")]
+    CurrentDir(#[source] io::Error),
+    /// The worker threads for `jobs` could not be started
+    #[error("failed to start worker threads")]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
+    /// The configuration can never produce a usable result
+    #[error("invalid configuration: {0}")]
+    InvalidConfig(String),
+    /// A sheet's frames don't all fit in a packed atlas of the configured size
+    #[error("frames of {} don't fit in a {size}x{size} atlas", path.display())]
+    AtlasOverflow { path: PathBuf, size: u32 },
+    /// The `file_list` could not be read
+    #[error("failed to read file list {}", path.display())]
+    ReadFileList {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// A config file could not be read
+    #[error("failed to read config file {}", path.display())]
+    ReadConfig {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// The earlier run's manifest for `compare` could not be read
+    #[error("failed to read manifest {}", path.display())]
+    ReadReport {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// The earlier run's manifest for `compare` is not a JSON manifest
+    #[error("invalid manifest {}: {message}", path.display())]
+    ParseReport { path: PathBuf, message: String },
+    /// A config file is not valid TOML/JSON or has unknown or mistyped settings
+    #[error("invalid config file {}: {message}", path.display())]
+    ParseConfig { path: PathBuf, message: String },
+    /// The atlas file next to a sheet could not be used
+    #[error("can't use atlas file {}: {message}", path.display())]
+    ParseAtlas { path: PathBuf, message: String },
+    /// A normal map and the diffuse sheet it should be cut like differ in size
+    #[error("{} is {}x{}, but its diffuse sheet {} is {}x{}", path.display(), size.0, size.1,
+            diffuse.display(), diffuse_size.0, diffuse_size.1)]
+    NormalMapSize { path: PathBuf, size: (u32, u32), diffuse: PathBuf, diffuse_size: (u32, u32) },
+    /// A frame is larger than the `uniform_frame_size` canvas
+    #[error("a {width}x{height} frame of {} doesn't fit the uniform {size}x{size} frame size", path.display())]
+    FrameTooLarge { path: PathBuf, width: u32, height: u32, size: u32 },
+    /// Detection found more frames than `max_frames` allows
+    #[error("{} has {count} frames, more than the limit of {max}", path.display())]
+    TooManyFrames { path: PathBuf, count: usize, max: usize },
+    /// The image is in a format whose decoder was left out of this build
+    #[error("{} is a {format} image, but this build was compiled without {format} support", path.display())]
+    UnsupportedFormat { path: PathBuf, format: String },
+    /// A frame's output file exists and `overwrite` is `Error`
+    #[error("{} already exists", path.display())]
+    OutputExists { path: PathBuf },
+    /// With `flatten_output`, a sheet's outputs would be named like those
+    /// of another sheet of the same folder
+    #[error("{} would get the same output names as {}", path.display(), other.display())]
+    OutputNameClash { path: PathBuf, other: PathBuf },
+    /// `background_index` can't be looked up in a sheet
+    #[error("{}: can't use palette index {index} as the background: {reason}", path.display())]
+    BackgroundIndex { path: PathBuf, index: u8, reason: String },
+    /// Some images in a batch run failed; each was reported as it happened
+    #[error("{failed} image(s) failed to process")]
+    ImagesFailed { failed: usize },
+}
+
+/// Log target of the one-line run summary, so it can be shown on its own
+/// while the full report is filtered out
+pub const SUMMARY_LOG_TARGET: &str = "spritesheet_cutter::summary";
+
+/// Result type used throughout the cutter
+pub type Result<T, E = CutterError> = std::result::Result<T, E>;
+
+/// Shorthand for rejecting a configuration with a formatted message
+macro_rules! invalid_config {
+    ($($arg:tt)*) => {
+        return Err(CutterError::InvalidConfig(format!($($arg)*)))
+    };
+}
+
+/// Configuration for the spritesheet cutter.
+///
+/// Deserializes from a config file where every field is optional and falls
+/// back to its default; colors are written as hex strings like `"ff00ff"`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CutterConfig {
+    /// Minimum width/height for a sprite frame
+    pub min_sprite_size: u32,
+    /// Maximum width/height for a sprite frame
+    pub max_sprite_size: u32,
+    /// Tolerance for background color detection
+    pub background_tolerance: u8,
+    /// Whether to remove backgrounds
+    pub remove_background: bool,
+    /// Frames with a smaller fraction of pixels above `alpha_threshold` keep
+    /// their background, so a misdetected background can't wipe out a sparse sprite
+    #[serde(serialize_with = "compact_f32::serialize_option")]
+    pub background_opacity_floor: Option<f32>,
+    /// Output directory name
+    pub output_dir: String,
+    /// Whether a relative `output_dir` is resolved against each input folder
+    /// (or a single sheet's folder) instead of the working directory
+    pub output_relative_to_input: bool,
+    /// What happens to output files that already exist in the output directory
+    pub overwrite: OverwritePolicy,
+    /// Command run on every frame file written, e.g. `pngquant --ext .png --force {file}`;
+    /// `{file}` is replaced by the file's path
+    pub exec: Option<String>,
+    /// Whether subfolders of the input folders are searched for sheets too
+    pub recursive: bool,
+    /// Whether sheets from subfolders of an input folder are written straight
+    /// into its output folder, named after their subfolders, instead of into
+    /// the same subfolders there
+    pub flatten_output: bool,
+    /// Whether each sheet's outputs go into their own `{sheet_name}` folder
+    /// inside the output folder
+    pub subfolder_per_sheet: bool,
+    /// Whether to write an ImageMagick `montage.txt` per output folder
+    pub emit_montage: bool,
+    /// Folders to process, or single sheets to cut; empty means the default
+    /// Base, Ships and Space folders
+    pub input_dirs: Vec<PathBuf>,
+    /// File listing exactly the images to process, one path per line
+    /// relative to the working directory, instead of the input folders; `-`
+    /// reads the list from stdin. Blank lines and lines starting with `#`
+    /// are skipped.
+    pub file_list: Option<PathBuf>,
+    /// Glob patterns such as `*.png`; when any are given, only images whose
+    /// file name matches one of them are processed
+    pub include: Vec<String>,
+    /// Glob patterns for image file names to skip, such as `*_backup.*`
+    pub exclude: Vec<String>,
+    /// Fixed grid layout; when set, boundary detection is skipped entirely
+    pub grid: Option<GridSpec>,
+    /// Pixels around the whole grid that belong to no cell
+    pub grid_margin: u32,
+    /// Pixels between neighbouring grid cells
+    pub grid_spacing: u32,
+    /// Cut the sheet as an animation strip of exactly this many equal frames,
+    /// skipping detection like `grid` does
+    pub strip_count: Option<u32>,
+    /// Which axis of the strip the frames are laid out along
+    pub strip_orientation: Orientation,
+    /// Algorithm used to find frames when no grid is given
+    pub detection_mode: DetectionMode,
+    /// Merge connected components whose boxes overlap by more than this
+    /// fraction of the smaller box
+    #[serde(serialize_with = "compact_f32::serialize_option")]
+    pub merge_overlap: Option<f32>,
+    /// Merge detected frames that touch along an edge with content running
+    /// across it, for sprites the detector split into pieces
+    pub merge_adjacent: bool,
+    /// Atlas description written next to each sheet's frames
+    pub atlas_format: AtlasFormat,
+    /// Manifest of every frame saved in a run, written to the output directory
+    pub manifest: Option<ManifestFormat>,
+    /// `manifest.json` of an earlier run to compare this run's frames with,
+    /// reporting each sheet whose frames changed
+    pub compare: Option<PathBuf>,
+    /// Anchor point written for every frame of the atlas file
+    pub pivot: Option<PivotMode>,
+    /// Whether each frame's average and dominant color are written into the
+    /// JSON atlas file and manifest
+    pub emit_colors: bool,
+    /// Whether to crop transparent borders from each extracted frame
+    pub trim: bool,
+    /// Pixels of the original border kept around the visible content when
+    /// trimming, so glows and shadows aren't clipped
+    pub trim_margin: u32,
+    /// Whether detection runs on the sheet with its empty outer border
+    /// cropped off; frames are still reported in sheet coordinates
+    pub autocrop: bool,
+    /// Whether sheets whose frames turn out to be evenly spaced are sliced as
+    /// that grid (see `SpritesheetCutter::infer_grid`) instead of along the
+    /// detected boundaries. Only applies to `DetectionMode::Boundaries`
+    /// without `autocrop`; an explicit `grid` or `strip_count` wins over it.
+    pub auto_grid: bool,
+    /// Keep sprites that touch the sheet's outer edge whole: the mostly
+    /// empty rows and columns next to such a sprite aren't taken for a gap
+    pub include_edge_frames: bool,
+    /// Whether boundary detection tightens each span between boundaries to
+    /// the first and last row or column with content
+    pub refine_boundaries: bool,
+    /// When boundary detection finds at most one frame on a sheet big enough
+    /// for several, retry with relaxed thresholds. Off by default, as relaxed
+    /// thresholds can also cut a single large sprite apart.
+    pub adaptive_retry: bool,
+    /// Region `(x, y, width, height)` of the sheet that detection is limited
+    /// to, e.g. to leave out a header or legend; frames are still reported
+    /// in sheet coordinates
+    pub roi: Option<(u32, u32, u32, u32)>,
+    /// Fraction of a row/column that must be transparent for it to count as a boundary
+    #[serde(serialize_with = "compact_f32::serialize")]
+    pub transparent_column_ratio: f32,
+    /// Luma difference between neighbouring pixels that counts as a color change
+    pub color_change_delta: u8,
+    /// Fraction of a row/column that must be color changes for it to count as a boundary
+    #[serde(serialize_with = "compact_f32::serialize")]
+    pub color_change_ratio: f32,
+    /// Channel the grayscale image that boundary detection works on is taken from
+    pub luma_source: LumaSource,
+    /// Output file name (without extension) for each frame; see `format_frame_filename`
+    pub filename_template: String,
+    /// Order in which frames are numbered
+    pub frame_order: FrameOrder,
+    /// Number given to the first frame of each sheet, e.g. 0 for engines
+    /// that count from zero
+    pub frame_index_start: u32,
+    /// Whether to write an animated GIF of each sheet's frames
+    pub preview_gif: bool,
+    /// Playback speed of the preview GIF
+    pub gif_fps: u32,
+    /// Whether to write all of each sheet's frames side by side in one PNG, for review
+    pub contact_sheet: bool,
+    /// Frames per row of the contact sheet; a near-square grid when unset
+    pub contact_sheet_columns: Option<u32>,
+    /// Where to write a machine-readable run summary
+    pub summary_json: Option<PathBuf>,
+    /// Fraction of a candidate frame's pixels that must be non-transparent
+    /// for it to be kept
+    #[serde(serialize_with = "compact_f32::serialize")]
+    pub content_threshold: f32,
+    /// Fewest non-transparent pixels a frame must hold to be kept; when set,
+    /// it replaces the `min_sprite_size` check on frame width and height
+    pub min_content_pixels: Option<u32>,
+    /// Pixels with alpha at or below this count as transparent when checking
+    /// content, trimming and finding gaps on sheets with an alpha channel
+    pub alpha_threshold: u8,
+    /// Transparent border added around each saved frame
+    pub frame_padding: u32,
+    /// Center every saved frame on a transparent square canvas of this size
+    pub uniform_frame_size: Option<u32>,
+    /// Save frames with premultiplied alpha, for renderers that expect it
+    pub premultiply_alpha: bool,
+    /// Known chroma-key background color; skips background auto-detection
+    #[serde(with = "hex_color")]
+    pub background_key: Option<Rgba<u8>>,
+    /// Palette slot of the background in indexed PNGs (or gray level in
+    /// 8-bit grayscale sheets); its exact color is keyed out per sheet
+    pub background_index: Option<u8>,
+    /// File name endings, such as `_n`, that mark a sheet as a normal map.
+    /// Normal maps are cut without background removal or trimming, along the
+    /// frames of the sheet with the same name minus the ending if there is one.
+    pub normal_map_suffixes: Vec<String>,
+    /// Which background-colored pixels get removed
+    pub background_mode: BackgroundMode,
+    /// How many background colors to detect, for checkerboard or two-tone
+    /// backgrounds; pixels matching any of them are background
+    pub background_color_count: usize,
+    /// How the distance between a pixel and the background color is measured
+    /// against `background_tolerance`
+    pub color_distance: ColorMetric,
+    /// Also require a pixel's alpha to be within `background_tolerance` of
+    /// the background's, so semi-transparent sprite edges in the background
+    /// color are kept
+    pub background_match_alpha: bool,
+    /// Where the background color is sampled; when unset, color detection
+    /// uses the corners and the grayscale fallback pass the whole image
+    pub background_strategy: Option<BackgroundStrategy>,
+    /// Color written over removed background pixels instead of transparency
+    #[serde(with = "hex_color")]
+    pub background_replacement: Option<Rgba<u8>>,
+    /// Color of the lines separating frames, when it differs from the
+    /// background. Lines of it count as gaps, and its pixels are removed with
+    /// the background.
+    #[serde(with = "hex_color")]
+    pub gutter_color: Option<Rgba<u8>>,
+    /// Take thin lines of a uniform color other than the background that
+    /// cross the whole sheet as gutters, when `gutter_color` isn't given. The
+    /// color is looked for once per sheet and only used to find boundaries;
+    /// it isn't removed from the frames.
+    pub detect_gutter: bool,
+    /// Show every image and detection step instead of a progress bar. The
+    /// steps are logged at debug level; the command line turns that on.
+    pub verbose: bool,
+    /// Time decoding, detection, background removal and encoding for each
+    /// sheet and report them in a table at the end of the run
+    pub timings: bool,
+    /// Most worker threads a run may use; `None` means one per logical CPU
+    pub jobs: Option<usize>,
+    /// Most sheets a run decodes and cuts at once, bounding how many sheet
+    /// images are held in memory together; `None` means one per worker thread
+    pub max_inflight: Option<usize>,
+    /// Whether frames are saved one file each or re-packed into one atlas
+    pub pack_mode: PackMode,
+    /// Largest width and height a packed atlas may grow to
+    pub atlas_size: u32,
+    /// Skip saving frames that repeat an earlier frame of the same sheet
+    pub dedup: bool,
+    /// Largest per-channel difference at which two frames still count as
+    /// duplicates; 0 only matches identical pixels
+    pub dedup_tolerance: u8,
+    /// Also count a frame as a duplicate when it is an earlier frame rotated
+    /// by 90, 180 or 270 degrees or flipped, recording the transform in the
+    /// duplicates file
+    pub dedup_transforms: bool,
+    /// Integer factor to enlarge saved frames by, with nearest-neighbor sampling
+    pub scale: Option<u32>,
+    /// Integer divisor to shrink saved frames by, with nearest-neighbor sampling
+    pub scale_down: Option<u32>,
+    /// Image format saved frames are encoded in
+    pub output_format: OutputFormat,
+    /// Quality (1-100) of lossy output formats, trading file size against
+    /// fidelity; the encoder's default when unset
+    pub output_quality: Option<u8>,
+    /// Skip sheets whose outputs from an earlier run with the same settings
+    /// are newer than the sheet itself
+    pub incremental: bool,
+    /// Keep running after the first pass and cut sheets again whenever they
+    /// change or new ones appear; implies `incremental`
+    pub watch: bool,
+    /// Boundaries at most this many pixels apart are collapsed into one
+    /// during boundary detection; 0 keeps every boundary
+    pub boundary_merge_distance: u32,
+    /// Also write `<sheet>_debug.png` with the detected frames outlined and numbered
+    pub debug_overlay: bool,
+    /// Take frame rects from a TexturePacker or Aseprite `<sheet>.json`
+    /// next to the sheet, when there is one, instead of detecting them.
+    /// An explicit `grid` or `strip_count` takes precedence, and a file that
+    /// can't be used is skipped with a warning.
+    pub read_atlas_files: bool,
+    /// Also write `<sheet>_bevy.rs`, a Bevy `TextureAtlasLayout` for the sheet
+    pub export_bevy: bool,
+    /// Sheets where detection finds more frames than this are skipped
+    /// without writing anything
+    pub max_frames: Option<usize>,
+}
+
+/// Image format for saved frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    #[default]
+    Png,
+    /// Lossless WebP, keeping the alpha channel
+    Webp,
+    /// JPEG has no alpha channel, so it only suits frames without transparency
+    Jpeg,
+    Bmp,
+}
+
+impl OutputFormat {
+    /// File extension for saved frames, without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Webp => "webp",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Bmp => "bmp",
+        }
+    }
+
+    /// Whether transparent pixels survive encoding
+    pub fn supports_alpha(self) -> bool {
+        self != OutputFormat::Jpeg
+    }
+
+    /// Whether the format is lossy and so takes `output_quality`; the others
+    /// are always written lossless
+    pub fn takes_quality(self) -> bool {
+        self == OutputFormat::Jpeg
+    }
+
+    /// `img` in a color type this format can store, keeping its own color
+    /// type and bit depth where possible and only dropping what doesn't fit
+    fn encodable(self, img: &DynamicImage) -> std::borrow::Cow<'_, DynamicImage> {
+        let color = img.color();
+        let bytes_per_channel = color.bytes_per_pixel() / color.channel_count();
+        // Only PNG takes 16-bit channels, and nothing here takes floats
+        let depth_fits = bytes_per_channel == 1 || (self == OutputFormat::Png && bytes_per_channel == 2);
+        if depth_fits && (self.supports_alpha() || !color.has_alpha()) {
+            return std::borrow::Cow::Borrowed(img);
+        }
+
+        let gray = matches!(color, image::ColorType::L8 | image::ColorType::La8
+                                 | image::ColorType::L16 | image::ColorType::La16);
+        std::borrow::Cow::Owned(match (gray, self.supports_alpha() && color.has_alpha()) {
+            (true, true) => DynamicImage::ImageLumaA8(img.to_luma_alpha8()),
+            (true, false) => DynamicImage::ImageLuma8(img.to_luma8()),
+            (false, true) => DynamicImage::ImageRgba8(img.to_rgba8()),
+            (false, false) => DynamicImage::ImageRgb8(img.to_rgb8()),
+        })
+    }
+
+    fn image_format(self) -> image::ImageFormat {
+        match self {
+            OutputFormat::Png => image::ImageFormat::Png,
+            OutputFormat::Webp => image::ImageFormat::WebP,
+            OutputFormat::Jpeg => image::ImageFormat::Jpeg,
+            OutputFormat::Bmp => image::ImageFormat::Bmp,
+        }
+    }
+}
+
+/// How extracted frames are written out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PackMode {
+    /// One image file per frame
+    #[default]
+    None,
+    /// Shelf-pack every frame of a sheet into `<sheet>_packed.<ext>`, with
+    /// the packed rects in `<sheet>_packed.json`
+    Shelf,
+}
+
+/// Which background-colored pixels `remove_background` clears
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackgroundMode {
+    /// Every pixel matching the background color
+    #[default]
+    ColorMatch,
+    /// Only matching pixels connected to the image border, so enclosed
+    /// regions of the same color inside a sprite survive
+    FloodFill,
+}
+
+/// How far a pixel's color is from the background color, compared against
+/// the background tolerance (0-255)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorMetric {
+    /// The largest difference of any one RGB channel
+    #[default]
+    PerChannel,
+    /// Straight-line distance in RGB space, so a pixel that differs in
+    /// several channels at once counts as further away
+    RgbEuclidean,
+    /// CIE76 delta E in L*a*b* space, which follows perceived difference;
+    /// the tolerance is scaled so that 255 means a delta E of 100
+    CielabDeltaE,
+}
+
+/// Which channel the grayscale image used to find frame boundaries is taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LumaSource {
+    /// Perceived brightness, weighting the RGB channels
+    #[default]
+    Luminance,
+    /// The red channel alone
+    Red,
+    /// The green channel alone
+    Green,
+    /// The blue channel alone
+    Blue,
+    /// The brightest of the three RGB channels
+    Max,
+    /// The alpha channel, for sheets whose sprites only stand out by opacity
+    Alpha,
+}
+
+impl LumaSource {
+    /// 16-bit grayscale copy of an image taken from this source
+    pub fn gray16(self, img: &DynamicImage) -> Image<image::Luma<u16>> {
+        let channel: fn(&image::Rgba<u16>) -> u16 = match self {
+            LumaSource::Luminance => return img.to_luma16(),
+            LumaSource::Red => |pixel| pixel[0],
+            LumaSource::Green => |pixel| pixel[1],
+            LumaSource::Blue => |pixel| pixel[2],
+            LumaSource::Max => |pixel| pixel[0].max(pixel[1]).max(pixel[2]),
+            LumaSource::Alpha => |pixel| pixel[3],
+        };
+        let rgba_img = img.to_rgba16();
+        Image::from_fn(rgba_img.width(), rgba_img.height(), |x, y| image::Luma([channel(rgba_img.get_pixel(x, y))]))
+    }
+
+    /// 8-bit grayscale copy of an image taken from this source
+    pub fn gray8(self, img: &DynamicImage) -> Image<image::Luma<u8>> {
+        match self {
+            LumaSource::Luminance => img.to_luma8(),
+            _ => DynamicImage::ImageLuma16(self.gray16(img)).to_luma8(),
+        }
+    }
+}
+
+/// Which pixels are sampled to guess a sheet's background color; the most
+/// common sampled value wins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackgroundStrategy {
+    /// 10x10 blocks in the four corners; suits sheets with a margin. Before
+    /// strategies existed only the top-left block was sampled, so sheets
+    /// whose other corners disagree may pick a different color than they did
+    Corners,
+    /// Every 4th pixel of the whole image; suits sparse sheets where the
+    /// background dominates but the corners may be covered
+    GlobalMode,
+    /// The full 1px border; suits tightly packed sheets whose corners are
+    /// sprite art but whose edges are mostly background
+    EdgePixels,
+}
+
+/// Order in which detected frames are numbered and written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FrameOrder {
+    /// Left to right, then top to bottom
+    #[default]
+    RowMajor,
+    /// Top to bottom, then left to right (for vertical strips)
+    ColumnMajor,
+}
+
+/// Direction an animation strip runs in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Orientation {
+    /// Frames side by side, left to right
+    #[default]
+    Horizontal,
+    /// Frames stacked top to bottom
+    Vertical,
+}
+
+/// Format of the per-sheet atlas file describing where each frame came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AtlasFormat {
+    /// Don't write an atlas file
+    #[default]
+    None,
+    /// TexturePacker "JSON (Hash)", as read by Phaser and PixiJS
+    TexturePackerHash,
+    /// Sparrow `<TextureAtlas>` XML, as read by Starling, OpenFL and HaxeFlixel
+    SparrowXml,
+}
+
+/// What to do with a frame file that already exists, e.g. one edited by
+/// hand since the last run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverwritePolicy {
+    /// Replace it with the newly cut frame
+    #[default]
+    Overwrite,
+    /// Leave it as it is and count it as kept
+    Skip,
+    /// Fail the sheet before writing any of its files
+    Error,
+}
+
+/// Format of the run-wide manifest listing every saved frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ManifestFormat {
+    /// `manifest.csv` with a header row, for spreadsheets and tilemap tools
+    Csv,
+    /// `manifest.json`, an array with one object per frame
+    Json,
+}
+
+/// Anchor point written for each frame of an atlas file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PivotMode {
+    /// Middle of the frame
+    Center,
+    /// Middle of the frame's bottom edge, where a character stands
+    BottomCenter,
+    /// Center of mass of the frame's pixels, weighted by alpha
+    Centroid,
+}
+
+/// How sprite frames are located on a sheet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DetectionMode {
+    /// Scan for empty or high-contrast rows and columns between sprites
+    #[default]
+    Boundaries,
+    /// One frame per connected blob of foreground pixels
+    ConnectedComponents,
+    /// Split at the valleys of the per-row and per-column foreground pixel
+    /// counts, for gutters that are sparser than the sprites but not empty
+    Projection,
+    /// Connected blobs grouped into rows (or columns) by position, so
+    /// staggered sprites keep their logical order
+    Clustered,
+}
+
+/// Layout of a spritesheet drawn on a uniform grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GridSpec {
+    /// Divide the sheet into this many columns and rows
+    Count { cols: u32, rows: u32 },
+    /// Cut cells of a fixed pixel size, starting at the top-left corner
+    CellSize { width: u32, height: u32 },
+}
+
+/// Folders processed when no input directories are given
+const DEFAULT_INPUT_DIRS: [&str; 3] = ["Base", "Ships", "Space"];
+
+/// Extensions of the images that are processed
+const IMAGE_EXTENSIONS: [&str; 9] = ["png", "jpg", "jpeg", "bmp", "gif", "tiff", "webp", "tga", "dds"];
+
+const DEFAULT_FILENAME_TEMPLATE: &str = "{name}_frame_{index:03}";
+
+/// Used instead of the default template when every sheet has its own
+/// folder, where repeating the sheet name in each file name is just noise
+const SUBFOLDER_FILENAME_TEMPLATE: &str = "frame_{index:03}";
+
+impl Default for CutterConfig {
+    fn default() -> Self {
+        Self {
+            min_sprite_size: 8,  // Reduced from 16 to catch smaller sprites
+            max_sprite_size: 1024,  // Increased from 512 to handle larger sprites
+            background_tolerance: 20,  // Increased from 10 for better background detection
+            remove_background: true,
+            background_opacity_floor: None,
+            output_dir: "assets2".to_string(),
+            output_relative_to_input: false,
+            overwrite: OverwritePolicy::default(),
+            exec: None,
+            recursive: false,
+            flatten_output: false,
+            subfolder_per_sheet: false,
+            emit_montage: false,
+            input_dirs: Vec::new(),
+            file_list: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            grid: None,
+            strip_count: None,
+            strip_orientation: Orientation::Horizontal,
+            grid_margin: 0,
+            grid_spacing: 0,
+            detection_mode: DetectionMode::default(),
+            merge_overlap: None,
+            merge_adjacent: false,
+            atlas_format: AtlasFormat::default(),
+            pivot: None,
+            emit_colors: false,
+            manifest: None,
+            compare: None,
+            trim: false,
+            trim_margin: 0,
+            autocrop: false,
+            auto_grid: false,
+            include_edge_frames: false,
+            refine_boundaries: false,
+            adaptive_retry: false,
+            roi: None,
+            transparent_column_ratio: 0.6,  // Reduced from 0.8
+            color_change_delta: 30,  // Reduced from 50
+            color_change_ratio: 0.2,  // Reduced from 0.3
+            luma_source: LumaSource::default(),
+            filename_template: DEFAULT_FILENAME_TEMPLATE.to_string(),
+            frame_index_start: 1,
+            frame_order: FrameOrder::default(),
+            preview_gif: false,
+            gif_fps: 10,
+            contact_sheet: false,
+            contact_sheet_columns: None,
+            summary_json: None,
+            content_threshold: 0.02,  // Reduced from 0.05
+            min_content_pixels: None,
+            alpha_threshold: 10,
+            frame_padding: 0,
+            uniform_frame_size: None,
+            premultiply_alpha: false,
+            background_key: None,
+            background_index: None,
+            normal_map_suffixes: Vec::new(),
+            background_mode: BackgroundMode::default(),
+            background_color_count: 1,
+            color_distance: ColorMetric::default(),
+            background_match_alpha: false,
+            background_strategy: None,
+            background_replacement: None,
+            gutter_color: None,
+            detect_gutter: false,
+            verbose: false,
+            timings: false,
+            jobs: None,
+            max_inflight: None,
+            pack_mode: PackMode::default(),
+            atlas_size: 2048,
+            dedup: false,
+            dedup_tolerance: 0,
+            dedup_transforms: false,
+            scale: None,
+            scale_down: None,
+            output_format: OutputFormat::default(),
+            output_quality: None,
+            incremental: false,
+            watch: false,
+            boundary_merge_distance: 0,
+            debug_overlay: false,
+            read_atlas_files: true,
+            export_bevy: false,
+            max_frames: None,
+        }
+    }
+}
+
+impl CutterConfig {
+    /// Load settings from a TOML file, or JSON if the name ends in `.json`.
+    /// Settings missing from the file keep their defaults.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .map_err(|source| CutterError::ReadConfig { path: path.to_path_buf(), source })?;
+        let parsed = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")) {
+            serde_json::from_str(&contents).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(&contents).map_err(|e| e.to_string())
+        };
+        parsed.map_err(|message| CutterError::ParseConfig { path: path.to_path_buf(), message })
+    }
+
+    /// Render the settings as TOML, in the format `from_file` reads
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("config is always representable as TOML")
+    }
+
+    /// Reject settings that can never produce a frame
+    pub fn validate(&self) -> Result<()> {
+        if self.min_sprite_size > self.max_sprite_size {
+            invalid_config!("minimum sprite size ({}) is larger than maximum sprite size ({})",
+                  self.min_sprite_size, self.max_sprite_size);
+        }
+        match self.grid {
+            Some(GridSpec::Count { cols, rows }) if cols == 0 || rows == 0 => {
+                invalid_config!("grid must have at least one column and one row");
+            }
+            Some(GridSpec::CellSize { width, height }) if width == 0 || height == 0 => {
+                invalid_config!("grid cells must be at least 1x1 pixels");
+            }
+            _ => {}
+        }
+        for (name, ratio) in [("transparent column ratio", self.transparent_column_ratio),
+                              ("color change ratio", self.color_change_ratio),
+                              ("content threshold", self.content_threshold)] {
+            if !(0.0..=1.0).contains(&ratio) {
+                invalid_config!("{} must be between 0 and 1, got {}", name, ratio);
+            }
+        }
+        if self.min_content_pixels == Some(0) {
+            invalid_config!("minimum content pixels must be at least 1");
+        }
+        if self.exec.as_ref().is_some_and(|command| command.trim().is_empty()) {
+            invalid_config!("the exec command must not be empty");
+        }
+        if self.contact_sheet_columns == Some(0) {
+            invalid_config!("a contact sheet needs at least one column");
+        }
+        if self.gutter_color.is_some() && self.detect_gutter {
+            invalid_config!("the gutter color can't be both given and detected");
+        }
+        if self.contact_sheet_columns.is_some() && !self.contact_sheet {
+            invalid_config!("contact sheet columns only apply to contact sheets; use them with --contact-sheet");
+        }
+        if self.gif_fps == 0 {
+            invalid_config!("GIF frame rate must be at least 1 fps");
+        }
+        if self.file_list.is_some() && !self.input_dirs.is_empty() {
+            invalid_config!("a file list can't be combined with input folders or sheets");
+        }
+        if let Some(overlap) = self.merge_overlap {
+            if !(0.0..=1.0).contains(&overlap) {
+                invalid_config!("merge overlap must be between 0 and 1, got {}", overlap);
+            }
+        }
+        if let Some(floor) = self.background_opacity_floor {
+            if !(0.0..=1.0).contains(&floor) {
+                invalid_config!("background opacity floor must be between 0 and 1, got {}", floor);
+            }
+        }
+        let sample_frame = SpriteFrame::new(0, 0, 1, 1);
+        format_frame_filename(&self.filename_template, "sheet", 1, 0, &sample_frame)
+            .map_err(|e| CutterError::InvalidConfig(format!("invalid filename template '{}': {}", self.filename_template, e)))?;
+        // Grid cells are only known with --grid or --auto-grid
+        let names_cells = ["{row", "{col"].iter().any(|key| self.filename_template.contains(key));
+        if names_cells && self.grid.is_none() && !self.auto_grid {
+            invalid_config!("{{row}} and {{col}} name grid cells; use them with --grid or --auto-grid");
+        }
+        if self.auto_grid {
+            // The grid is inferred from the boundaries the main pass finds, on the whole sheet
+            if self.detection_mode != DetectionMode::Boundaries {
+                invalid_config!("auto grid infers the grid from boundary detection; \
+                                 use it with --detection-mode boundaries");
+            }
+            if self.autocrop {
+                invalid_config!("auto grid and autocrop can't be used together");
+            }
+        }
+        if matches!(self.roi, Some((_, _, width, height)) if width == 0 || height == 0) {
+            invalid_config!("region of interest must be at least 1x1 pixels");
+        }
+        if self.strip_count == Some(0) {
+            invalid_config!("a strip must have at least one frame");
+        }
+        if self.strip_count.is_some() && self.grid.is_some() {
+            invalid_config!("strip count and grid can't be used together");
+        }
+        if self.strip_orientation != Orientation::Horizontal && self.strip_count.is_none() {
+            invalid_config!("strip orientation only applies to strips; use it with --strip-count");
+        }
+        if self.background_color_count == 0 {
+            invalid_config!("background color count must be at least 1");
+        }
+        if self.jobs == Some(0) {
+            invalid_config!("jobs must be at least 1");
+        }
+        if self.max_inflight == Some(0) {
+            invalid_config!("max inflight must be at least 1 sheet");
+        }
+        if self.max_frames == Some(0) {
+            invalid_config!("frame limit must be at least 1");
+        }
+        if self.background_key.is_some() && self.background_index.is_some() {
+            invalid_config!("background key and background index can't be used together");
+        }
+        if self.normal_map_suffixes.iter().any(|suffix| suffix.is_empty()) {
+            invalid_config!("normal map suffixes can't be empty");
+        }
+        glob_set(&self.include)?;
+        glob_set(&self.exclude)?;
+        if self.output_dir.trim().is_empty() {
+            invalid_config!("output directory must not be empty");
+        }
+        if self.manifest.is_some() && self.output_per_input() {
+            invalid_config!("the manifest is written into a single output directory and can't be combined with \
+                             --output-relative-to-input");
+        }
+        match (self.scale, self.scale_down) {
+            (Some(0), _) | (_, Some(0)) => invalid_config!("scale factors must be at least 1"),
+            (Some(_), Some(_)) => invalid_config!("scale and scale-down can't be used together"),
+            (Some(_), None) | (None, Some(_)) => {
+                // Atlas rects are measured in source sheet pixels
+                if self.atlas_format != AtlasFormat::None || self.pack_mode != PackMode::None {
+                    invalid_config!("scaled frames can't be described by an atlas file");
+                }
+            }
+            (None, None) => {}
+        }
+        if self.dedup_tolerance > 0 && !self.dedup {
+            invalid_config!("dedup tolerance only applies when deduplicating; use it with --dedup");
+        }
+        if self.dedup_transforms {
+            if !self.dedup {
+                invalid_config!("dedup transforms only apply when deduplicating; use them with --dedup");
+            }
+            // Atlas entries can only point at the original frame's pixels as they are
+            if self.atlas_format != AtlasFormat::None || self.pack_mode != PackMode::None {
+                invalid_config!("rotated or flipped duplicates can't be described by an atlas file");
+            }
+        }
+        if self.trim_margin > 0 && !self.trim {
+            invalid_config!("trim margin only applies when trimming; use it with --trim");
+        }
+        if self.pivot.is_some() && self.atlas_format == AtlasFormat::None && self.pack_mode == PackMode::None
+            && !self.export_bevy {
+            invalid_config!("pivot points are written into the atlas file or Bevy layout; use them with \
+                             --atlas-format, --pack or --export-bevy");
+        }
+        let json_atlas = self.atlas_format == AtlasFormat::TexturePackerHash
+            || (self.pack_mode != PackMode::None && self.atlas_format != AtlasFormat::SparrowXml);
+        if self.emit_colors && !json_atlas && self.manifest != Some(ManifestFormat::Json) {
+            invalid_config!("frame colors are written into JSON metadata; use them with --atlas-format texture-packer-hash, \
+                             --pack or --manifest json");
+        }
+        if self.output_quality.is_some_and(|quality| !(1..=100).contains(&quality)) {
+            invalid_config!("output quality must be between 1 and 100");
+        }
+        let clears_to_transparent = self.remove_background
+            && self.background_replacement.is_none_or(|color| color[3] < 255);
+        if !self.output_format.supports_alpha()
+            && (clears_to_transparent || self.frame_padding > 0 || self.uniform_frame_size.is_some()
+                || self.pack_mode != PackMode::None) {
+            invalid_config!("{:?} output can't store transparency; use it with --no-remove-background \
+                             or an opaque --bg-replace color, no padding, no uniform size and no packing",
+                           self.output_format);
+        }
+        if self.uniform_frame_size == Some(0) {
+            invalid_config!("uniform frame size must be at least 1 pixel");
+        }
+        if (self.incremental || self.watch) && self.emit_montage {
+            invalid_config!("montage.txt only lists the sheets processed in a run and can't be combined with incremental runs");
+        }
+        if (self.incremental || self.watch) && self.manifest.is_some() {
+            invalid_config!("the manifest only lists the sheets processed in a run and can't be combined with incremental runs");
+        }
+        if (self.incremental || self.watch) && self.compare.is_some() {
+            invalid_config!("comparing needs every sheet cut in the run and can't be combined with incremental runs");
+        }
+        if self.watch && self.file_list.is_some() {
+            invalid_config!("watch mode watches input folders and sheets and can't be combined with a file list");
+        }
+        if self.pack_mode != PackMode::None {
+            if self.atlas_size == 0 {
+                invalid_config!("atlas size must be at least 1 pixel");
+            }
+            if self.emit_montage {
+                invalid_config!("montage.txt needs one file per frame and can't be combined with packing");
+            }
+            if self.manifest.is_some() {
+                invalid_config!("the manifest needs one file per frame and can't be combined with packing");
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether each input gets its own output directory, resolved against it
+    fn output_per_input(&self) -> bool {
+        self.output_relative_to_input && Path::new(&self.output_dir).is_relative()
+    }
+
+    /// Output directory for the sheets of an input in `input_dir`: an
+    /// absolute `output_dir` as it is, and a relative one resolved against
+    /// `input_dir` with `output_relative_to_input`, or else `current_dir`
+    fn output_root(&self, current_dir: &Path, input_dir: &Path) -> PathBuf {
+        let base = if self.output_relative_to_input { input_dir } else { current_dir };
+        base.join(&self.output_dir)
+    }
+}
+
+/// Represents a detected sprite frame
+#[derive(Debug, Clone)]
+pub struct SpriteFrame {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Row and column (both from 0) of the grid cell the frame was cut
+    /// from, for frames sliced on a configured or inferred grid
+    pub cell: Option<(u32, u32)>,
+}
+
+impl SpriteFrame {
+    /// A frame at `x`, `y` that wasn't cut from a grid cell
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        SpriteFrame { x, y, width, height, cell: None }
+    }
+}
+
+/// Which detection path produced a sheet's frames; the first thing to check
+/// when a sheet was cut badly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DetectionMethod {
+    /// Cut on the configured grid
+    Grid,
+    /// Split into the configured number of strip frames
+    Strip,
+    /// One frame per connected blob
+    ConnectedComponents,
+    /// The main pass over empty and high-contrast rows and columns
+    Boundaries,
+    /// The main pass again with relaxed thresholds, after it found at most one frame
+    RelaxedBoundaries,
+    /// Valleys in the foreground pixel counts of rows and columns
+    Projection,
+    /// Connected blobs ordered by the rows or columns they cluster into
+    Clustered,
+    /// Rows and columns of an opaque sheet that are mostly its plain background
+    BackgroundDifference,
+    /// The fallback for horizontal strips, splitting on empty columns
+    HorizontalFallback,
+    /// The fallback for vertical strips, splitting on empty rows
+    VerticalFallback,
+    /// Cut on a grid inferred from evenly spaced boundaries
+    InferredGrid,
+    /// Read from an atlas file next to the sheet
+    AtlasFile,
+    /// One frame per frame of an animated GIF
+    GifFrames,
+    /// Nothing was detected, so the whole image was copied as one sprite
+    SingleSprite,
+}
+
+impl std::fmt::Display for DetectionMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DetectionMethod::Grid => "grid",
+            DetectionMethod::Strip => "strip",
+            DetectionMethod::ConnectedComponents => "connected components",
+            DetectionMethod::Boundaries => "boundaries",
+            DetectionMethod::RelaxedBoundaries => "relaxed boundaries",
+            DetectionMethod::Projection => "projection",
+            DetectionMethod::Clustered => "clustered",
+            DetectionMethod::BackgroundDifference => "background difference",
+            DetectionMethod::HorizontalFallback => "horizontal fallback",
+            DetectionMethod::VerticalFallback => "vertical fallback",
+            DetectionMethod::InferredGrid => "inferred grid",
+            DetectionMethod::AtlasFile => "atlas file",
+            DetectionMethod::GifFrames => "gif frames",
+            DetectionMethod::SingleSprite => "single sprite",
+        })
+    }
+}
+
+impl DetectionMethod {
+    /// Whether the frames were guessed from the pixels, rather than laid
+    /// out by a grid, strip, atlas file or animation
+    pub fn is_heuristic(self) -> bool {
+        matches!(self, DetectionMethod::ConnectedComponents | DetectionMethod::Boundaries
+                     | DetectionMethod::RelaxedBoundaries | DetectionMethod::Projection | DetectionMethod::Clustered
+                     | DetectionMethod::BackgroundDifference | DetectionMethod::HorizontalFallback
+                     | DetectionMethod::VerticalFallback)
+    }
+}
+
+/// Frames found in a sheet, together with the path that found them
+#[derive(Debug, Clone)]
+pub struct Detection {
+    pub frames: Vec<SpriteFrame>,
+    pub method: DetectionMethod,
+}
+
+/// A frame that has been written to disk
+#[derive(Debug, Clone)]
+struct SavedFrame {
+    /// Output file name, relative to the output directory
+    filename: String,
+    /// Where the frame was cut from in the source sheet
+    frame: SpriteFrame,
+    /// Offset of the saved image within `frame` once transparent borders are trimmed
+    trim_x: u32,
+    trim_y: u32,
+    /// Size of the frame's pixels in the saved image, excluding padding
+    width: u32,
+    height: u32,
+    /// Transparent border added on every side of the saved image
+    padding: u32,
+    /// With a uniform frame size, where the padded frame sits on that canvas
+    uniform: Option<UniformCanvas>,
+    /// With dedup, the file name of the earlier identical frame; this
+    /// frame's own file was not written
+    duplicate_of: Option<String>,
+    /// With `dedup_transforms`, how `duplicate_of` is rotated or flipped to
+    /// give this frame; `None` when it is the same
+    duplicate_transform: Option<FrameTransform>,
+    /// Anchor point as a fraction of `sourceSize`, when one was asked for
+    pivot: Option<(f32, f32)>,
+    /// Average and dominant color of the frame's visible pixels, when asked for
+    colors: Option<(Rgba<u8>, Rgba<u8>)>,
+    /// Whether the frame's file already existed and was left as it was
+    kept: bool,
+    /// With `background_opacity_floor`, the opaque share of a frame whose
+    /// background was kept because it fell below the floor
+    kept_background: Option<f32>,
+    /// With background removal, the first of the colors cleared from the frame
+    background: Option<Rgba<u8>>,
+}
+
+/// Square canvas a saved frame was centered on
+#[derive(Debug, Clone, Copy)]
+struct UniformCanvas {
+    size: u32,
+    x: u32,
+    y: u32,
+}
+
+impl SavedFrame {
+    /// Top-left corner of the frame's own pixels in the saved image
+    fn content_offset(&self) -> (u32, u32) {
+        let (x, y) = self.uniform.map_or((0, 0), |canvas| (canvas.x, canvas.y));
+        (x + self.padding, y + self.padding)
+    }
+
+    /// Where the frame's own pixels sit in its untrimmed source size, and
+    /// that size, as written to `spriteSourceSize` and `sourceSize`
+    fn source_rect(&self) -> (u32, u32, (u32, u32)) {
+        match self.uniform {
+            Some(canvas) => {
+                let (x, y) = self.content_offset();
+                (x, y, (canvas.size, canvas.size))
+            }
+            None => (self.trim_x + self.padding, self.trim_y + self.padding,
+                     (self.frame.width + 2 * self.padding, self.frame.height + 2 * self.padding)),
+        }
+    }
+
+    /// Anchor point for `mode` as a fraction of the source size. `centroid`
+    /// is the center of mass within the frame's own pixels; without one
+    /// (an empty frame) their middle is used.
+    fn pivot_for(&self, mode: PivotMode, centroid: Option<(f32, f32)>) -> (f32, f32) {
+        match mode {
+            PivotMode::Center => (0.5, 0.5),
+            PivotMode::BottomCenter => (0.5, 1.0),
+            PivotMode::Centroid => {
+                let (x, y, (width, height)) = self.source_rect();
+                let (centroid_x, centroid_y) = centroid.unwrap_or((self.width as f32 / 2.0, self.height as f32 / 2.0));
+                ((x as f32 + centroid_x) / width as f32, (y as f32 + centroid_y) / height as f32)
+            }
+        }
+    }
+
+    /// Whether trimming removed anything from the frame
+    fn is_trimmed(&self) -> bool {
+        self.width != self.frame.width || self.height != self.frame.height
+    }
+
+    /// The file on disk holding this frame's pixels
+    fn file(&self) -> &str {
+        self.duplicate_of.as_deref().unwrap_or(&self.filename)
+    }
+
+    /// Warning for the sheet's report when the frame's background was kept
+    /// because of `background_opacity_floor`
+    fn kept_background_warning(&self) -> Option<String> {
+        self.kept_background.map(|ratio| {
+            format!("kept the background of {}, as only {:.1}% of it is opaque", self.filename, ratio * 100.0)
+        })
+    }
+}
+
+/// Rotation or flip turning one frame into another
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum FrameTransform {
+    /// Rotated 90 degrees clockwise
+    #[serde(rename = "rotate-90")]
+    Rotate90,
+    #[serde(rename = "rotate-180")]
+    Rotate180,
+    #[serde(rename = "rotate-270")]
+    Rotate270,
+    /// Mirrored left to right
+    #[serde(rename = "flip-horizontal")]
+    FlipHorizontal,
+    /// Mirrored top to bottom
+    #[serde(rename = "flip-vertical")]
+    FlipVertical,
+}
+
+impl FrameTransform {
+    const ALL: [FrameTransform; 5] = [FrameTransform::Rotate90, FrameTransform::Rotate180, FrameTransform::Rotate270,
+                                      FrameTransform::FlipHorizontal, FrameTransform::FlipVertical];
+
+    fn apply(self, image: &RgbaImage) -> RgbaImage {
+        match self {
+            FrameTransform::Rotate90 => image::imageops::rotate90(image),
+            FrameTransform::Rotate180 => image::imageops::rotate180(image),
+            FrameTransform::Rotate270 => image::imageops::rotate270(image),
+            FrameTransform::FlipHorizontal => image::imageops::flip_horizontal(image),
+            FrameTransform::FlipVertical => image::imageops::flip_vertical(image),
+        }
+    }
+
+    /// The transform undoing this one
+    fn inverse(self) -> Self {
+        match self {
+            FrameTransform::Rotate90 => FrameTransform::Rotate270,
+            FrameTransform::Rotate270 => FrameTransform::Rotate90,
+            other => other,
+        }
+    }
+}
+
+/// Finds frames that repeat an earlier frame of the same sheet
+struct FrameDeduper {
+    /// Largest per-channel difference that still counts as equal
+    tolerance: u8,
+    /// Whether rotated and flipped copies of a frame count as repeats
+    transforms: bool,
+    /// Frames kept so far, with the file name each was saved as
+    uniques: Vec<(RgbaImage, String)>,
+    /// Exact pixel hash to indices into `uniques`
+    by_hash: HashMap<u64, Vec<usize>>,
+}
+
+impl FrameDeduper {
+    fn new(tolerance: u8) -> Self {
+        Self { tolerance, transforms: false, uniques: Vec::new(), by_hash: HashMap::new() }
+    }
+
+    /// Return the file name of an earlier frame matching `image`, along
+    /// with how that frame is rotated or flipped to give `image` when
+    /// `transforms` is set, or remember `image` as a new unique frame saved
+    /// as `filename`
+    fn check(&mut self, image: &DynamicImage, filename: &str) -> Option<(String, Option<FrameTransform>)> {
+        let pixels = image.to_rgba8();
+        let hash = pixel_hash(&pixels);
+
+        if let Some(index) = self.find(&pixels, hash) {
+            return Some((self.uniques[index].1.clone(), None));
+        }
+        if self.transforms {
+            for transform in FrameTransform::ALL {
+                // Undo the transform on this frame to compare it with the original
+                let candidate = transform.inverse().apply(&pixels);
+                if let Some(index) = self.find(&candidate, pixel_hash(&candidate)) {
+                    return Some((self.uniques[index].1.clone(), Some(transform)));
+                }
+            }
+        }
+
+        self.by_hash.entry(hash).or_default().push(self.uniques.len());
+        self.uniques.push((pixels, filename.to_string()));
+        None
+    }
+
+    /// Index of the unique frame matching `pixels`, whose hash is `hash`
+    fn find(&self, pixels: &RgbaImage, hash: u64) -> Option<usize> {
+        if self.tolerance == 0 {
+            // Confirm the pixels too, so a hash collision can't drop a frame
+            self.by_hash.get(&hash)
+                .and_then(|indices| indices.iter().find(|&&i| self.uniques[i].0 == *pixels))
+                .copied()
+        } else {
+            self.uniques.iter().position(|(unique, _)| images_within_tolerance(unique, pixels, self.tolerance))
+        }
+    }
+}
+
+/// Hash of an image's size and pixels
+fn pixel_hash(pixels: &RgbaImage) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    pixels.dimensions().hash(&mut hasher);
+    pixels.as_raw().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// One saved frame in the run manifest. `x`, `y`, `width` and `height` are
+/// the frame's cell on the source sheet; `trimmed_x` and `trimmed_y` are
+/// where the saved pixels start inside that cell after `trim`.
+#[derive(Debug, Clone, serde::Serialize)]
+struct ManifestRow {
+    source_file: String,
+    output_file: String,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    trimmed_x: u32,
+    trimmed_y: u32,
+    /// With `emit_colors`, only written to the JSON manifest
+    #[serde(skip_serializing_if = "Option::is_none", serialize_with = "hex_color::serialize")]
+    average_color: Option<Rgba<u8>>,
+    #[serde(skip_serializing_if = "Option::is_none", serialize_with = "hex_color::serialize")]
+    dominant_color: Option<Rgba<u8>>,
+}
+
+/// Write the run manifest into `output_root` as `manifest.csv` or `manifest.json`
+fn write_manifest(output_root: &Path, format: ManifestFormat, rows: &[ManifestRow]) -> Result<()> {
+    fs::create_dir_all(output_root)
+        .map_err(|source| CutterError::CreateOutputDir { path: output_root.to_path_buf(), source })?;
+    let (path, contents) = match format {
+        ManifestFormat::Csv => {
+            let mut contents = String::from("source_file,output_file,x,y,width,height,trimmed_x,trimmed_y\n");
+            for row in rows {
+                contents.push_str(&format!("{},{},{},{},{},{},{},{}\n", csv_field(&row.source_file),
+                                           csv_field(&row.output_file), row.x, row.y, row.width, row.height,
+                                           row.trimmed_x, row.trimmed_y));
+            }
+            (output_root.join("manifest.csv"), contents)
+        }
+        ManifestFormat::Json => (output_root.join("manifest.json"), serde_json::to_string_pretty(rows)?),
+    };
+    fs::write(&path, contents).map_err(|source| CutterError::WriteFile { path, source })
+}
+
+/// One frame of an earlier run's `manifest.json`, as much of it as `compare`
+/// looks at
+#[derive(Debug, Clone, serde::Deserialize)]
+struct ReportFrame {
+    source_file: String,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// Read the `manifest.json` of an earlier run for `compare`
+fn read_report(path: &Path) -> Result<Vec<ReportFrame>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|source| CutterError::ReadReport { path: path.to_path_buf(), source })?;
+    serde_json::from_str(&contents)
+        .map_err(|e| CutterError::ParseReport { path: path.to_path_buf(), message: e.to_string() })
+}
+
+/// How each sheet's frames changed between an earlier run's manifest and
+/// this run's, as `<sheet>: <change>` in sheet order. Frames are compared
+/// one by one in frame order; sheets whose frames all match are left out,
+/// as are sheets of the earlier run that this run didn't try to cut, listed
+/// in `attempted`.
+fn compare_frames(previous: &[ReportFrame], current: &[ManifestRow], attempted: &HashSet<String>) -> Vec<String> {
+    let mut sheets = BTreeMap::new();
+    for frame in previous {
+        let (before, _) = sheets.entry(frame.source_file.as_str()).or_insert_with(|| (Vec::new(), Vec::new()));
+        before.push([frame.x, frame.y, frame.width, frame.height]);
+    }
+    for row in current {
+        let (_, after) = sheets.entry(row.source_file.as_str()).or_insert_with(|| (Vec::new(), Vec::new()));
+        after.push([row.x, row.y, row.width, row.height]);
+    }
+    sheets.into_iter()
+        .filter(|(sheet, (_, after))| !after.is_empty() || attempted.contains(*sheet))
+        .filter_map(|(sheet, (before, after))| {
+            let change = if after.is_empty() {
+                format!("no frames this run, {} before", before.len())
+            } else if before.is_empty() {
+                format!("new, {} frame(s)", after.len())
+            } else if before.len() != after.len() {
+                format!("{} frame(s), {} before", after.len(), before.len())
+            } else {
+                let moved = before.iter().zip(&after).filter(|(before, after)| before != after).count();
+                if moved == 0 {
+                    return None;
+                }
+                format!("{} of {} frame rect(s) changed", moved, after.len())
+            };
+            Some(format!("{}: {}", sheet, change))
+        })
+        .collect()
+}
+
+/// A CSV field, quoted when it holds a comma, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// What cutting one sheet of a run saved, with the advice of
+/// [`SpritesheetCutter::suggest_detector`] when it looks badly cut
+enum SheetCut {
+    /// The detected frames, and the detection path that found them
+    Frames(Vec<SavedFrame>, DetectionMethod, Option<String>),
+    /// The whole sheet as one sprite, as no frames were found on it
+    SingleSprite(SavedFrame, Option<String>),
+}
+
+impl SheetCut {
+    /// Frames saved, counting a single-sprite copy as one
+    fn frame_count(&self) -> usize {
+        match self {
+            SheetCut::Frames(saved_frames, ..) => saved_frames.len(),
+            SheetCut::SingleSprite(..) => 1,
+        }
+    }
+}
+
+/// Run `work` on each of `items` on the current thread pool, at most
+/// `limit` at a time, and return the results in the order of `items`.
+///
+/// The limit is a fixed number of workers that each take the next item once
+/// done with the last, rather than a semaphore: a pool thread waiting for a
+/// permit can be the one holding another, when it picked up the waiting job
+/// while joining nested parallel work.
+fn bounded_map<T: Sync, R: Send>(items: &[T], limit: usize, work: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let results: Vec<Mutex<Option<R>>> = items.iter().map(|_| Mutex::new(None)).collect();
+    rayon::scope(|scope| {
+        for _ in 0..limit.min(items.len()) {
+            scope.spawn(|_| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else { break };
+                let result = work(item);
+                *results[index].lock().unwrap() = Some(result);
+            });
+        }
+    });
+    results.into_iter().map(|result| result.into_inner().unwrap().expect("every item was worked on")).collect()
+}
+
+/// `path` with `/` separators, as written into metadata
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|component| match component {
+            // The separators around the root are added by the join below
+            std::path::Component::RootDir => String::new(),
+            _ => component.as_os_str().to_string_lossy().to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Outcome of a whole run, for the final report and `--summary-json`
+#[derive(Debug, Clone, Default)]
+pub struct RunSummary {
+    /// Images that produced output
+    pub processed: usize,
+    /// Images that hit an error
+    pub failed: usize,
+    /// Frames written across all images (a single-sprite copy counts as one)
+    pub frames_total: usize,
+    /// Frames skipped by `--dedup` as repeats of an earlier frame
+    pub frames_deduped: usize,
+    /// Frames whose file already existed and was kept by `OverwritePolicy::Skip`
+    pub frames_kept: usize,
+    /// Images skipped by `--incremental` because their outputs were current
+    pub unchanged: usize,
+    /// Images skipped because they had more frames than `max_frames`
+    pub over_frame_limit: usize,
+    /// How many images each detection path handled
+    pub detection_methods: BTreeMap<DetectionMethod, usize>,
+    /// Input folders that did not exist
+    pub folders_skipped: usize,
+    /// Images named in the `file_list` that did not exist
+    pub files_skipped: usize,
+    /// Suspicious detection results (see
+    /// [`SpritesheetCutter::validate_frames`]) and failed `exec` runs, as
+    /// `<sheet>: <warning>`
+    pub warnings: Vec<String>,
+    /// Time spent in each phase per sheet, in processing order, when
+    /// `timings` is on
+    pub sheet_timings: Vec<(PathBuf, PhaseTimings)>,
+    /// With `compare`, each sheet whose frames differ from the earlier run's,
+    /// as `<sheet>: <change>`
+    pub frame_changes: Vec<String>,
+    /// Sheets whose frames look badly cut, with what might cut them better
+    /// (see [`SpritesheetCutter::suggest_detector`]), as `<sheet>: <suggestion>`
+    pub suggestions: Vec<String>,
+}
+
+impl RunSummary {
+    /// The gist of the run in one line, for quiet mode
+    pub fn one_line(&self) -> String {
+        let mut line = format!("Processed {} image(s) into {} frame(s)", self.processed, self.frames_total);
+        for (count, what) in [(self.failed, "failed"), (self.unchanged, "unchanged"),
+                              (self.over_frame_limit, "over the frame limit"),
+                              (self.frames_kept, "existing frame(s) kept"),
+                              (self.frame_changes.len(), "sheet(s) changed since the compared run")] {
+            if count > 0 {
+                line.push_str(&format!(", {} {}", count, what));
+            }
+        }
+        line
+    }
+
+    /// Write `{processed, failed, frames_total, frames_deduped, unchanged,
+    /// frames_kept, over_frame_limit, detection_methods, warnings,
+    /// suggestions, frame_changes}` as JSON for scripts and CI
+    fn write_json(&self, path: &Path) -> Result<()> {
+        let summary = serde_json::json!({
+            "processed": self.processed,
+            "failed": self.failed,
+            "frames_total": self.frames_total,
+            "frames_deduped": self.frames_deduped,
+            "unchanged": self.unchanged,
+            "frames_kept": self.frames_kept,
+            "over_frame_limit": self.over_frame_limit,
+            "detection_methods": self.detection_methods,
+            "warnings": self.warnings,
+            "suggestions": self.suggestions,
+            "frame_changes": self.frame_changes,
+        });
+        fs::write(path, serde_json::to_string_pretty(&summary)?)
+            .map_err(|source| CutterError::WriteFile { path: path.to_path_buf(), source })?;
+        Ok(())
+    }
+}
+
+/// Time spent in each phase of processing a sheet
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PhaseTimings {
+    /// Reading and decoding the sheet
+    pub decode: Duration,
+    /// Finding the frames
+    pub detection: Duration,
+    /// Removing the background from the frames
+    pub background_removal: Duration,
+    /// Encoding and writing the frames
+    pub encoding: Duration,
+}
+
+/// A phase recorded in [`PhaseTimings`]
+#[derive(Debug, Clone, Copy)]
+enum Phase {
+    Decode,
+    Detection,
+    BackgroundRemoval,
+    Encoding,
+}
+
+impl PhaseTimings {
+    /// Time spent in all phases together
+    pub fn total(&self) -> Duration {
+        self.decode + self.detection + self.background_removal + self.encoding
+    }
+
+    fn add(&mut self, phase: Phase, elapsed: Duration) {
+        *match phase {
+            Phase::Decode => &mut self.decode,
+            Phase::Detection => &mut self.detection,
+            Phase::BackgroundRemoval => &mut self.background_removal,
+            Phase::Encoding => &mut self.encoding,
+        } += elapsed;
+    }
+}
+
+impl std::ops::Add for PhaseTimings {
+    type Output = PhaseTimings;
+
+    fn add(self, other: PhaseTimings) -> PhaseTimings {
+        PhaseTimings {
+            decode: self.decode + other.decode,
+            detection: self.detection + other.detection,
+            background_removal: self.background_removal + other.background_removal,
+            encoding: self.encoding + other.encoding,
+        }
+    }
+}
+
+/// Lines of the `--timings` table: one row per sheet in milliseconds, then
+/// the totals
+fn timings_table(sheets: &[(PathBuf, PhaseTimings)]) -> Vec<String> {
+    let total = sheets.iter().fold(PhaseTimings::default(), |sum, (_, timings)| sum + *timings);
+    let mut rows: Vec<(String, PhaseTimings)> = sheets.iter()
+        .map(|(path, timings)| (path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().to_string(), *timings))
+        .collect();
+    rows.push(("total".to_string(), total));
+
+    let name_width = rows.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0).max("sheet".len());
+    let ms = |duration: Duration| format!("{:.1}", duration.as_secs_f64() * 1000.0);
+    let mut lines = vec![format!("{:<name_width$} {:>10} {:>10} {:>10} {:>10} {:>10}",
+                                 "sheet", "decode", "detect", "background", "encode", "total (ms)")];
+    lines.extend(rows.iter().map(|(name, timings)| {
+        format!("{:<name_width$} {:>10} {:>10} {:>10} {:>10} {:>10}", name, ms(timings.decode), ms(timings.detection),
+                ms(timings.background_removal), ms(timings.encoding), ms(timings.total()))
+    }));
+    lines
+}
+
+/// How often watch mode looks at the input sheets for changes, at most, when
+/// it has to poll them. Each look lists the input folders and reads every
+/// sheet's metadata.
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watch mode waits at least this many times as long as listing the inputs
+/// took between looks, so large trees aren't scanned nonstop
+const WATCH_SCAN_BACKOFF: u32 = 10;
+
+/// How long changed sheets must stay unchanged before watch mode cuts them,
+/// so an art program that saves in several writes triggers one pass
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Modification time and size of a sheet, by which watch mode tells that it
+/// changed
+type SheetStamp = (Option<std::time::SystemTime>, u64);
+
+/// Sheets in `after` that are new or whose modification time or size differs
+/// from `before`. Deleted sheets don't count.
+fn changed_sheets<T: PartialEq>(before: &BTreeMap<PathBuf, T>, after: &BTreeMap<PathBuf, T>) -> Vec<PathBuf> {
+    after.iter()
+        .filter(|(path, stamp)| before.get(*path) != Some(*stamp))
+        .map(|(path, _)| path.clone())
+        .collect()
+}
+
+/// Share of an opaque sheet's border pixels that must match its background
+/// color for boundary detection to split it on that color
+const UNIFORM_BORDER_RATIO: f32 = 0.9;
+
+/// Share of the background samples that each detected background color
+/// after the first must have, so a sprite reaching into a corner isn't
+/// taken for a second background color
+const MIN_BACKGROUND_COLOR_SHARE: f32 = 0.1;
+
+/// File in each output folder where `--incremental` runs record their outputs
+const INCREMENTAL_STATE_FILE: &str = ".cutter-state.json";
+
+/// What earlier incremental runs wrote into one output folder
+#[derive(Debug, Default)]
+struct IncrementalState {
+    /// Fingerprint of the settings the recorded outputs were made with
+    config_hash: String,
+    /// Files written for each source sheet, keyed by the sheet's path
+    /// relative to its input folder
+    outputs: BTreeMap<String, Vec<String>>,
+}
+
+impl IncrementalState {
+    /// Load the state of `output_dir`. A missing or unreadable state file, or
+    /// one written with different settings, yields an empty state so that
+    /// every sheet gets processed again.
+    fn load(output_dir: &Path, config_hash: &str) -> Self {
+        let mut state = Self { config_hash: config_hash.to_string(), outputs: BTreeMap::new() };
+        let Ok(contents) = fs::read_to_string(output_dir.join(INCREMENTAL_STATE_FILE)) else { return state };
+        let Ok(saved) = serde_json::from_str::<serde_json::Value>(&contents) else { return state };
+        if saved["config"] != config_hash {
+            return state;
+        }
+
+        if let Some(sheets) = saved["sheets"].as_object() {
+            for (sheet, files) in sheets {
+                let files = files.as_array().into_iter().flatten()
+                    .filter_map(|file| file.as_str().map(str::to_string))
+                    .collect();
+                state.outputs.insert(sheet.clone(), files);
+            }
+        }
+        state
+    }
+
+    /// Whether every output recorded for `sheet`, found at `image_path`, still
+    /// exists and is at least as new as the sheet
+    fn is_up_to_date(&self, sheet: &str, image_path: &Path, output_dir: &Path) -> bool {
+        let Some(files) = self.outputs.get(sheet) else { return false };
+        let Ok(source_modified) = fs::metadata(image_path).and_then(|meta| meta.modified()) else { return false };
+
+        !files.is_empty() && files.iter().all(|file| {
+            fs::metadata(output_dir.join(file))
+                .and_then(|meta| meta.modified())
+                .is_ok_and(|modified| modified >= source_modified)
+        })
+    }
+
+    fn record(&mut self, sheet: String, files: Vec<String>) {
+        self.outputs.insert(sheet, files);
+    }
+
+    fn save(&self, output_dir: &Path) -> Result<()> {
+        let state = serde_json::json!({
+            "config": self.config_hash,
+            "sheets": self.outputs,
+        });
+        let path = output_dir.join(INCREMENTAL_STATE_FILE);
+        fs::write(&path, serde_json::to_string_pretty(&state)?)
+            .map_err(|source| CutterError::WriteFile { path: path.clone(), source })?;
+        Ok(())
+    }
+}
+
+/// 64-bit FNV-1a hash of `bytes`, stable across Rust releases
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Where a run finds images: an input folder or sheet, or the images of a
+/// file list
+enum RunInput {
+    Path(PathBuf),
+    Listed(Vec<PathBuf>),
+}
+
+/// Main spritesheet cutter structure
+pub struct SpritesheetCutter {
+    config: CutterConfig,
+    /// Input folder the sheets being processed were found in; their
+    /// subfolders carry over into output paths
+    input_root: Option<PathBuf>,
+    /// Sheets of the run that share their folder and file stem with another
+    /// sheet of the run, such as `hero.png` and `hero.jpg`, without extension
+    shared_stems: Arc<HashSet<PathBuf>>,
+    /// Phase times of the sheet being processed, shared with the cutters
+    /// derived from this one
+    timings: Arc<Mutex<PhaseTimings>>,
+    /// Background of the whole sheet, when detection runs on a crop of it
+    sheet_background: Option<SheetBackground>,
+}
+
+/// What the whole sheet says about its background, for detection passes
+/// that only see a crop whose edges may be all sprite
+#[derive(Debug, Clone)]
+struct SheetBackground {
+    /// As `sheet_background_colors` finds them on the whole sheet
+    colors: Vec<Rgba<u8>>,
+    /// As `uniform_background` finds it on the whole sheet
+    uniform: Option<Rgba<u8>>,
+}
+
+impl SpritesheetCutter {
+    /// Create a cutter with the given configuration
+    pub fn new(config: CutterConfig) -> Self {
+        Self { config, input_root: None, shared_stems: Arc::default(), timings: Arc::default(), sheet_background: None }
+    }
+
+    /// Cutter with other settings for a single sheet, keeping the input root
+    fn with_config(&self, config: CutterConfig) -> SpritesheetCutter {
+        SpritesheetCutter {
+            config,
+            input_root: self.input_root.clone(),
+            shared_stems: Arc::clone(&self.shared_stems),
+            timings: Arc::clone(&self.timings),
+            sheet_background: self.sheet_background.clone(),
+        }
+    }
+
+    /// Cutter for `image_files`, the sheets found in `root`
+    fn rooted_at(&self, root: &Path, image_files: &[PathBuf]) -> SpritesheetCutter {
+        let mut stems = HashSet::new();
+        let shared_stems = image_files.iter()
+            .map(|image_path| image_path.with_extension(""))
+            .filter(|stem| !stems.insert(stem.clone()))
+            .collect();
+        SpritesheetCutter {
+            config: self.config.clone(),
+            input_root: Some(root.to_path_buf()),
+            shared_stems: Arc::new(shared_stems),
+            timings: Arc::clone(&self.timings),
+            sheet_background: None,
+        }
+    }
+
+    /// Cutter for one sheet of a run, with phase times of its own so sheets
+    /// cut at the same time aren't timed together
+    fn for_sheet(&self) -> SpritesheetCutter {
+        SpritesheetCutter {
+            config: self.config.clone(),
+            input_root: self.input_root.clone(),
+            shared_stems: Arc::clone(&self.shared_stems),
+            timings: Arc::default(),
+            sheet_background: None,
+        }
+    }
+
+    /// Cutter that detects frames on a crop of `img`, judging the background
+    /// by the whole sheet
+    fn for_crop_of(&self, img: &DynamicImage) -> SpritesheetCutter {
+        let sheet_background = SheetBackground {
+            colors: self.sheet_background_colors(&img.to_rgba8()),
+            uniform: self.uniform_background(img),
+        };
+        SpritesheetCutter { sheet_background: Some(sheet_background), ..self.with_config(self.config.clone()) }
+    }
+
+    /// Run `f`, adding the time it took to `phase` when timings are on
+    fn timed<T>(&self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        if !self.config.timings {
+            return f();
+        }
+        let start = Instant::now();
+        let result = f();
+        self.timings.lock().unwrap().add(phase, start.elapsed());
+        result
+    }
+
+    /// Phase times recorded since the last call
+    fn take_timings(&self) -> PhaseTimings {
+        std::mem::take(&mut *self.timings.lock().unwrap())
+    }
+
+    /// Process all image files in the configured input directories
+    /// (Base, Ships, and Space when none are given), and any input that is
+    /// a single image file
+    ///
+    /// Every image is attempted even if some fail; the run only returns an
+    /// error afterwards, once the summary has been reported.
+    pub fn process_directory(&self) -> Result<RunSummary> {
+        match self.config.jobs {
+            Some(jobs) => rayon::ThreadPoolBuilder::new()
+                .num_threads(jobs)
+                .build()?
+                .install(|| self.process_inputs()),
+            None => self.process_inputs(),
+        }
+    }
+
+    /// Process the inputs like `process_directory`, then keep watching them
+    /// and cut every sheet that is added or modified again. Changes are
+    /// picked up once the files have stopped changing for a moment, so a
+    /// sheet still being saved isn't cut half-written. Later passes are
+    /// incremental, so only the changed sheets are redone.
+    ///
+    /// The input folders and sheets are watched with the system's file
+    /// notifications. Where those can't be had, as on some network drives or
+    /// for an input that doesn't exist yet, or they stop working, changes are
+    /// found by polling instead: listing the inputs and reading each sheet's
+    /// metadata every `WATCH_POLL_INTERVAL`, or less often when that listing
+    /// is slow, as on large recursive trees.
+    ///
+    /// Only returns if the working directory can't be read; failed passes
+    /// are reported and watching goes on.
+    pub fn watch(&self) -> Result<()> {
+        let cutter = self.with_config(CutterConfig { incremental: true, ..self.config.clone() });
+        let pass = || {
+            if let Err(e) = cutter.process_directory() {
+                error!("{}", e);
+            }
+        };
+
+        pass();
+        let mut known = cutter.watched_sheets()?;
+        info!("\nWatching {} sheets for changes (Ctrl+C to stop)...", known.len());
+        let (sender, events) = std::sync::mpsc::channel();
+        match cutter.notify_on_changes(sender) {
+            // Kept alive while its events are read
+            Ok(_debouncer) => {
+                for batch in &events {
+                    if let Err(e) = batch {
+                        warn!("Watching the inputs failed ({}); polling them for changes instead", e);
+                        break;
+                    }
+                    // Events only say that something changed; comparing the
+                    // sheets tells which, and skips writes to other files
+                    known = cutter.cut_changed_sheets(known, &pass)?;
+                }
+            }
+            Err(e) => warn!("Can't watch the inputs ({}); polling them for changes instead", e),
+        }
+        cutter.poll_for_changes(known, &pass)
+    }
+
+    /// Watch every input folder and sheet, sending a batch of events to
+    /// `sender` once the files it names have been left alone for
+    /// `WATCH_DEBOUNCE`
+    fn notify_on_changes(&self, sender: std::sync::mpsc::Sender<DebounceEventResult>)
+        -> Result<Debouncer<RecommendedWatcher>, notify::Error> {
+        let mut debouncer = new_debouncer(WATCH_DEBOUNCE, sender)?;
+        let mode = if self.config.recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+        let current_dir = std::env::current_dir()?;
+        // An input that doesn't exist yet fails here, leaving it to polling
+        // to notice when it turns up
+        for input in self.watched_inputs() {
+            debouncer.watcher().watch(&current_dir.join(input), mode)?;
+        }
+        Ok(debouncer)
+    }
+
+    /// Look for changed sheets every `WATCH_POLL_INTERVAL`, or less often
+    /// when listing the inputs is slow, cutting them once they settle
+    fn poll_for_changes(&self, mut known: BTreeMap<PathBuf, SheetStamp>, pass: &dyn Fn()) -> Result<()> {
+        let mut scan_time = Duration::ZERO;
+        loop {
+            std::thread::sleep(WATCH_POLL_INTERVAL.max(scan_time * WATCH_SCAN_BACKOFF));
+            let start = Instant::now();
+            let current = self.watched_sheets()?;
+            scan_time = start.elapsed();
+            known = if changed_sheets(&known, &current).is_empty() {
+                current
+            } else {
+                self.cut_changed_sheets(known, pass)?
+            };
+        }
+    }
+
+    /// Wait for the sheets to stop changing, then run `pass` if any differ
+    /// from `known`. Returns the sheets the pass started from, so sheets
+    /// saved while it runs are picked up next time.
+    fn cut_changed_sheets(&self, known: BTreeMap<PathBuf, SheetStamp>, pass: &dyn Fn())
+        -> Result<BTreeMap<PathBuf, SheetStamp>> {
+        // Wait for rapid successive writes to settle
+        let mut current = self.watched_sheets()?;
+        loop {
+            std::thread::sleep(WATCH_DEBOUNCE);
+            let settled = self.watched_sheets()?;
+            if settled == current {
+                break;
+            }
+            current = settled;
+        }
+
+        let changed = changed_sheets(&known, &current);
+        if !changed.is_empty() {
+            let names: Vec<_> = changed.iter().map(|path| path.file_name().unwrap().to_string_lossy()).collect();
+            info!("\n=== Changed: {} ===", names.join(", "));
+            pass();
+        }
+        Ok(current)
+    }
+
+    /// The input folders and files watch mode looks at, relative to the
+    /// working directory
+    fn watched_inputs(&self) -> Vec<PathBuf> {
+        if self.config.input_dirs.is_empty() {
+            DEFAULT_INPUT_DIRS.iter().map(PathBuf::from).collect()
+        } else {
+            self.config.input_dirs.clone()
+        }
+    }
+
+    /// Every sheet `process_directory` would pick up, with its modification
+    /// time and size
+    fn watched_sheets(&self) -> Result<BTreeMap<PathBuf, SheetStamp>> {
+        let current_dir = std::env::current_dir()
+            .map_err(CutterError::CurrentDir)?;
+
+        let mut sheets = BTreeMap::new();
+        for input in self.watched_inputs() {
+            let path = current_dir.join(input);
+            let image_files = if path.is_file() {
+                vec![path]
+            } else if path.is_dir() {
+                // Unreadable folders are reported by the passes themselves
+                self.find_image_files(&path, &self.config.output_root(&current_dir, &path)).unwrap_or_default()
+            } else {
+                continue;
+            };
+            for image_path in image_files {
+                let stamp = fs::metadata(&image_path)
+                    .map(|metadata| (metadata.modified().ok(), metadata.len()))
+                    .unwrap_or((None, 0));
+                sheets.insert(image_path, stamp);
+            }
+        }
+        Ok(sheets)
+    }
+
+    /// Body of `process_directory`, run inside its thread pool
+    fn process_inputs(&self) -> Result<RunSummary> {
+        let current_dir = std::env::current_dir()
+            .map_err(CutterError::CurrentDir)?;
+        
+        let mut summary = RunSummary::default();
+        let inputs: Vec<RunInput> = match &self.config.file_list {
+            Some(list_path) => vec![RunInput::Listed(self.read_file_list(list_path, &current_dir, &mut summary)?)],
+            None if self.config.input_dirs.is_empty() => {
+                DEFAULT_INPUT_DIRS.iter().map(|folder| RunInput::Path(PathBuf::from(folder))).collect()
+            }
+            None => self.config.input_dirs.iter().cloned().map(RunInput::Path).collect(),
+        };
+        let mut manifest = Vec::new();
+        // Sheets this run tried to cut, as `source_file` in the manifest
+        let mut attempted = HashSet::new();
+        // Read before cutting anything, so a bad path fails the run early
+        let previous_report = self.config.compare.as_deref().map(read_report).transpose()?;
+
+        for input in inputs {
+            let (folder_name, output_root, output_path, image_files, cutter) = match input {
+                // Listed images go into the output directory below the same
+                // folders they are in below the working directory
+                RunInput::Listed(image_files) => {
+                    info!("\n=== Processing listed images ===");
+                    let output_root = self.config.output_root(&current_dir, &current_dir);
+                    let cutter = self.rooted_at(&current_dir, &image_files);
+                    ("listed images".to_string(), output_root.clone(), output_root, image_files, cutter)
+                }
+                RunInput::Path(folder) => {
+                    let folder_path = current_dir.join(&folder);
+
+                    // A single sheet named directly goes straight into the output
+                    // directory, whatever the include and exclude patterns say
+                    if folder_path.is_file() {
+                        let folder_name = folder_path.file_name().unwrap().to_string_lossy().to_string();
+                        info!("\n=== Processing {} ===", folder_name);
+                        let sheet_folder = folder_path.parent().unwrap();
+                        let output_root = self.config.output_root(&current_dir, sheet_folder);
+                        (folder_name, output_root.clone(), output_root, vec![folder_path.clone()], self.rooted_at(sheet_folder, &[]))
+                    } else if folder_path.is_dir() {
+                        let folder_name = folder_display_name(&folder_path);
+                        info!("\n=== Processing {} folder ===", folder_name);
+                        let output_root = self.config.output_root(&current_dir, &folder_path);
+                        let image_files = self.find_image_files(&folder_path, &output_root)?;
+                        // An output directory inside the folder needs no subfolder named after it
+                        let output_path = if self.config.output_per_input() {
+                            output_root.clone()
+                        } else {
+                            output_root.join(&folder_name)
+                        };
+                        let cutter = self.rooted_at(&folder_path, &image_files);
+                        (folder_name, output_root, output_path, image_files, cutter)
+                    } else {
+                        warn!("Folder '{}' not found, skipping...", folder.display());
+                        summary.folders_skipped += 1;
+                        continue;
+                    }
+                }
+            };
+
+            if image_files.is_empty() {
+                info!("No image files found in the {} directory.", folder_name);
+                continue;
+            }
+
+            // Create output directory for this folder
+            fs::create_dir_all(&output_path)
+                .map_err(|source| CutterError::CreateOutputDir { path: output_path.clone(), source })?;
+
+            info!("Found {} image files to process in {}", image_files.len(), folder_name);
+            // Where this folder's outputs are, relative to the output directory
+            let output_folder = output_path.strip_prefix(&output_root).unwrap_or(Path::new("")).to_path_buf();
+            let mut montage_entries = Vec::new();
+            let progress = self.progress_bar(image_files.len());
+            let mut state = self.config.incremental
+                .then(|| IncrementalState::load(&output_path, &self.config_fingerprint()));
+            // Flattened names can coincide, as for `a_b/x.png` and `a/b_x.png`
+            let mut first_named = HashMap::new();
+            let clashes: HashMap<&PathBuf, &PathBuf> = image_files.iter()
+                .filter(|_| self.config.flatten_output)
+                .filter_map(|image_path| {
+                    let first = *first_named.entry(cutter.sheet_name(image_path)).or_insert(image_path);
+                    (first != image_path).then_some((image_path, first))
+                })
+                .collect();
+
+            let mut pending = Vec::new();
+            for (index, image_path) in image_files.iter().enumerate() {
+                let file_name = image_path.file_name().unwrap().to_string_lossy();
+                attempted.insert(slash_path(image_path.strip_prefix(&current_dir).unwrap_or(image_path)));
+                let up_to_date = state.as_ref()
+                    .is_some_and(|state| state.is_up_to_date(&cutter.source_name(image_path), image_path, &output_path));
+                if up_to_date {
+                    debug!("Skipping {}/{}: {} (unchanged)", index + 1, image_files.len(), file_name);
+                    summary.unchanged += 1;
+                    progress.inc(1);
+                    continue;
+                }
+                if let Some(&other) = clashes.get(image_path) {
+                    let e = CutterError::OutputNameClash { path: image_path.clone(), other: other.clone() };
+                    progress.suspend(|| error!("Error processing {}: {}", file_name, e));
+                    summary.failed += 1;
+                    progress.inc(1);
+                    continue;
+                }
+                
+                let sheet_dir = output_path.join(cutter.sheet_folder(image_path));
+                if let Err(source) = fs::create_dir_all(&sheet_dir) {
+                    let e = CutterError::CreateOutputDir { path: sheet_dir, source };
+                    progress.suspend(|| error!("Error processing {}: {}", file_name, e));
+                    summary.failed += 1;
+                    progress.inc(1);
+                    continue;
+                }
+                pending.push((index, image_path, sheet_dir));
+            }
+
+            // Decode and cut the sheets up to `max_inflight` at a time, then
+            // report on them in order
+            let max_inflight = self.config.max_inflight.unwrap_or_else(rayon::current_num_threads);
+            let frames_cut = AtomicUsize::new(0);
+            let cuts = bounded_map(&pending, max_inflight, |(index, image_path, sheet_dir)| {
+                let file_name = image_path.file_name().unwrap().to_string_lossy();
+                debug!("Processing {}/{}: {}", index + 1, image_files.len(), file_name);
+                progress.set_message(format!("{} ({} frames so far)", file_name, frames_cut.load(Ordering::Relaxed)));
+                let sheet_cutter = cutter.for_sheet();
+                let cut = sheet_cutter.cut_sheet(image_path, sheet_dir);
+                frames_cut.fetch_add(cut.as_ref().map_or(0, SheetCut::frame_count), Ordering::Relaxed);
+                progress.inc(1);
+                (cut, sheet_cutter.take_timings())
+            });
+
+            for ((_, image_path, sheet_dir), (cut, timings)) in pending.iter().zip(cuts) {
+                let image_path = *image_path;
+                let file_name = image_path.file_name().unwrap().to_string_lossy();
+                let source_file = slash_path(image_path.strip_prefix(&current_dir).unwrap_or(image_path));
+                let result = match cut {
+                    Ok(SheetCut::Frames(saved_frames, method, suggestion)) => {
+                        let deduped = saved_frames.iter().filter(|saved| saved.duplicate_of.is_some()).count();
+                        let kept = saved_frames.iter().filter(|saved| saved.kept).count();
+                        debug!("  → Extracted {} frames ({})", saved_frames.len(), method);
+                        if method == DetectionMethod::InferredGrid {
+                            let (rows, cols) = saved_frames.iter()
+                                .filter_map(|saved| saved.frame.cell)
+                                .fold((0, 0), |(rows, cols), (row, col)| (rows.max(row + 1), cols.max(col + 1)));
+                            progress.suspend(|| info!("{}: cut as an inferred {}x{} grid", file_name, cols, rows));
+                        }
+                        if method.is_heuristic() {
+                            let frames: Vec<SpriteFrame> = saved_frames.iter().map(|saved| saved.frame.clone()).collect();
+                            let sheet_size = image::image_dimensions(image_path).unwrap_or((0, 0));
+                            for warning in cutter.validate_frames(&frames, sheet_size) {
+                                progress.suspend(|| warn!("{}: {}", file_name, warning));
+                                summary.warnings.push(format!("{}: {}", source_file, warning));
+                            }
+                        }
+                        if let Some(suggestion) = suggestion {
+                            summary.suggestions.push(format!("{}: {}", source_file, suggestion));
+                        }
+                        for warning in saved_frames.iter().filter_map(SavedFrame::kept_background_warning) {
+                            progress.suspend(|| warn!("{}: {}", file_name, warning));
+                            summary.warnings.push(format!("{}: {}", source_file, warning));
+                        }
+                        if self.config.exec.is_some() {
+                            // Kept files weren't written in this run
+                            let fresh: Vec<SavedFrame> = saved_frames.iter().filter(|saved| !saved.kept).cloned().collect();
+                            let files = cutter.written_files(image_path, &fresh).into_iter().map(|file| output_path.join(file));
+                            for warning in self.run_exec_hook(files) {
+                                progress.suspend(|| warn!("{}: {}", file_name, warning));
+                                summary.warnings.push(format!("{}: {}", source_file, warning));
+                            }
+                        }
+                        if deduped > 0 {
+                            debug!("  → Skipped {} duplicate frames", deduped);
+                        }
+                        if self.config.emit_montage {
+                            montage_entries.push(cutter.montage_entry(image_path, &saved_frames));
+                        }
+                        if self.config.manifest.is_some() || previous_report.is_some() {
+                            manifest.extend(saved_frames.iter().map(|saved| ManifestRow {
+                                source_file: source_file.clone(),
+                                output_file: slash_path(&output_folder.join(cutter.in_sheet_folder(image_path, saved.file()))),
+                                x: saved.frame.x,
+                                y: saved.frame.y,
+                                width: saved.frame.width,
+                                height: saved.frame.height,
+                                trimmed_x: saved.trim_x,
+                                trimmed_y: saved.trim_y,
+                                average_color: saved.colors.map(|(average, _)| average),
+                                dominant_color: saved.colors.map(|(_, dominant)| dominant),
+                            }));
+                        }
+                        Ok((saved_frames.len() - deduped - kept, deduped, kept, cutter.recorded_files(image_path, &saved_frames),
+                            method))
+                    }
+                    Ok(SheetCut::SingleSprite(saved, suggestion)) => {
+                        debug!("  → Copied as single sprite");
+                        if let Some(suggestion) = suggestion {
+                            summary.suggestions.push(format!("{}: {}", source_file, suggestion));
+                        }
+                        if let Some(warning) = saved.kept_background_warning() {
+                            progress.suspend(|| warn!("{}: {}", file_name, warning));
+                            summary.warnings.push(format!("{}: {}", source_file, warning));
+                        }
+                        if !saved.kept {
+                            for warning in self.run_exec_hook([sheet_dir.join(&saved.filename)]) {
+                                progress.suspend(|| warn!("{}: {}", file_name, warning));
+                                summary.warnings.push(format!("{}: {}", source_file, warning));
+                            }
+                        }
+                        if self.config.manifest.is_some() || previous_report.is_some() {
+                            manifest.push(ManifestRow {
+                                source_file: source_file.clone(),
+                                output_file: slash_path(&output_folder.join(cutter.in_sheet_folder(image_path, &saved.filename))),
+                                x: 0,
+                                y: 0,
+                                width: saved.frame.width,
+                                height: saved.frame.height,
+                                trimmed_x: saved.trim_x,
+                                trimmed_y: saved.trim_y,
+                                average_color: saved.colors.map(|(average, _)| average),
+                                dominant_color: saved.colors.map(|(_, dominant)| dominant),
+                            });
+                        }
+                        Ok((usize::from(!saved.kept), 0, usize::from(saved.kept),
+                            vec![cutter.in_sheet_folder(image_path, &saved.filename)], DetectionMethod::SingleSprite))
+                    }
+                    Err(e) => Err(e),
+                };
+                if self.config.timings {
+                    summary.sheet_timings.push((image_path.clone(), timings));
+                }
+
+                match result {
+                    Ok((frames_written, frames_deduped, frames_kept, files, method)) => {
+                        summary.processed += 1;
+                        *summary.detection_methods.entry(method).or_default() += 1;
+                        summary.frames_total += frames_written;
+                        summary.frames_deduped += frames_deduped;
+                        summary.frames_kept += frames_kept;
+                        if let Some(state) = state.as_mut() {
+                            state.record(cutter.source_name(image_path), files);
+                        }
+                    }
+                    Err(e @ CutterError::TooManyFrames { .. }) => {
+                        progress.suspend(|| warn!("Skipped {}: {}", file_name, e));
+                        summary.over_frame_limit += 1;
+                    }
+                    Err(e) => {
+                        progress.suspend(|| error!("Error processing {}: {}", file_name, e));
+                        summary.failed += 1;
+                    }
+                }
+            }
+            progress.finish_and_clear();
+            if let Some(state) = &state {
+                state.save(&output_path)?;
+            }
+
+            if !montage_entries.is_empty() {
+                self.write_montage_file(&output_path, &montage_entries)?;
+                info!("Wrote montage.txt for {}", folder_name);
+            }
+        }
+
+        info!("\n=== Processing Complete! ===");
+        info!("Successfully processed {} images across all folders.", summary.processed);
+        if !summary.detection_methods.is_empty() {
+            let counts: Vec<String> = summary.detection_methods.iter()
+                .map(|(method, count)| format!("{} {}", method, count))
+                .collect();
+            info!("Sheets per detection path: {}", counts.join(", "));
+        }
+        if summary.failed > 0 {
+            info!("Failed to process {} image(s).", summary.failed);
+        }
+        if summary.unchanged > 0 {
+            info!("Skipped {} unchanged image(s).", summary.unchanged);
+        }
+        if summary.frames_deduped > 0 {
+            info!("Skipped {} duplicate frame(s).", summary.frames_deduped);
+        }
+        if summary.frames_kept > 0 {
+            info!("Kept {} existing frame file(s) instead of overwriting them.", summary.frames_kept);
+        }
+        if summary.over_frame_limit > 0 {
+            info!("Skipped {} image(s) with too many frames; the detection settings may need retuning.",
+                     summary.over_frame_limit);
+        }
+        if summary.folders_skipped > 0 {
+            info!("Skipped {} missing input folder(s).", summary.folders_skipped);
+        }
+        if summary.files_skipped > 0 {
+            info!("Skipped {} missing listed image(s).", summary.files_skipped);
+        }
+        if !summary.warnings.is_empty() {
+            info!("{} warning(s) about suspicious results or failed --exec runs; see above.", summary.warnings.len());
+        }
+        if !summary.suggestions.is_empty() {
+            info!("\n{} sheet(s) may cut better another way:", summary.suggestions.len());
+            for suggestion in &summary.suggestions {
+                info!("  {}", suggestion);
+            }
+        }
+        if self.config.timings && !summary.sheet_timings.is_empty() {
+            info!("\nTime per phase:");
+            for line in timings_table(&summary.sheet_timings) {
+                info!("{}", line);
+            }
+        }
+        if let (Some(previous), Some(path)) = (&previous_report, &self.config.compare) {
+            summary.frame_changes = compare_frames(previous, &manifest, &attempted);
+            info!("\nCompared with {}: {} sheet(s) changed", path.display(), summary.frame_changes.len());
+            for change in &summary.frame_changes {
+                info!("  {}", change);
+            }
+        }
+        if let Some(format) = self.config.manifest {
+            write_manifest(&self.config.output_root(&current_dir, &current_dir), format, &manifest)?;
+        }
+        if self.config.output_per_input() {
+            info!("Check the '{}' directory in each input folder for results.", self.config.output_dir);
+        } else {
+            info!("Check the '{}' directory for results.", self.config.output_dir);
+        }
+        info!(target: SUMMARY_LOG_TARGET, "{}", summary.one_line());
+
+        if let Some(summary_path) = &self.config.summary_json {
+            summary.write_json(summary_path)?;
+        }
+        if summary.failed > 0 {
+            return Err(CutterError::ImagesFailed { failed: summary.failed });
+        }
+        Ok(summary)
+    }
+
+    /// Images named in the file list at `list_path` (or stdin for `-`),
+    /// resolved against `current_dir`. Ones that don't exist are skipped
+    /// with a warning and counted in `summary`.
+    fn read_file_list(&self, list_path: &Path, current_dir: &Path, summary: &mut RunSummary) -> Result<Vec<PathBuf>> {
+        let read_error = |source| CutterError::ReadFileList { path: list_path.to_path_buf(), source };
+        let list = if list_path == Path::new("-") {
+            io::read_to_string(io::stdin()).map_err(read_error)?
+        } else {
+            fs::read_to_string(current_dir.join(list_path)).map_err(read_error)?
+        };
+
+        let mut image_files = Vec::new();
+        for line in list.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let image_path = current_dir.join(line);
+            if image_path.is_file() {
+                image_files.push(image_path);
+            } else {
+                warn!("Listed image '{}' not found, skipping...", line);
+                summary.files_skipped += 1;
+            }
+        }
+        Ok(image_files)
+    }
+
+    /// Run the `exec` command on each of `files` in turn, returning a warning
+    /// for every run that couldn't start or exited unsuccessfully. The
+    /// command is split on whitespace and run directly, not through a
+    /// shell; without a `{file}` placeholder the path goes last.
+    fn run_exec_hook(&self, files: impl IntoIterator<Item = PathBuf>) -> Vec<String> {
+        let Some(template) = &self.config.exec else { return Vec::new() };
+        let mut warnings = Vec::new();
+        for file in files {
+            let path = file.to_string_lossy();
+            let mut words = template.split_whitespace().map(|word| word.replace("{file}", &path));
+            let Some(program) = words.next() else { continue };
+            let mut command = std::process::Command::new(&program);
+            command.args(words);
+            if !template.contains("{file}") {
+                command.arg(&file);
+            }
+
+            let name = file.file_name().unwrap_or_default().to_string_lossy();
+            match command.output() {
+                Ok(output) if output.status.success() => {}
+                Ok(output) => {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    let reason = stderr.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or_default().trim();
+                    let mut warning = format!("'{}' failed on {} ({})", program, name, output.status);
+                    if !reason.is_empty() {
+                        warning += &format!(": {}", reason);
+                    }
+                    warnings.push(warning);
+                }
+                Err(e) => warnings.push(format!("couldn't run '{}' on {}: {}", program, name, e)),
+            }
+        }
+        warnings
+    }
+
+    /// Fingerprint of every setting that affects what gets written for a
+    /// sheet, so incremental runs redo sheets whose outputs used other settings
+    fn config_fingerprint(&self) -> String {
+        let relevant = CutterConfig {
+            input_dirs: Vec::new(),
+            file_list: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            summary_json: None,
+            verbose: false,
+            timings: false,
+            jobs: None,
+            max_inflight: None,
+            incremental: false,
+            watch: false,
+            ..self.config.clone()
+        };
+        // Hashed by hand, as the standard hasher may change between releases
+        // and would invalidate every state file
+        format!("{:016x}", fnv1a(format!("{:?}", relevant).as_bytes()))
+    }
+
+    /// Every file recorded for an incremental run: the frames, or the packed
+    /// atlas, along with the sheet's side files
+    fn recorded_files(&self, image_path: &Path, saved_frames: &[SavedFrame]) -> Vec<String> {
+        let duplicates = saved_frames.iter().any(|saved| saved.duplicate_of.is_some());
+        let mut files = if self.config.pack_mode == PackMode::None {
+            self.written_files(image_path, saved_frames)
+        } else {
+            Vec::new()
+        };
+        files.extend(self.side_files(&self.sheet_name(image_path), duplicates).iter()
+            .map(|file| self.in_sheet_folder(image_path, file)));
+        files
+    }
+
+    /// Files a processed sheet left in its output folder, relative to that folder
+    fn written_files(&self, image_path: &Path, saved_frames: &[SavedFrame]) -> Vec<String> {
+        if self.config.pack_mode != PackMode::None {
+            let packed = format!("{}_packed.{}", self.sheet_name(image_path), self.config.output_format.extension());
+            return vec![self.in_sheet_folder(image_path, &packed)];
+        }
+        saved_frames.iter()
+            .filter(|saved| saved.duplicate_of.is_none())
+            .map(|saved| self.in_sheet_folder(image_path, &saved.filename))
+            .collect()
+    }
+
+    /// Path of the sheet relative to the input folder it was found in
+    fn relative_source(&self, image_path: &Path) -> PathBuf {
+        self.input_root.as_deref()
+            .and_then(|root| image_path.strip_prefix(root).ok())
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| PathBuf::from(image_path.file_name().unwrap()))
+    }
+
+    /// `relative_source` with `/` separators, as recorded in metadata
+    fn source_name(&self, image_path: &Path) -> String {
+        slash_path(&self.relative_source(image_path))
+    }
+
+    /// Name the sheet's output files start with: its file stem, after its
+    /// subfolders of the input folder when the output is flattened. When
+    /// another sheet of the run next to it has the same stem, such as
+    /// `hero.png` and `hero.jpg`, the extension is appended (`hero_png`) so
+    /// their outputs don't overwrite each other.
+    fn sheet_name(&self, image_path: &Path) -> String {
+        let mut stem = image_path.file_stem().unwrap().to_string_lossy().to_string();
+        if self.shared_stems.contains(&image_path.with_extension("")) {
+            if let Some(extension) = image_path.extension() {
+                stem = format!("{}_{}", stem, extension.to_string_lossy());
+            }
+        }
+        if !self.config.flatten_output {
+            return stem;
+        }
+        let relative = self.relative_source(image_path);
+        let mut parts: Vec<String> = relative.parent()
+            .into_iter()
+            .flat_map(Path::components)
+            .map(|component| slugify(&component.as_os_str().to_string_lossy()))
+            .collect();
+        parts.push(stem);
+        parts.join("_")
+    }
+
+    /// Folder inside the output folder that gets this sheet's outputs: its
+    /// subfolder of the input folder unless the output is flattened, and its
+    /// own folder if sheets get one
+    fn sheet_folder(&self, image_path: &Path) -> PathBuf {
+        let mut folder = PathBuf::new();
+        if !self.config.flatten_output {
+            if let Some(parent) = self.relative_source(image_path).parent() {
+                folder.push(parent);
+            }
+        }
+        if self.config.subfolder_per_sheet {
+            folder.push(self.sheet_name(image_path));
+        }
+        folder
+    }
+
+    /// Path of a file the sheet wrote, relative to the output folder
+    fn in_sheet_folder(&self, image_path: &Path, file: &str) -> String {
+        let mut parts: Vec<String> = self.sheet_folder(image_path).components()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect();
+        parts.push(file.to_string());
+        parts.join("/")
+    }
+
+    /// Progress bar over one folder's images; hidden in verbose mode, where
+    /// the per-image debug lines take its place, and when nothing is logged
+    /// at info level. The bar is thread-safe, so
+    /// workers can tick it directly.
+    fn progress_bar(&self, len: usize) -> ProgressBar {
+        if self.config.verbose || log::log_enabled!(Level::Debug) || !log::log_enabled!(Level::Info) {
+            return ProgressBar::hidden();
+        }
+        let style = ProgressStyle::with_template("{bar:40.cyan/blue} {pos}/{len} {wide_msg}")
+            .expect("progress template is valid")
+            .progress_chars("=> ");
+        ProgressBar::new(len as u64).with_style(style)
+    }
+
+    /// Frame rects from a `<sheet>.json` atlas next to the sheet, if reading
+    /// them is enabled and there is one, in the order the file lists them
+    fn atlas_file_frames(&self, image_path: &Path, sheet_size: (u32, u32)) -> Result<Option<Vec<SpriteFrame>>> {
+        let atlas_path = image_path.with_extension("json");
+        if !self.config.read_atlas_files || !atlas_path.is_file() {
+            return Ok(None);
+        }
+        let invalid = |message: String| CutterError::ParseAtlas { path: atlas_path.clone(), message };
+
+        let contents = fs::read_to_string(&atlas_path).map_err(|e| invalid(e.to_string()))?;
+        let atlas: serde_json::Value = serde_json::from_str(&contents).map_err(|e| invalid(e.to_string()))?;
+        let frames = atlas_frames(&atlas).map_err(invalid)?;
+        let outside = |f: &&SpriteFrame| f.x as u64 + f.width as u64 > sheet_size.0 as u64
+            || f.y as u64 + f.height as u64 > sheet_size.1 as u64;
+        if let Some(frame) = frames.iter().find(outside) {
+            return Err(invalid(format!("frame {}x{}+{}+{} lies outside the {}x{} sheet",
+                                       frame.width, frame.height, frame.x, frame.y, sheet_size.0, sheet_size.1)));
+        }
+        debug!("  → Using {} frames from {}", frames.len(), atlas_path.display());
+        Ok(Some(frames))
+    }
+
+    /// Rust snippet building a Bevy `TextureAtlasLayout` for the source sheet.
+    ///
+    /// Grid and evenly divided strip sheets become a `from_grid` call whose
+    /// indices count cells row by row, empty ones included. Anything else
+    /// lists each frame's rect, so index `i` is frame `i + 1`.
+    fn bevy_layout(&self, sheet_name: &str, sheet_size: (u32, u32), frames: &[SpriteFrame]) -> String {
+        let (width, height) = sheet_size;
+        let grid = self.bevy_grid(sheet_size);
+        let uvec = |value: u32| match value {
+            0 => "None".to_string(),
+            value => format!("Some(UVec2::splat({}))", value),
+        };
+
+        let mut snippet = format!("// TextureAtlasLayout for {} ({}x{}), written by {}\n",
+                                  sheet_name, width, height, env!("CARGO_PKG_NAME"));
+        if let Some((cell_width, cell_height, cols, rows, padding, offset)) = grid {
+            snippet += "// Indices count the grid cells row by row, including empty ones\n";
+            snippet += &format!("TextureAtlasLayout::from_grid(UVec2::new({}, {}), {}, {}, {}, {})\n",
+                                cell_width, cell_height, cols, rows, uvec(padding), uvec(offset));
+        } else {
+            snippet += "// Index i is frame i + 1 of the saved frames\n{\n";
+            snippet += &format!("    let mut layout = TextureAtlasLayout::new_empty(UVec2::new({}, {}));\n", width, height);
+            for frame in frames {
+                snippet += &format!("    layout.add_texture(URect::new({}, {}, {}, {}));\n",
+                                    frame.x, frame.y, frame.x + frame.width, frame.y + frame.height);
+            }
+            snippet += "    layout\n}\n";
+        }
+        snippet
+    }
+
+    /// Cell size, columns, rows, padding and offset of the `from_grid`
+    /// layout for a sheet, when it's cut on a grid Bevy can describe
+    fn bevy_grid(&self, sheet_size: (u32, u32)) -> Option<(u32, u32, u32, u32, u32, u32)> {
+        let (width, height) = sheet_size;
+        let (margin, spacing) = (self.config.grid_margin, self.config.grid_spacing);
+        match (self.config.grid, self.config.strip_count) {
+            (Some(spec), _) => {
+                let (count_x, count_y, cell_x, cell_y) = match spec {
+                    GridSpec::Count { cols, rows } => (Some(cols), Some(rows), None, None),
+                    GridSpec::CellSize { width, height } => (None, None, Some(width), Some(height)),
+                };
+                // Bevy has no partial cells, so only count those that fit whole
+                let whole_cells = |extent: u32, count: Option<u32>, cell: Option<u32>| {
+                    let (cell, count) = grid_axis(extent, count, cell, margin, spacing)?;
+                    Some((cell, count.min((extent.saturating_sub(margin) + spacing) / (cell + spacing))))
+                };
+                match (whole_cells(width, count_x, cell_x), whole_cells(height, count_y, cell_y)) {
+                    (Some((cell_width, cols)), Some((cell_height, rows))) => {
+                        Some((cell_width, cell_height, cols, rows, spacing, margin))
+                    }
+                    _ => None,
+                }
+            }
+            (None, Some(count)) => match self.config.strip_orientation {
+                Orientation::Horizontal if width % count == 0 => Some((width / count, height, count, 1, 0, 0)),
+                Orientation::Vertical if height % count == 0 => Some((width, height / count, 1, count, 0, 0)),
+                _ => None,
+            },
+            (None, None) => None,
+        }
+    }
+
+    /// Comment lines giving the Bevy `Anchor` of each layout index of the
+    /// frames in `saved_frames` that have a pivot, for `Sprite::anchor`.
+    /// Bevy's anchors are centered on the frame with y pointing up.
+    fn bevy_anchors(&self, sheet_size: (u32, u32), saved_frames: &[SavedFrame]) -> String {
+        let grid = self.bevy_grid(sheet_size).map(|(_, _, cols, rows, _, _)| (cols, rows));
+        let round = |value: f32| (value * 1000.0).round() / 1000.0;
+        let mut lines = String::new();
+        for (frame_index, saved) in saved_frames.iter().enumerate() {
+            let Some((pivot_x, pivot_y)) = saved.pivot else { continue };
+            let index = match (grid, saved.frame.cell) {
+                // Partial cells at the far edges aren't in the layout
+                (Some((cols, rows)), Some((row, col))) if row >= rows || col >= cols => continue,
+                (Some((cols, _)), Some((row, col))) => (row * cols + col) as usize,
+                _ => frame_index,
+            };
+            if lines.is_empty() {
+                lines += "// Sprite::anchor of each index, from the frames' pivots\n";
+            }
+            lines += &format!("// {}: Anchor::Custom(Vec2::new({:?}, {:?}))\n",
+                              index, round(pivot_x - 0.5), round(0.5 - pivot_y));
+        }
+        lines
+    }
+
+    /// Find all image files in the directory, narrowed down by the include
+    /// and exclude patterns
+    fn find_image_files(&self, dir: &Path, output_root: &Path) -> Result<Vec<PathBuf>> {
+        let mut image_files = Vec::new();
+        let supported_extensions: HashSet<&str> = IMAGE_EXTENSIONS.iter().cloned().collect();
+        let include = glob_set(&self.config.include)?;
+        let exclude = glob_set(&self.config.exclude)?;
+        let wanted = |name: &std::ffi::OsStr| {
+            (self.config.include.is_empty() || include.is_match(name)) && !exclude.is_match(name)
+        };
+
+        // Subfolders only when recursing, and never the output directory,
+        // whose frames would otherwise be cut again on the next run
+        let max_depth = if self.config.recursive { usize::MAX } else { 1 };
+        for entry in WalkDir::new(dir)
+            .max_depth(max_depth)
+            .into_iter()
+            .filter_entry(|entry| entry.depth() == 0 || entry.path() != output_root)
+            .filter_map(|e| e.ok())
+        {
+            if entry.file_type().is_file() {
+                if let Some(extension) = entry.path().extension() {
+                    if let Some(ext_str) = extension.to_str() {
+                        if supported_extensions.contains(ext_str.to_lowercase().as_str())
+                            && wanted(entry.file_name()) {
+                            image_files.push(entry.path().to_path_buf());
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(image_files)
+    }
+
+    /// Cut one sheet of a run, copying it as a single sprite when no frames
+    /// are found on it
+    fn cut_sheet(&self, image_path: &Path, sheet_dir: &Path) -> Result<SheetCut> {
+        let (cutter, img, detection) = self.load_sheet(image_path)?;
+        // Advice comes from the sheet already decoded here, and only for the
+        // paths that guess at the layout
+        let suggest = |frames: Vec<SpriteFrame>, method| {
+            self.timed(Phase::Detection, || self.suggest_detector(&img, &Detection { frames, method }))
+        };
+        match cutter.as_ref().unwrap_or(self).cut_spritesheet(image_path, &img, detection, sheet_dir) {
+            Ok((saved_frames, method)) => {
+                let suggestion = method.is_heuristic()
+                    .then(|| suggest(saved_frames.iter().map(|saved| saved.frame.clone()).collect(), method))
+                    .flatten();
+                Ok(SheetCut::Frames(saved_frames, method, suggestion))
+            }
+            Err(CutterError::NoFramesDetected { .. }) => {
+                let saved = self.copy_single_sprite(image_path, sheet_dir)?;
+                let suggestion = suggest(vec![saved.frame.clone()], DetectionMethod::SingleSprite);
+                Ok(SheetCut::SingleSprite(saved, suggestion))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Process a single spritesheet, failing with `NoFramesDetected` when
+    /// there is nothing to cut. Runs go through [`Self::cut_sheet`].
+    #[cfg(test)]
+    fn process_spritesheet(&self, image_path: &Path, output_dir: &Path) -> Result<(Vec<SavedFrame>, DetectionMethod)> {
+        let (cutter, img, detection) = self.load_sheet(image_path)?;
+        cutter.as_ref().unwrap_or(self).cut_spritesheet(image_path, &img, detection, output_dir)
+    }
+
+    /// Decode a sheet and find its frames, along with the cutter to cut it
+    /// with when that isn't this one: normal maps keep their background, and
+    /// palette-keyed sheets key out their palette color
+    fn load_sheet(&self, image_path: &Path) -> Result<(Option<SpritesheetCutter>, DynamicImage, Detection)> {
+        if self.is_normal_map(image_path) {
+            let (img, detection) = self.normal_map_detection(image_path)?;
+            return Ok((Some(self.for_normal_maps()), img, detection));
+        }
+        if let Some(keyed) = self.palette_keyed(image_path)? {
+            let (_, img, detection) = keyed.load_sheet(image_path)?;
+            return Ok((Some(keyed), img, detection));
+        }
+        if let Some((strip, detection)) = self.timed(Phase::Decode, || gif_animation_strip(image_path))? {
+            return Ok((None, strip, detection));
+        }
+        let img = self.timed(Phase::Decode, || open_image(image_path))?;
+        let detection = self.timed(Phase::Detection, || self.sheet_detection(image_path, &img))?;
+        Ok((None, img, detection))
+    }
+
+    /// Cut a single sheet without writing anything, returning each frame's
+    /// file name and its bytes in the output format, in frame order. A sheet
+    /// with no frames comes back as one sprite, as it would be copied by
+    /// [`SpritesheetCutter::process_directory`]. Sheets are loaded and frames
+    /// finished as when cutting to disk, so normal maps follow their diffuse
+    /// sheet and frames skipped by dedup are left out. Side outputs such as
+    /// atlases and previews are not produced.
+    pub fn cut_to_memory(&self, image_path: &Path) -> Result<Vec<(String, Vec<u8>)>> {
+        let (cutter, img, detection) = self.load_sheet(image_path)?;
+        cutter.as_ref().unwrap_or(self).cut_loaded_to_memory(image_path, img, detection)
+    }
+
+    /// [`SpritesheetCutter::cut_to_memory`] once the sheet is loaded
+    fn cut_loaded_to_memory(&self, image_path: &Path, img: DynamicImage, detection: Detection)
+        -> Result<Vec<(String, Vec<u8>)>> {
+        let Detection { frames, method } = detection;
+        if frames.is_empty() {
+            let (processed, saved) = self.single_sprite(image_path, img)?;
+            let bytes = self.encode_image(&processed, &saved.filename)?;
+            return Ok(vec![(saved.filename, bytes)]);
+        }
+        if let Some(max) = self.config.max_frames.filter(|&max| frames.len() > max) {
+            return Err(CutterError::TooManyFrames { path: image_path.to_path_buf(), count: frames.len(), max });
+        }
+        debug!("  → Detected {} frames with {}", frames.len(), method);
+
+        let base_name = self.sheet_name(image_path);
+        self.finish_frames(image_path, &img, &frames, &base_name)?.into_iter()
+            .filter(|(_, saved)| saved.duplicate_of.is_none())
+            .map(|(processed, saved)| {
+                let bytes = self.encode_image(&processed, &saved.filename)?;
+                Ok((saved.filename, bytes))
+            })
+            .collect()
+    }
+
+    /// Frames of a sheet: the rects of its atlas file if it has one, or else
+    /// whatever detection finds. An explicit grid or strip wins over the
+    /// atlas file, and an atlas file that can't be used is only warned about.
+    fn sheet_detection(&self, image_path: &Path, img: &DynamicImage) -> Result<Detection> {
+        if self.config.grid.is_some() || self.config.strip_count.is_some() {
+            return self.detect_with_method(img);
+        }
+        match self.atlas_file_frames(image_path, img.dimensions()) {
+            Ok(Some(frames)) => Ok(Detection { frames, method: DetectionMethod::AtlasFile }),
+            Ok(None) => self.detect_with_method(img),
+            Err(e) => {
+                warn!("{}; detecting frames instead", e);
+                self.detect_with_method(img)
+            }
+        }
+    }
+
+    /// Decode a normal map and find its frames: those of its diffuse sheet
+    /// when there is one. Its flat background is data, not something to
+    /// remove, so it's cut by [`SpritesheetCutter::for_normal_maps`].
+    fn normal_map_detection(&self, image_path: &Path) -> Result<(DynamicImage, Detection)> {
+        let img = self.timed(Phase::Decode, || open_image(image_path))?;
+        let detection = match self.diffuse_sibling(image_path) {
+            Some(diffuse) => {
+                let diffuse_img = self.timed(Phase::Decode, || open_image(&diffuse))?;
+                if diffuse_img.dimensions() != img.dimensions() {
+                    return Err(CutterError::NormalMapSize {
+                        path: image_path.to_path_buf(),
+                        size: img.dimensions(),
+                        diffuse,
+                        diffuse_size: diffuse_img.dimensions(),
+                    });
+                }
+                debug!("  → Normal map; cutting it like {}", diffuse.display());
+                let keyed = self.palette_keyed(&diffuse)?;
+                let cutter = keyed.as_ref().unwrap_or(self);
+                self.timed(Phase::Detection, || cutter.sheet_detection(&diffuse, &diffuse_img))?
+            }
+            None => {
+                debug!("  → Normal map; keeping its background");
+                self.timed(Phase::Detection, || self.sheet_detection(image_path, &img))?
+            }
+        };
+        Ok((img, detection))
+    }
+
+    /// Extract and finish each of a sheet's frames, marking those that
+    /// repeat an earlier frame when deduplicating
+    fn finish_frames(&self, image_path: &Path, img: &DynamicImage, frames: &[SpriteFrame], base_name: &str)
+        -> Result<Vec<(DynamicImage, SavedFrame)>> {
+        let extracted = self.extract_frames(img, frames).collect::<Result<Vec<_>>>()?;
+        let mut deduper = FrameDeduper {
+            transforms: self.config.dedup_transforms,
+            ..FrameDeduper::new(self.config.dedup_tolerance)
+        };
+        let mut finished = Vec::with_capacity(frames.len());
+        for (position, (frame, (processed, kept_background, background))) in
+            frames.iter().zip(extracted).enumerate() {
+            let filename = self.frame_filename(base_name, position, frame)?;
+            let (processed, mut saved) = self.finish_frame(image_path, frame, filename, processed)?;
+            saved.kept_background = kept_background;
+            saved.background = background;
+            if self.config.dedup {
+                if let Some((original, transform)) = deduper.check(&processed, &saved.filename) {
+                    saved.duplicate_of = Some(original);
+                    saved.duplicate_transform = transform;
+                }
+            }
+            finished.push((processed, saved));
+        }
+        Ok(finished)
+    }
+
+    /// Whether the sheet's file name ends in one of the normal map suffixes
+    fn is_normal_map(&self, image_path: &Path) -> bool {
+        self.normal_map_base(image_path).is_some()
+    }
+
+    /// File stem of a normal map without its suffix
+    fn normal_map_base(&self, image_path: &Path) -> Option<String> {
+        let stem = image_path.file_stem()?.to_string_lossy();
+        self.config.normal_map_suffixes.iter().find_map(|suffix| {
+            let split = stem.len().checked_sub(suffix.len()).filter(|&split| split > 0)?;
+            let (base, ending) = (stem.get(..split)?, stem.get(split..)?);
+            ending.eq_ignore_ascii_case(suffix).then(|| base.to_string())
+        })
+    }
+
+    /// The diffuse sheet next to a normal map: the same name without the
+    /// suffix, preferably with the same extension
+    fn diffuse_sibling(&self, image_path: &Path) -> Option<PathBuf> {
+        let base = self.normal_map_base(image_path)?;
+        let extension = image_path.extension().map(|ext| ext.to_string_lossy().to_string());
+        extension.into_iter()
+            .chain(IMAGE_EXTENSIONS.iter().map(|ext| ext.to_string()))
+            .map(|ext| image_path.with_file_name(format!("{}.{}", base, ext)))
+            .find(|path| path.is_file())
+    }
+
+    /// Cutter for normal maps, which keeps every pixel of each frame
+    fn for_normal_maps(&self) -> SpritesheetCutter {
+        self.with_config(CutterConfig {
+            remove_background: false,
+            trim: false,
+            normal_map_suffixes: Vec::new(),
+            ..self.config.clone()
+        })
+    }
+
+    /// Cut a sheet along `detection`'s frames and save them along with any
+    /// other configured outputs
+    fn cut_spritesheet(&self, image_path: &Path, img: &DynamicImage, detection: Detection, output_dir: &Path)
+        -> Result<(Vec<SavedFrame>, DetectionMethod)> {
+        let Detection { frames, method } = detection;
+        let base_name = self.sheet_name(image_path);
+
+        let packing = self.config.pack_mode != PackMode::None;
+        // Refuse before writing anything, so a sheet is never half replaced
+        if self.config.overwrite == OverwritePolicy::Error {
+            let mut files = self.side_files(&base_name, true);
+            if !packing {
+                for (position, frame) in frames.iter().enumerate() {
+                    files.push(self.frame_filename(&base_name, position, frame)?);
+                }
+            }
+            if let Some(path) = files.into_iter().map(|file| output_dir.join(file)).find(|path| path.exists()) {
+                return Err(CutterError::OutputExists { path });
+            }
+        }
+
+        // Written even when nothing was found, since that's when it helps most
+        let overlay_path = output_dir.join(format!("{}_debug.png", base_name));
+        if self.config.debug_overlay && self.may_write(&overlay_path)? {
+            debug_overlay(img, &frames, self.config.frame_index_start as usize).save(&overlay_path)
+                .map_err(|source| CutterError::SaveFrame { path: overlay_path.clone(), source })?;
+        }
+        
+        if frames.is_empty() {
+            return Err(CutterError::NoFramesDetected { path: image_path.to_path_buf() });
+        }
+        if let Some(max) = self.config.max_frames.filter(|&max| frames.len() > max) {
+            return Err(CutterError::TooManyFrames { path: image_path.to_path_buf(), count: frames.len(), max });
+        }
+
+        debug!("  → Detected {} frames with {}", frames.len(), method);
+
+        // Finish and save each frame
+        let finished = self.finish_frames(image_path, img, &frames, &base_name)?;
+        let mut saved_frames = Vec::with_capacity(finished.len());
+        let mut frame_images = Vec::new();
+        for (processed, mut saved) in finished {
+            if !packing && saved.duplicate_of.is_none() {
+                saved.kept = !self.save_frame(&processed, &output_dir.join(&saved.filename))?;
+            }
+
+            saved_frames.push(saved);
+            if self.config.preview_gif || self.config.contact_sheet || packing {
+                frame_images.push(processed);
+            }
+        }
+
+        if packing {
+            self.write_packed_atlas(image_path, &frame_images, &saved_frames, method, output_dir)?;
+        }
+        if saved_frames.iter().any(|saved| saved.duplicate_of.is_some()) {
+            self.write_duplicates_file(image_path, &saved_frames, output_dir)?;
+        }
+        let gif_path = output_dir.join(format!("{}_preview.gif", base_name));
+        if self.config.preview_gif && self.may_write(&gif_path)? {
+            self.export_gif(&frame_images, &gif_path, self.config.gif_fps)?;
+        }
+        let contact_path = output_dir.join(format!("{}_contact.png", base_name));
+        if self.config.contact_sheet && self.may_write(&contact_path)? {
+            let columns = self.config.contact_sheet_columns
+                .unwrap_or_else(|| (frame_images.len() as f64).sqrt().ceil() as u32);
+            contact_sheet(&frame_images, columns).save_with_format(&contact_path, image::ImageFormat::Png)
+                .map_err(|source| CutterError::SaveFrame { path: contact_path.clone(), source })?;
+        }
+
+        let bevy_path = output_dir.join(format!("{}_bevy.rs", base_name));
+        if self.config.export_bevy && self.may_write(&bevy_path)? {
+            let sheet_name = image_path.file_name().unwrap().to_string_lossy();
+            let layout = self.bevy_layout(&sheet_name, img.dimensions(), &frames)
+                + &self.bevy_anchors(img.dimensions(), &saved_frames);
+            fs::write(&bevy_path, layout)
+                .map_err(|source| CutterError::WriteFile { path: bevy_path.clone(), source })?;
+        }
+        match self.config.atlas_format {
+            AtlasFormat::None => {}
+            AtlasFormat::TexturePackerHash => {
+                self.write_texture_packer_hash(image_path, img.dimensions(), &saved_frames, method, output_dir)?;
+            }
+            AtlasFormat::SparrowXml => self.write_sparrow_xml(image_path, &saved_frames, output_dir)?,
+        }
+
+        Ok((saved_frames, method))
+    }
+
+    /// Names of the files other than frames that cutting a sheet named
+    /// `base_name` may write into its output folder, as configured; the
+    /// duplicates file is only listed when there are `duplicates`
+    fn side_files(&self, base_name: &str, duplicates: bool) -> Vec<String> {
+        let mut files = Vec::new();
+        if self.config.debug_overlay {
+            files.push(format!("{}_debug.png", base_name));
+        }
+        if self.config.pack_mode != PackMode::None {
+            let atlas_extension = if self.config.atlas_format == AtlasFormat::SparrowXml { "xml" } else { "json" };
+            files.push(format!("{}_packed.{}", base_name, self.config.output_format.extension()));
+            files.push(format!("{}_packed.{}", base_name, atlas_extension));
+        }
+        if self.config.dedup && duplicates {
+            files.push(format!("{}_duplicates.json", base_name));
+        }
+        if self.config.preview_gif {
+            files.push(format!("{}_preview.gif", base_name));
+        }
+        if self.config.contact_sheet {
+            files.push(format!("{}_contact.png", base_name));
+        }
+        if self.config.export_bevy {
+            files.push(format!("{}_bevy.rs", base_name));
+        }
+        match self.config.atlas_format {
+            AtlasFormat::None => {}
+            AtlasFormat::TexturePackerHash => files.push(format!("{}.json", base_name)),
+            AtlasFormat::SparrowXml => files.push(format!("{}.xml", base_name)),
+        }
+        files
+    }
+
+    /// File name, with extension, of the frame at `position` on the sheet,
+    /// counted from 0
+    fn frame_filename(&self, base_name: &str, position: usize, frame: &SpriteFrame) -> Result<String> {
+        let index = self.config.frame_index_start as usize + position;
+        let stem = format_frame_filename(self.filename_template(), base_name, index, position, frame)
+            .map_err(CutterError::InvalidConfig)?;
+        Ok(format!("{}.{}", stem, self.config.output_format.extension()))
+    }
+
+    /// Turn an extracted frame into the image that gets saved: trimmed,
+    /// padded, scaled, centered on the uniform canvas and premultiplied as
+    /// configured. Also returns where its pixels came from, to be saved as
+    /// `filename`.
+    fn finish_frame(&self, image_path: &Path, frame: &SpriteFrame, filename: String, processed: DynamicImage)
+        -> Result<(DynamicImage, SavedFrame)> {
+        let (processed, trim_x, trim_y) = if self.config.trim {
+            self.trim_transparent(&processed)
+        } else {
+            (processed, 0, 0)
+        };
+        let (content_width, content_height) = processed.dimensions();
+        let centroid = match self.config.pivot {
+            Some(PivotMode::Centroid) => alpha_centroid(&processed),
+            _ => None,
+        };
+        let colors = self.config.emit_colors.then(|| self.frame_colors(&processed));
+        let processed = if self.config.frame_padding > 0 {
+            self.pad_frame(&processed, self.config.frame_padding)
+        } else {
+            processed
+        };
+        let processed = self.scale_frame(processed);
+        let (processed, uniform) = match self.config.uniform_frame_size {
+            Some(size) => {
+                let (width, height) = processed.dimensions();
+                if width > size || height > size {
+                    return Err(CutterError::FrameTooLarge { path: image_path.to_path_buf(), width, height, size });
+                }
+                let canvas = UniformCanvas { size, x: (size - width) / 2, y: (size - height) / 2 };
+                let mut centered = RgbaImage::new(size, size);
+                image::imageops::overlay(&mut centered, &processed.to_rgba8(), canvas.x as i64, canvas.y as i64);
+                (DynamicImage::ImageRgba8(centered), Some(canvas))
+            }
+            None => (processed, None),
+        };
+        let processed = if self.config.premultiply_alpha {
+            self.premultiply_alpha(processed)
+        } else {
+            processed
+        };
+
+        let mut saved = SavedFrame {
+            filename,
+            frame: frame.clone(),
+            trim_x,
+            trim_y,
+            width: content_width,
+            height: content_height,
+            padding: self.config.frame_padding,
+            uniform,
+            duplicate_of: None,
+            duplicate_transform: None,
+            pivot: None,
+            colors,
+            kept: false,
+            kept_background: None,
+            background: None,
+        };
+        saved.pivot = self.config.pivot.map(|mode| saved.pivot_for(mode, centroid));
+        Ok((processed, saved))
+    }
+
+    /// Template for frame file names, dropping the sheet name from the
+    /// default one when each sheet has its own folder
+    fn filename_template(&self) -> &str {
+        if self.config.subfolder_per_sheet && self.config.filename_template == DEFAULT_FILENAME_TEMPLATE {
+            SUBFOLDER_FILENAME_TEMPLATE
+        } else {
+            &self.config.filename_template
+        }
+    }
+
+    /// Assemble frames into a looping animated GIF. Frames of differing sizes
+    /// are centered on a transparent canvas as large as the biggest frame.
+    fn export_gif(&self, frames: &[DynamicImage], path: &Path, fps: u32) -> Result<()> {
+        let canvas_width = frames.iter().map(|frame| frame.width()).max().unwrap_or(1);
+        let canvas_height = frames.iter().map(|frame| frame.height()).max().unwrap_or(1);
+        let delay = Delay::from_numer_denom_ms(1000, fps.max(1));
+
+        let file = fs::File::create(path)
+            .map_err(|source| CutterError::WriteFile { path: path.to_path_buf(), source })?;
+        let mut encoder = GifEncoder::new(io::BufWriter::new(file));
+        encoder.set_repeat(Repeat::Infinite)
+            .map_err(|source| CutterError::EncodeImage { path: path.to_path_buf(), source })?;
+
+        for frame in frames {
+            let mut canvas = RgbaImage::new(canvas_width, canvas_height);
+            let offset_x = (canvas_width - frame.width()) / 2;
+            let offset_y = (canvas_height - frame.height()) / 2;
+            image::imageops::overlay(&mut canvas, &frame.to_rgba8(), offset_x as i64, offset_y as i64);
+
+            encoder.encode_frame(Frame::from_parts(canvas, 0, 0, delay))
+                .map_err(|source| CutterError::EncodeImage { path: path.to_path_buf(), source })?;
+        }
+
+        Ok(())
+    }
+
+    /// Write `{name}.json` in TexturePacker's JSON hash format. Frame rects
+    /// point into the original sheet, which is named as the atlas image, and
+    /// each key is the file name of the PNG saved for that frame. Trimmed
+    /// frames report the trimmed rect, with `spriteSourceSize` placing it
+    /// inside the untrimmed cell. Padding grows `sourceSize` and shifts
+    /// `spriteSourceSize` by the padding on each side.
+    fn write_texture_packer_hash(&self, image_path: &Path, sheet_size: (u32, u32), saved_frames: &[SavedFrame],
+                                 method: DetectionMethod, output_dir: &Path) -> Result<()> {
+        let base_name = self.sheet_name(image_path);
+        let sheet_name = image_path.file_name()
+            .unwrap()
+            .to_string_lossy();
+
+        let origins: Vec<(u32, u32)> = saved_frames.iter()
+            .map(|saved| (saved.frame.x + saved.trim_x, saved.frame.y + saved.trim_y))
+            .collect();
+        let mut atlas = texture_packer_hash(&sheet_name, sheet_size, saved_frames, &origins, method,
+                                            &self.source_name(image_path));
+        self.record_removed_background(&mut atlas, saved_frames);
+
+        let atlas_path = output_dir.join(format!("{}.json", base_name));
+        if self.may_write(&atlas_path)? {
+            fs::write(&atlas_path, serde_json::to_string_pretty(&atlas)?)
+                .map_err(|source| CutterError::WriteFile { path: atlas_path.clone(), source })?;
+        }
+        Ok(())
+    }
+
+    /// Write `<sheet>.xml`, a Sparrow atlas of the frames on the source sheet
+    fn write_sparrow_xml(&self, image_path: &Path, saved_frames: &[SavedFrame], output_dir: &Path) -> Result<()> {
+        let sheet_name = image_path.file_name().unwrap().to_string_lossy();
+        let origins: Vec<(u32, u32)> = saved_frames.iter()
+            .map(|saved| (saved.frame.x + saved.trim_x, saved.frame.y + saved.trim_y))
+            .collect();
+
+        let atlas_path = output_dir.join(format!("{}.xml", self.sheet_name(image_path)));
+        if self.may_write(&atlas_path)? {
+            fs::write(&atlas_path, sparrow_xml(&sheet_name, saved_frames, &origins))
+                .map_err(|source| CutterError::WriteFile { path: atlas_path.clone(), source })?;
+        }
+        Ok(())
+    }
+
+    /// Write `{name}_duplicates.json`, mapping each frame skipped by dedup to
+    /// the file of the earlier frame it repeats. With `dedup_transforms`,
+    /// each entry is `{"frame": <file>, "transform": <transform or null>}`.
+    fn write_duplicates_file(&self, image_path: &Path, saved_frames: &[SavedFrame], output_dir: &Path) -> Result<()> {
+        let base_name = self.sheet_name(image_path);
+
+        let mut duplicates = serde_json::Map::new();
+        for saved in saved_frames {
+            if let Some(original) = &saved.duplicate_of {
+                let entry = if self.config.dedup_transforms {
+                    serde_json::json!({ "frame": original, "transform": saved.duplicate_transform })
+                } else {
+                    original.clone().into()
+                };
+                duplicates.insert(saved.filename.clone(), entry);
+            }
+        }
+
+        let path = output_dir.join(format!("{}_duplicates.json", base_name));
+        if self.may_write(&path)? {
+            fs::write(&path, serde_json::to_string_pretty(&duplicates)?)
+                .map_err(|source| CutterError::WriteFile { path: path.clone(), source })?;
+        }
+        Ok(())
+    }
+
+    /// Shelf-pack a sheet's processed frames into `{name}_packed.<ext>` and
+    /// describe them in `{name}_packed.json`, in the same TexturePacker hash
+    /// format as `--atlas-format`, with rects pointing into the packed image.
+    /// Frames keep their padding, so packed neighbours don't bleed together.
+    /// Deduplicated frames are packed once and share the original's rect.
+    fn write_packed_atlas(&self, image_path: &Path, frame_images: &[DynamicImage], saved_frames: &[SavedFrame],
+                          method: DetectionMethod, output_dir: &Path) -> Result<()> {
+        let base_name = self.sheet_name(image_path);
+        let unique: Vec<usize> = (0..saved_frames.len())
+            .filter(|&i| saved_frames[i].duplicate_of.is_none())
+            .collect();
+        let sizes: Vec<(u32, u32)> = unique.iter().map(|&i| frame_images[i].dimensions()).collect();
+        let packing = pack_shelves(&sizes, self.config.atlas_size)
+            .ok_or_else(|| CutterError::AtlasOverflow {
+                path: image_path.to_path_buf(),
+                size: self.config.atlas_size,
+            })?;
+
+        let mut atlas_image = RgbaImage::new(packing.width, packing.height);
+        let mut packed_at = HashMap::new();
+        for (&i, &(x, y)) in unique.iter().zip(&packing.positions) {
+            image::imageops::replace(&mut atlas_image, &frame_images[i].to_rgba8(), x as i64, y as i64);
+            packed_at.insert(saved_frames[i].filename.as_str(), (x, y));
+        }
+        let image_name = format!("{}_packed.{}", base_name, self.config.output_format.extension());
+        let packed_path = output_dir.join(&image_name);
+        if self.may_write(&packed_path)? {
+            self.save_image(&DynamicImage::ImageRgba8(atlas_image), &packed_path)?;
+        }
+
+        let origins: Vec<(u32, u32)> = saved_frames.iter()
+            .map(|saved| {
+                let (x, y) = packed_at[saved.file()];
+                let (offset_x, offset_y) = saved.content_offset();
+                (x + offset_x, y + offset_y)
+            })
+            .collect();
+        let (atlas_path, atlas) = if self.config.atlas_format == AtlasFormat::SparrowXml {
+            (output_dir.join(format!("{}_packed.xml", base_name)), sparrow_xml(&image_name, saved_frames, &origins))
+        } else {
+            let mut atlas = texture_packer_hash(&image_name, (packing.width, packing.height), saved_frames, &origins,
+                                                method, &self.source_name(image_path));
+            self.record_removed_background(&mut atlas, saved_frames);
+            (output_dir.join(format!("{}_packed.json", base_name)), serde_json::to_string_pretty(&atlas)?)
+        };
+        if self.may_write(&atlas_path)? {
+            fs::write(&atlas_path, atlas)
+                .map_err(|source| CutterError::WriteFile { path: atlas_path.clone(), source })?;
+        }
+        Ok(())
+    }
+
+    /// Build the `montage.txt` entry for one sheet: a comment per frame with its
+    /// source geometry, then the ImageMagick command that rebuilds a contact sheet
+    fn montage_entry(&self, image_path: &Path, saved_frames: &[SavedFrame]) -> String {
+        let montage = self.in_sheet_folder(image_path, &format!("{}_montage.png", self.sheet_name(image_path)));
+
+        let mut entry = format!("# {}: {} frames\n", self.source_name(image_path), saved_frames.len());
+        let files: Vec<String> = saved_frames.iter()
+            .map(|saved| shell_quote(&self.in_sheet_folder(image_path, saved.file())))
+            .collect();
+        for (saved, file) in saved_frames.iter().zip(&files) {
+            let frame = &saved.frame;
+            entry.push_str(&format!("#   {} {}x{}+{}+{}\n",
+                                    file, frame.width, frame.height, frame.x, frame.y));
+        }
+
+        // Lay frames out in a roughly square grid, each at its original size
+        let columns = (saved_frames.len() as f64).sqrt().ceil() as usize;
+        entry.push_str("montage");
+        for file in &files {
+            entry.push(' ');
+            entry.push_str(file);
+        }
+        entry.push_str(&format!(" -tile {}x -geometry +2+2 -background none {}\n",
+                                columns, shell_quote(&montage)));
+        entry
+    }
+
+    /// Write the collected montage entries for one output folder
+    fn write_montage_file(&self, output_dir: &Path, entries: &[String]) -> Result<()> {
+        let mut contents = String::from(
+            "# ImageMagick contact sheets for the frames in this folder.\n\
+             # Run from this directory: sh montage.txt\n\n",
+        );
+        contents.push_str(&entries.join("\n"));
+
+        let montage_path = output_dir.join("montage.txt");
+        fs::write(&montage_path, contents)
+            .map_err(|source| CutterError::WriteFile { path: montage_path.clone(), source })?;
+        Ok(())
+    }
+
+    /// With `background_index` set, a cutter for this one sheet that keys
+    /// out exactly the indexed color, with no tolerance
+    fn palette_keyed(&self, image_path: &Path) -> Result<Option<SpritesheetCutter>> {
+        let Some(index) = self.config.background_index else { return Ok(None) };
+        if !self.config.remove_background {
+            return Ok(None);
+        }
+        let key = palette_color(image_path, index)?;
+        debug!("  → Palette index {} is {:?}", index, key.0);
+        Ok(Some(self.with_config(CutterConfig {
+            background_key: Some(key),
+            background_index: None,
+            background_tolerance: 0,
+            ..self.config.clone()
+        })))
+    }
+
+    /// Encode an image in the configured output format, keeping its color
+    /// type and bit depth unless the format can't store them
+    fn save_image(&self, img: &DynamicImage, path: &Path) -> Result<()> {
+        self.timed(Phase::Encoding, || {
+            let mut writer = io::BufWriter::new(fs::File::create(path)?);
+            self.write_encoded(img, &mut writer)?;
+            io::Write::flush(&mut writer).map_err(image::ImageError::IoError)
+        })
+        .map_err(|source| CutterError::SaveFrame { path: path.to_path_buf(), source })
+    }
+
+    /// Write an image in the output format, at `output_quality` if the
+    /// format takes one
+    fn write_encoded<W: io::Write + io::Seek>(&self, img: &DynamicImage, writer: &mut W) -> image::ImageResult<()> {
+        let format = self.config.output_format;
+        let encodable = format.encodable(img);
+        match self.config.output_quality {
+            Some(quality) if format == OutputFormat::Jpeg => {
+                encodable.write_with_encoder(image::codecs::jpeg::JpegEncoder::new_with_quality(writer, quality))
+            }
+            _ => encodable.write_to(writer, format.image_format()),
+        }
+    }
+
+    /// Whether `overwrite` lets an output file be written to `path`: `false`
+    /// when an existing file is to be kept, an error when it must be left
+    /// alone and the sheet fail
+    fn may_write(&self, path: &Path) -> Result<bool> {
+        if !path.exists() {
+            return Ok(true);
+        }
+        match self.config.overwrite {
+            OverwritePolicy::Overwrite => Ok(true),
+            OverwritePolicy::Skip => Ok(false),
+            OverwritePolicy::Error => Err(CutterError::OutputExists { path: path.to_path_buf() }),
+        }
+    }
+
+    /// Save a frame file as `overwrite` allows, returning `false` when an
+    /// existing file was kept instead
+    fn save_frame(&self, img: &DynamicImage, path: &Path) -> Result<bool> {
+        if !self.may_write(path)? {
+            return Ok(false);
+        }
+        self.save_image(img, path)?;
+        Ok(true)
+    }
+
+    /// Copy a single sprite image to the output directory, in the output
+    /// format, returning what was saved
+    fn copy_single_sprite(&self, image_path: &Path, output_dir: &Path) -> Result<SavedFrame> {
+        if self.is_normal_map(image_path) {
+            return self.for_normal_maps().copy_single_sprite(image_path, output_dir);
+        }
+        if let Some(keyed) = self.palette_keyed(image_path)? {
+            return keyed.copy_single_sprite(image_path, output_dir);
+        }
+        let img = self.timed(Phase::Decode, || open_image(image_path))?;
+        let (processed, mut saved) = self.single_sprite(image_path, img)?;
+
+        saved.kept = !self.save_frame(&processed, &output_dir.join(&saved.filename))?;
+        Ok(saved)
+    }
+
+    /// A sheet without frames, processed as one frame covering all of it:
+    /// background removal and then the same finishing as detected frames
+    fn single_sprite(&self, image_path: &Path, img: DynamicImage) -> Result<(DynamicImage, SavedFrame)> {
+        let (width, height) = img.dimensions();
+        let frame = SpriteFrame::new(0, 0, width, height);
+        let filename = format!("{}.{}", self.sheet_name(image_path), self.config.output_format.extension());
+        let kept_background = self.config.background_opacity_floor
+            .and_then(|_| self.below_opacity_floor(&img, &frame, &self.content_backgrounds(&img)));
+        let processed = if self.config.remove_background && kept_background.is_none() {
+            self.timed(Phase::BackgroundRemoval, || self.remove_background(&img))?
+        } else {
+            img
+        };
+        let (processed, mut saved) = self.finish_frame(image_path, &frame, filename, processed)?;
+        saved.kept_background = kept_background.filter(|_| self.config.remove_background);
+        Ok((processed, saved))
+    }
+
+    /// Encode an image in the output format, for frames that never touch
+    /// the disk
+    fn encode_image(&self, img: &DynamicImage, name: &str) -> Result<Vec<u8>> {
+        let mut bytes = io::Cursor::new(Vec::new());
+        self.write_encoded(img, &mut bytes)
+            .map_err(|source| CutterError::EncodeImage { path: PathBuf::from(name), source })?;
+        Ok(bytes.into_inner())
+    }
+
+    /// Sanity check detected frames on a sheet of `sheet_size`, returning a
+    /// warning for each sign that detection went wrong: frames that are all
+    /// the same size (a grid would cut them more cleanly), one frame covering
+    /// most of the sheet, or frame sizes that are far apart.
+    pub fn validate_frames(&self, frames: &[SpriteFrame], sheet_size: (u32, u32)) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let area = |frame: &SpriteFrame| frame.width as u64 * frame.height as u64;
+        let sheet_area = sheet_size.0 as u64 * sheet_size.1 as u64;
+
+        if let [first, rest @ ..] = frames {
+            if !rest.is_empty() && rest.iter().all(|frame| (frame.width, frame.height) == (first.width, first.height)) {
+                warnings.push(format!("all {} frames are {}x{}; --grid or --auto-grid may cut this sheet more cleanly",
+                                      frames.len(), first.width, first.height));
+            }
+        }
+        if let Some(largest) = frames.iter().max_by_key(|frame| area(frame)) {
+            let coverage = area(largest) as f64 / sheet_area.max(1) as f64;
+            if coverage > LARGE_FRAME_COVERAGE {
+                warnings.push(format!("one frame covers {:.0}% of the sheet; the gaps between sprites may have been missed",
+                                      coverage * 100.0));
+            }
+        }
+        let areas = frames.iter().map(area);
+        if let (Some(smallest), Some(largest)) = (areas.clone().min(), areas.max()) {
+            if largest as f64 > smallest.max(1) as f64 * FRAME_AREA_SPREAD {
+                warnings.push(format!("frame sizes vary from {} to {} pixels; some sprites may be split or merged",
+                                      smallest, largest));
+            }
+        }
+        warnings
+    }
+
+    /// What went wrong when `detection` looks like a bad cut of a sheet of
+    /// `sheet_size`: one frame covering most of the sheet, thousands of
+    /// frames, or frames found only by a fallback pass
+    fn poor_detection(&self, detection: &Detection, sheet_size: (u32, u32)) -> Option<String> {
+        let sheet_area = (sheet_size.0 as u64 * sheet_size.1 as u64).max(1);
+        let coverage = |frame: &SpriteFrame| (frame.width as u64 * frame.height as u64) as f64 / sheet_area as f64;
+        match (detection.method, detection.frames.as_slice()) {
+            (DetectionMethod::SingleSprite, _) => Some("nothing was detected, so it was copied whole".to_string()),
+            (_, [frame]) if coverage(frame) > LARGE_FRAME_COVERAGE => {
+                Some("one frame covers most of the sheet".to_string())
+            }
+            (_, frames) if frames.len() >= MANY_FRAMES => Some(format!("cut into {} frames", frames.len())),
+            (method @ (DetectionMethod::RelaxedBoundaries | DetectionMethod::HorizontalFallback
+                       | DetectionMethod::VerticalFallback), _) => {
+                Some(format!("frames were only found by the {} pass", method))
+            }
+            _ => None,
+        }
+    }
+
+    /// Advice for a sheet whose `detection` looks like a bad cut (one frame
+    /// covering most of the sheet, thousands of frames, or frames found only
+    /// by a fallback pass): slice it as a grid whose cells are the spacing at
+    /// which the most common size of block of content repeats, with a margin
+    /// when those blocks don't start at the first cell. `None` when the frames
+    /// look fine, no block size repeats, as on a sheet holding a single
+    /// sprite, or the repeated blocks don't fit one grid.
+    pub fn suggest_detector(&self, img: &DynamicImage, detection: &Detection) -> Option<String> {
+        let problem = self.poor_detection(detection, img.dimensions())?;
+        let blocks = self.detect_connected_components(img);
+        let mut counts: Vec<((u32, u32), usize)> = Vec::new();
+        for block in &blocks {
+            match counts.iter_mut().find(|(size, _)| *size == (block.width, block.height)) {
+                Some((_, count)) => *count += 1,
+                None => counts.push(((block.width, block.height), 1)),
+            }
+        }
+        let (size, count) = counts.into_iter().rev().max_by_key(|&(_, count)| count)?;
+        if count < 2 {
+            return None;
+        }
+        let repeated: Vec<&SpriteFrame> =
+            blocks.iter().filter(|block| (block.width, block.height) == size).collect();
+
+        // The cell is the most common distance between the blocks' origins,
+        // not their size: 24px sprites drawn every 32px need 32px cells. An
+        // axis along which they don't repeat takes the whole sheet.
+        let pitch = |mut origins: Vec<u32>, extent: u32| {
+            origins.sort_unstable();
+            origins.dedup();
+            let mut gaps: Vec<u32> = origins.windows(2).map(|pair| pair[1] - pair[0]).collect();
+            gaps.sort_unstable();
+            // Ties go to the smaller gap
+            gaps.chunk_by(|a, b| a == b).rev().max_by_key(|run| run.len()).map_or(extent, |run| run[0])
+        };
+        let (width, height) = img.dimensions();
+        let cell_width = pitch(repeated.iter().map(|block| block.x).collect(), width);
+        let cell_height = pitch(repeated.iter().map(|block| block.y).collect(), height);
+        let fits = |margin: u32| {
+            repeated.iter().all(|block| {
+                block.x >= margin && block.y >= margin
+                    && (block.x - margin) % cell_width + block.width <= cell_width
+                    && (block.y - margin) % cell_height + block.height <= cell_height
+            })
+        };
+        let margin = repeated.iter().map(|block| (block.x % cell_width).min(block.y % cell_height)).min()?;
+        let flags = if fits(0) {
+            format!("--grid-cell {}x{}", cell_width, cell_height)
+        } else if fits(margin) {
+            format!("--grid-cell {}x{} --grid-margin {}", cell_width, cell_height, margin)
+        } else {
+            return None;
+        };
+        Some(format!("{}; consider {} ({} blocks of content repeat at that spacing)", problem, flags, count))
+    }
+
+    /// Detect sprite frames in the image, returned in the configured frame order
+    pub fn detect_sprite_frames(&self, img: &DynamicImage) -> Result<Vec<SpriteFrame>> {
+        self.detect(img)
+    }
+
+    /// First stage of processing a sheet: find its frames, in the configured
+    /// frame order. See [`Self::detect_with_method`] to also learn which
+    /// detection path found them.
+    pub fn detect(&self, img: &DynamicImage) -> Result<Vec<SpriteFrame>> {
+        Ok(self.detect_with_method(img)?.frames)
+    }
+
+    /// Like [`detect`](Self::detect), but also reporting which detection
+    /// path found the frames
+    pub fn detect_with_method(&self, img: &DynamicImage) -> Result<Detection> {
+        // Look for the gutter color once, on the whole sheet, rather than in
+        // every pass that finds boundaries
+        if self.config.detect_gutter {
+            let gutter_color = self.gutter_color(&img.to_rgba8());
+            debug!("  → Detected gutter color: {}", gutter_color.map_or("none".to_string(), hex_string));
+            let resolved = self.with_config(CutterConfig { gutter_color, detect_gutter: false, ..self.config.clone() });
+            return resolved.detect_with_method(img);
+        }
+        let Some((x, y, width, height)) = self.config.roi else {
+            return self.detect_in_region(img);
+        };
+        let (sheet_width, sheet_height) = img.dimensions();
+        if x as u64 + width as u64 > sheet_width as u64 || y as u64 + height as u64 > sheet_height as u64 {
+            return Err(CutterError::InvalidConfig(format!(
+                "region of interest {},{} {}x{} doesn't fit in a {}x{} image",
+                x, y, width, height, sheet_width, sheet_height)));
+        }
+
+        let mut detection = self.detect_in_region(&img.crop_imm(x, y, width, height))?;
+        for frame in &mut detection.frames {
+            frame.x += x;
+            frame.y += y;
+        }
+        Ok(detection)
+    }
+
+    /// `detect` on the whole of `img`, which may already be cropped to the
+    /// region of interest
+    fn detect_in_region(&self, img: &DynamicImage) -> Result<Detection> {
+        // Content on a sheet without alpha is told apart by its background
+        // colors, so find those once rather than for every frame checked
+        if !img.color().has_alpha() && self.sheet_background.is_none() {
+            return self.for_crop_of(img).detect_in_region(img);
+        }
+        // A known grid layout beats any heuristic
+        let mut detection = if let Some(spec) = self.config.grid {
+            Detection { frames: self.slice_grid(img, spec)?, method: DetectionMethod::Grid }
+        } else if let Some(count) = self.config.strip_count {
+            let frames = self.split_strip(img, count, self.config.strip_orientation);
+            Detection { frames, method: DetectionMethod::Strip }
+        } else if self.config.autocrop {
+            let (cropped, offset_x, offset_y) = self.autocrop_sheet(img);
+            let mut detection = self.for_crop_of(img).detect_content(&cropped)?;
+            for frame in &mut detection.frames {
+                frame.x += offset_x;
+                frame.y += offset_y;
+            }
+            detection
+        } else {
+            self.detect_content(img)?
+        };
+
+        // Empty frames would crop to nothing and fail to save
+        detection.frames.retain(|frame| frame.width > 0 && frame.height > 0);
+        // Clustered frames come in the order of their clusters, which a
+        // plain sort by position would undo for staggered rows
+        match self.config.frame_order {
+            _ if detection.method == DetectionMethod::Clustered => {}
+            FrameOrder::RowMajor => detection.frames.sort_by_key(|frame| (frame.y, frame.x)),
+            FrameOrder::ColumnMajor => detection.frames.sort_by_key(|frame| (frame.x, frame.y)),
+        }
+        Ok(detection)
+    }
+
+    /// Find frames with the configured detection mode
+    fn detect_content(&self, img: &DynamicImage) -> Result<Detection> {
+        let mut detection = match self.config.detection_mode {
+            DetectionMode::ConnectedComponents => {
+                Detection { frames: self.detect_connected_components(img), method: DetectionMethod::ConnectedComponents }
+            }
+            DetectionMode::Projection => {
+                let (vertical, horizontal) = self.find_boundaries_by_projection(img);
+                let frames = self.frames_between_boundaries(img, &vertical, &horizontal);
+                Detection { frames, method: DetectionMethod::Projection }
+            }
+            DetectionMode::Clustered => {
+                let frames = cluster_frames(self.detect_connected_components(img), self.config.frame_order);
+                Detection { frames, method: DetectionMethod::Clustered }
+            }
+            DetectionMode::Boundaries => self.detect_boundary_frames(img)?,
+        };
+        if self.config.merge_adjacent {
+            detection.frames = self.merge_adjacent_frames(img, detection.frames);
+        }
+        Ok(detection)
+    }
+
+    /// Merge frames that share an edge with content on both sides of it
+    /// somewhere along the edge, as long as the merged frame stays within
+    /// `max_sprite_size`. Content means opaque on sheets with alpha and not
+    /// the background color otherwise. Frames are merged pairwise until no
+    /// pair qualifies, so a sprite cut into several pieces ends up whole.
+    fn merge_adjacent_frames(&self, img: &DynamicImage, mut frames: Vec<SpriteFrame>) -> Vec<SpriteFrame> {
+        let rgba_img = img.to_rgba8();
+        let has_alpha = img.color().has_alpha();
+        let background_colors = self.background_colors(&rgba_img);
+        let is_content = |x: u32, y: u32| {
+            let pixel = rgba_img.get_pixel(x, y);
+            if has_alpha {
+                pixel[3] > self.config.alpha_threshold
+            } else {
+                !self.is_background_pixel(pixel, &background_colors)
+            }
+        };
+        // Whether content crosses the edge `a` shares with `b`, if they share one
+        let joined = |a: &SpriteFrame, b: &SpriteFrame| {
+            let (top, bottom) = (a.y.max(b.y), (a.y + a.height).min(b.y + b.height));
+            let (left, right) = (a.x.max(b.x), (a.x + a.width).min(b.x + b.width));
+            if b.x == a.x + a.width && top < bottom {
+                (top..bottom).any(|y| is_content(b.x - 1, y) && is_content(b.x, y))
+            } else if b.y == a.y + a.height && left < right {
+                (left..right).any(|x| is_content(x, b.y - 1) && is_content(x, b.y))
+            } else {
+                false
+            }
+        };
+
+        let mut merged_any = true;
+        while merged_any {
+            merged_any = false;
+            'search: for i in 0..frames.len() {
+                for j in 0..frames.len() {
+                    let (a, b) = (&frames[i], &frames[j]);
+                    if i == j || !joined(a, b) {
+                        continue;
+                    }
+                    let x = a.x.min(b.x);
+                    let y = a.y.min(b.y);
+                    let union = SpriteFrame::new(x, y, (a.x + a.width).max(b.x + b.width) - x,
+                                                 (a.y + a.height).max(b.y + b.height) - y);
+                    if union.width > self.config.max_sprite_size || union.height > self.config.max_sprite_size {
+                        continue;
+                    }
+                    frames[i] = union;
+                    frames.remove(j);
+                    merged_any = true;
+                    break 'search;
+                }
+            }
+        }
+        frames
+    }
+
+    /// Crop the empty border around everything on the sheet, returning the
+    /// cropped sheet and where it starts on the original. Empty means
+    /// transparent on sheets with alpha, and the background color otherwise.
+    /// A sheet with nothing on it is returned whole.
+    pub fn autocrop_sheet(&self, img: &DynamicImage) -> (DynamicImage, u32, u32) {
+        let rgba_img = img.to_rgba8();
+        let has_alpha = img.color().has_alpha();
+        let background_colors = self.background_colors(&rgba_img);
+
+        let mut bounds: Option<(u32, u32, u32, u32)> = None;
+        for (x, y, pixel) in rgba_img.enumerate_pixels() {
+            let is_content = if has_alpha {
+                pixel[3] > self.config.alpha_threshold
+            } else {
+                !self.is_background_pixel(pixel, &background_colors)
+            };
+            if is_content {
+                bounds = Some(match bounds {
+                    Some((min_x, min_y, max_x, max_y)) => (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)),
+                    None => (x, y, x, y),
+                });
+            }
+        }
+
+        match bounds {
+            Some((min_x, min_y, max_x, max_y)) => {
+                (img.crop_imm(min_x, min_y, max_x - min_x + 1, max_y - min_y + 1), min_x, min_y)
+            }
+            None => (img.clone(), 0, 0),
+        }
+    }
+
+    /// Detect sprite frames using intelligent boundary detection
+    fn detect_boundary_frames(&self, img: &DynamicImage) -> Result<Detection> {
+        // Convert to grayscale for analysis, keeping the precision of 16-bit sheets
+        let gray_img = self.config.luma_source.gray16(img);
+        let (vertical_boundaries, horizontal_boundaries) = self.sheet_boundaries(img, &gray_img);
+        if let Some(frames) = self.inferred_grid_frames(img, &vertical_boundaries, &horizontal_boundaries) {
+            return Ok(Detection { frames, method: DetectionMethod::InferredGrid });
+        }
+        let frames = self.frames_between_boundaries(img, &vertical_boundaries, &horizontal_boundaries);
+
+        // Opaque sheets have no transparent gaps, and only dark gutters read
+        // as empty in luma, so when that finds no split, look for where the
+        // plain background shows instead
+        if frames.len() <= 1 {
+            if let Some(background) = self.uniform_background(img) {
+                let split = self.background_difference_frames(img, background);
+                if split.len() > frames.len() {
+                    return Ok(Detection { frames: split, method: DetectionMethod::BackgroundDifference });
+                }
+            }
+        }
+
+        if frames.len() <= 1 && self.config.adaptive_retry {
+            if let Some(relaxed) = self.relaxed_boundary_frames(img, &gray_img) {
+                return Ok(Detection { frames: relaxed, method: DetectionMethod::RelaxedBoundaries });
+            }
+        }
+
+        // If no frames were detected, try fallback detection
+        if frames.is_empty() {
+            debug!("  → No frames detected with main algorithm, trying fallback...");
+            let fallback = self.fallback_detection(img, &self.config.luma_source.gray8(img))?;
+            if !fallback.frames.is_empty() {
+                debug!("  → Fallback detection found {} frames", fallback.frames.len());
+            }
+            return Ok(fallback);
+        }
+
+        Ok(Detection { frames, method: DetectionMethod::Boundaries })
+    }
+
+    /// Retry boundary detection with `RELAXED_THRESHOLDS`, for sheets where
+    /// the main pass found at most one frame. Of the attempts that found more
+    /// than one frame, returns the frames of the most plausible: the one whose
+    /// frames are closest to the same size, the least relaxed on a tie. Sheets
+    /// that can't fit two frames of `min_sprite_size` side by side or one
+    /// above the other aren't retried.
+    fn relaxed_boundary_frames(&self, img: &DynamicImage, gray_img: &Image<image::Luma<u16>>) -> Option<Vec<SpriteFrame>> {
+        let (width, height) = img.dimensions();
+        let min = self.config.min_sprite_size;
+        if !(width >= 2 * min && height >= min || height >= 2 * min && width >= min) {
+            return None;
+        }
+
+        let mut best: Option<(f32, Vec<SpriteFrame>, CutterConfig)> = None;
+        for (transparent_factor, color_change_factor, content_factor) in RELAXED_THRESHOLDS {
+            let relaxed = self.with_config(CutterConfig {
+                transparent_column_ratio: self.config.transparent_column_ratio * transparent_factor,
+                color_change_ratio: self.config.color_change_ratio * color_change_factor,
+                content_threshold: self.config.content_threshold * content_factor,
+                ..self.config.clone()
+            });
+            let (vertical, horizontal) = relaxed.sheet_boundaries(img, gray_img);
+            let frames = relaxed.frames_between_boundaries(img, &vertical, &horizontal);
+            if frames.len() < 2 {
+                continue;
+            }
+            let spread = area_spread(&frames);
+            if best.as_ref().is_none_or(|(best_spread, _, _)| spread < *best_spread) {
+                best = Some((spread, frames, relaxed.config));
+            }
+        }
+
+        let (spread, frames, config) = best?;
+        debug!("  → Relaxed thresholds found {} frames with an area spread of {:.2} (transparent ratio {}, \
+                color change ratio {}, content threshold {})", frames.len(), spread, config.transparent_column_ratio,
+               config.color_change_ratio, config.content_threshold);
+        Some(frames)
+    }
+
+    /// Vertical and horizontal boundaries of a sheet, as the `boundaries`
+    /// detector sees them.
+    ///
+    /// Luma is compared at 16-bit precision, so 16-bit sheets keep gutters
+    /// and edges that differ by less than one 8-bit level; 8-bit sheets get
+    /// exactly the 8-bit result. The alpha test reads 8-bit alpha, like
+    /// `alpha_threshold`.
+    fn sheet_boundaries(&self, img: &DynamicImage, gray_img: &Image<image::Luma<u16>>) -> (Vec<u32>, Vec<u32>) {
+        // Sheets that actually use their alpha channel tell us directly where
+        // the gaps are; dark-but-opaque sprites would otherwise look empty
+        let rgba_img = img.to_rgba8();
+        let uses_alpha = img.color().has_alpha()
+            && rgba_img.pixels().any(|pixel| pixel[3] <= self.config.alpha_threshold);
+        let alpha_img = uses_alpha.then_some(&rgba_img);
+
+        let mut vertical = self.find_vertical_boundaries(gray_img, alpha_img);
+        let mut horizontal = self.find_horizontal_boundaries(gray_img, alpha_img);
+        if let Some(gutter) = self.gutter_color(&rgba_img) {
+            let (width, height) = rgba_img.dimensions();
+            let is_gutter = |x: u32, y: u32| self.matches_color(rgba_img.get_pixel(x, y), &gutter);
+            vertical = self.with_gutter_lines(&vertical, width, |x| (0..height).filter(|&y| is_gutter(x, y)).count(), height);
+            horizontal = self.with_gutter_lines(&horizontal, height, |y| (0..width).filter(|&x| is_gutter(x, y)).count(), width);
+        }
+        if self.config.include_edge_frames {
+            let (width, height) = gray_img.dimensions();
+            let is_filled = |x: u32, y: u32| match alpha_img {
+                Some(rgba_img) => rgba_img.get_pixel(x, y)[3] > self.config.alpha_threshold,
+                None => gray_img.get_pixel(x, y)[0] >= DARK_LUMA,
+            };
+            drop_edge_gutters(&mut vertical, width, |x| (0..height).any(|y| is_filled(x, y)));
+            drop_edge_gutters(&mut horizontal, height, |y| (0..width).any(|x| is_filled(x, y)));
+        }
+        if self.config.refine_boundaries {
+            let (width, height) = rgba_img.dimensions();
+            let background_colors = self.background_colors(&rgba_img);
+            let is_filled = |x: u32, y: u32| match alpha_img {
+                Some(rgba_img) => rgba_img.get_pixel(x, y)[3] > self.config.alpha_threshold,
+                None => !self.is_background_pixel(rgba_img.get_pixel(x, y), &background_colors),
+            };
+            vertical = self.refine_boundaries(&vertical, |x| (0..height).all(|y| !is_filled(x, y)));
+            horizontal = self.refine_boundaries(&horizontal, |y| (0..width).all(|x| !is_filled(x, y)));
+        }
+        (vertical, horizontal)
+    }
+
+    /// Tighten the spans between `coarse` boundaries to their content, so
+    /// frames don't keep a sliver of the gutter next to them. Within each span
+    /// the first and last line that isn't empty become boundaries too; the
+    /// empty lines left outside them make spans without content, which
+    /// `frames_between_boundaries` drops. Spans whose content is narrower
+    /// than `min_sprite_size` are left as they are, so small sprites aren't
+    /// lost.
+    fn refine_boundaries(&self, coarse: &[u32], is_empty: impl Fn(u32) -> bool) -> Vec<u32> {
+        let mut refined = coarse.to_vec();
+        for pair in coarse.windows(2) {
+            let Some(first) = (pair[0]..pair[1]).find(|&line| !is_empty(line)) else {
+                continue;
+            };
+            let last = (first..pair[1]).rev().find(|&line| !is_empty(line)).unwrap_or(first);
+            if last + 1 - first >= self.config.min_sprite_size {
+                refined.extend([first, last + 1]);
+            }
+        }
+        refined.sort_unstable();
+        refined.dedup();
+        refined
+    }
+
+    /// Merge the lines along one axis that are mostly gutter color into
+    /// `boundaries`. `gutter_count` tells how many pixels of a line match the
+    /// gutter color, out of `length`; a gutter line wins over any other
+    /// boundary within `boundary_merge_distance` of it. Boundaries go on both
+    /// sides of a gutter line, so the line itself ends up in a span too thin
+    /// to be a frame rather than at the edge of its neighbour.
+    fn with_gutter_lines(&self, boundaries: &[u32], extent: u32, gutter_count: impl Fn(u32) -> usize, length: u32)
+        -> Vec<u32> {
+        let candidates: Vec<(u32, f32)> = boundaries.iter().map(|&position| (position, 1.0))
+            .chain((1..extent.saturating_sub(1))
+                .filter(|&position| gutter_count(position) as f32 / length as f32 > self.config.transparent_column_ratio)
+                .flat_map(|position| [(position, 2.0), (position + 1, 2.0)]))
+            .collect();
+        merge_close_boundaries(&candidates, self.config.boundary_merge_distance, extent)
+    }
+
+    /// Infer the grid a sheet is drawn on from where its boundaries fall:
+    /// `(cols, rows, cell_width, cell_height)` when the gaps between frames
+    /// are evenly spaced on both axes, or `None` when they aren't, the cells
+    /// would be smaller than `min_sprite_size`, or there is only one cell.
+    ///
+    /// A grid with an empty cell has a gap twice as wide there, so it isn't
+    /// recognized.
+    pub fn infer_grid(&self, img: &DynamicImage) -> Option<(u32, u32, u32, u32)> {
+        let (vertical, horizontal) = self.sheet_boundaries(img, &self.config.luma_source.gray16(img));
+        self.grid_of_boundaries(img.dimensions(), &vertical, &horizontal)
+    }
+
+    /// `infer_grid` for a sheet of `size` whose boundaries are known
+    fn grid_of_boundaries(&self, (width, height): (u32, u32), vertical: &[u32], horizontal: &[u32])
+        -> Option<(u32, u32, u32, u32)> {
+        let (cols, cell_width) = even_spacing(vertical, width)?;
+        let (rows, cell_height) = even_spacing(horizontal, height)?;
+        let min = self.config.min_sprite_size;
+        (cols * rows > 1 && cell_width >= min && cell_height >= min).then_some((cols, rows, cell_width, cell_height))
+    }
+
+    /// Frames of the grid the boundary pass's boundaries are spaced on, when
+    /// `auto_grid` is on. The inferred grid is logged either way, as a hint
+    /// for `--grid`.
+    fn inferred_grid_frames(&self, img: &DynamicImage, vertical: &[u32], horizontal: &[u32])
+        -> Option<Vec<SpriteFrame>> {
+        if !self.config.auto_grid && !log::log_enabled!(Level::Debug) {
+            return None;
+        }
+        let (width, height) = img.dimensions();
+        let (cols, rows, cell_width, cell_height) = self.grid_of_boundaries((width, height), vertical, horizontal)?;
+        debug!("  → Inferred a {}x{} grid of {}x{} cells", cols, rows, cell_width, cell_height);
+        if !self.config.auto_grid {
+            return None;
+        }
+        let cuts = |count: u32, extent: u32| (0..=count).map(|i| i * extent / count).collect::<Vec<_>>();
+        let (vertical, horizontal) = (cuts(cols, width), cuts(rows, height));
+        let mut frames = self.frames_between_boundaries(img, &vertical, &horizontal);
+        for frame in &mut frames {
+            let index = |cuts: &[u32], start: u32| cuts.iter().position(|&cut| cut == start).unwrap_or(0) as u32;
+            frame.cell = Some((index(&horizontal, frame.y), index(&vertical, frame.x)));
+        }
+        Some(frames)
+    }
+
+    /// Frames in the cells between consecutive vertical and horizontal
+    /// boundaries that are within the size limits and have content
+    fn frames_between_boundaries(&self, img: &DynamicImage, vertical: &[u32], horizontal: &[u32]) -> Vec<SpriteFrame> {
+        let mut frames = Vec::new();
+        for (x, frame_width) in boundary_spans(vertical) {
+            for (y, frame_height) in boundary_spans(horizontal) {
+                // Validate frame size
+                if self.long_enough(frame_width)
+                    && self.long_enough(frame_height)
+                    && frame_width <= self.config.max_sprite_size 
+                    && frame_height <= self.config.max_sprite_size {
+                    
+                    // Check if frame contains non-transparent content
+                    if self.frame_has_content(img, x, y, frame_width, frame_height) {
+                        frames.push(SpriteFrame::new(x, y, frame_width, frame_height));
+                    }
+                }
+            }
+        }
+        frames
+    }
+
+    /// Divide an animation strip into `count` equal frames along its long
+    /// axis, running the full length of the other one.
+    ///
+    /// No content check or detection is involved, so art that touches the
+    /// frame edges is cut just as reliably. When the length doesn't divide
+    /// evenly, the spare pixels are spread over the frames.
+    pub fn split_strip(&self, img: &DynamicImage, count: u32, orientation: Orientation) -> Vec<SpriteFrame> {
+        let (width, height) = img.dimensions();
+        let length = match orientation {
+            Orientation::Horizontal => width,
+            Orientation::Vertical => height,
+        };
+        let edge = |i: u32| (i as u64 * length as u64 / count.max(1) as u64) as u32;
+
+        (0..count)
+            .map(|i| (edge(i), edge(i + 1) - edge(i)))
+            .filter(|&(_, size)| size > 0)
+            .map(|(start, size)| match orientation {
+                Orientation::Horizontal => SpriteFrame::new(start, 0, size, height),
+                Orientation::Vertical => SpriteFrame::new(0, start, width, size),
+            })
+            .collect()
+    }
+
+    /// Divide the image into equal grid cells, skipping cells with no content.
+    ///
+    /// Cell `(col, row)` starts at `margin + col * (cell + spacing)`. Without
+    /// margin or spacing, an image that does not divide evenly has its last
+    /// column and row clamped to the image edge; with them, only whole cells
+    /// are cut, and a grid that doesn't fit even one cell is an error.
+    fn slice_grid(&self, img: &DynamicImage, spec: GridSpec) -> Result<Vec<SpriteFrame>> {
+        let (width, height) = img.dimensions();
+        let (margin, spacing) = (self.config.grid_margin, self.config.grid_spacing);
+        let (count_x, count_y, cell_x, cell_y) = match spec {
+            GridSpec::Count { cols, rows } => (Some(cols), Some(rows), None, None),
+            GridSpec::CellSize { width, height } => (None, None, Some(width), Some(height)),
+        };
+        let (Some((cell_width, cols)), Some((cell_height, rows))) = (
+            grid_axis(width, count_x, cell_x, margin, spacing),
+            grid_axis(height, count_y, cell_y, margin, spacing),
+        ) else {
+            return Err(CutterError::InvalidConfig(format!(
+                "grid with margin {} and spacing {} doesn't fit in a {}x{} image",
+                margin, spacing, width, height)));
+        };
+        let exact_cells = margin > 0 || spacing > 0;
+
+        let mut frames = Vec::new();
+        for row in 0..rows {
+            for col in 0..cols {
+                let x = margin + col * (cell_width + spacing);
+                let y = margin + row * (cell_height + spacing);
+                // The last column/row absorbs any remainder (or is cut short) at the edge
+                let frame_width = if exact_cells {
+                    cell_width
+                } else if col + 1 == cols {
+                    width - x
+                } else {
+                    cell_width.min(width - x)
+                };
+                let frame_height = if exact_cells {
+                    cell_height
+                } else if row + 1 == rows {
+                    height - y
+                } else {
+                    cell_height.min(height - y)
+                };
+
+                if self.frame_has_content(img, x, y, frame_width, frame_height) {
+                    frames.push(SpriteFrame {
+                        x,
+                        y,
+                        width: frame_width,
+                        height: frame_height,
+                        cell: Some((row, col)),
+                    });
+                }
+            }
+        }
+
+        Ok(frames)
+    }
+
+    /// Find one bounding box per 8-connected blob of foreground pixels.
+    ///
+    /// Foreground is any pixel with alpha above `alpha_threshold` on images with an alpha
+    /// channel, or any pixel not matching the detected background color on
+    /// opaque images.
+    fn detect_connected_components(&self, img: &DynamicImage) -> Vec<SpriteFrame> {
+        let rgba_img = img.to_rgba8();
+        let (width, height) = rgba_img.dimensions();
+        let has_alpha = img.color().has_alpha();
+        let background_colors = self.background_colors(&rgba_img);
+
+        let is_foreground = |x: u32, y: u32| {
+            let pixel = rgba_img.get_pixel(x, y);
+            if has_alpha {
+                pixel[3] > self.config.alpha_threshold
+            } else {
+                !self.is_background_pixel(pixel, &background_colors)
+            }
+        };
+
+        let mut visited = vec![false; (width as usize) * (height as usize)];
+        let mut frames = Vec::new();
+        let mut stack = Vec::new();
+
+        for start_y in 0..height {
+            for start_x in 0..width {
+                let start_index = (start_y * width + start_x) as usize;
+                if visited[start_index] || !is_foreground(start_x, start_y) {
+                    continue;
+                }
+
+                // Flood fill this blob, growing its bounding box as we go
+                visited[start_index] = true;
+                stack.push((start_x, start_y));
+                let (mut min_x, mut min_y, mut max_x, mut max_y) = (start_x, start_y, start_x, start_y);
+
+                while let Some((x, y)) = stack.pop() {
+                    min_x = min_x.min(x);
+                    min_y = min_y.min(y);
+                    max_x = max_x.max(x);
+                    max_y = max_y.max(y);
+
+                    for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+                        for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                            let index = (ny * width + nx) as usize;
+                            if !visited[index] && is_foreground(nx, ny) {
+                                visited[index] = true;
+                                stack.push((nx, ny));
+                            }
+                        }
+                    }
+                }
+
+                frames.push(SpriteFrame::new(min_x, min_y, max_x - min_x + 1, max_y - min_y + 1));
+            }
+        }
+
+        if let Some(overlap) = self.config.merge_overlap {
+            frames = merge_overlapping_frames(frames, overlap);
+        }
+
+        // Drop specks that hold too few pixels, or are small in both dimensions
+        frames.retain(|frame| match self.config.min_content_pixels {
+            Some(min_pixels) => {
+                let pixels = (frame.y..frame.y + frame.height)
+                    .flat_map(|y| (frame.x..frame.x + frame.width).map(move |x| (x, y)))
+                    .filter(|&(x, y)| is_foreground(x, y))
+                    .count();
+                pixels >= min_pixels as usize
+            }
+            None => frame.width >= self.config.min_sprite_size || frame.height >= self.config.min_sprite_size,
+        });
+
+        frames
+    }
+
+    /// The background color of a sheet without an alpha channel whose border
+    /// is almost all that color, or `None` for any other sheet
+    fn uniform_background(&self, img: &DynamicImage) -> Option<Rgba<u8>> {
+        if let Some(sheet_background) = &self.sheet_background {
+            return sheet_background.uniform;
+        }
+        if img.color().has_alpha() {
+            return None;
+        }
+        let rgba_img = img.to_rgba8();
+        let (width, height) = rgba_img.dimensions();
+        let background = *self.sheet_background_colors(&rgba_img).first()?;
+        let border: Vec<_> = (0..width).flat_map(|x| [(x, 0), (x, height - 1)])
+            .chain((0..height).flat_map(|y| [(0, y), (width - 1, y)]))
+            .collect();
+        let matching = border.iter().filter(|&&(x, y)| self.matches_color(rgba_img.get_pixel(x, y), &background)).count();
+        (matching as f32 / border.len() as f32 >= UNIFORM_BORDER_RATIO).then_some(background)
+    }
+
+    /// Frames between the rows and columns of an opaque sheet that are mostly
+    /// `background` (or the configured gutter color), keeping cells where
+    /// enough pixels differ from it, tightened to those pixels
+    fn background_difference_frames(&self, img: &DynamicImage, background: Rgba<u8>) -> Vec<SpriteFrame> {
+        let rgba_img = img.to_rgba8();
+        let (width, height) = rgba_img.dimensions();
+        let is_background = |x: u32, y: u32| {
+            let pixel = rgba_img.get_pixel(x, y);
+            self.matches_color(pixel, &background)
+                || self.config.gutter_color.is_some_and(|gutter| self.matches_color(pixel, &gutter))
+        };
+        let mut vertical = self.empty_line_boundaries(width, height, is_background);
+        let mut horizontal = self.empty_line_boundaries(height, width, |y, x| is_background(x, y));
+        debug!("    → Found {} vertical and {} horizontal background boundaries", vertical.len(), horizontal.len());
+
+        if self.config.refine_boundaries {
+            vertical = self.refine_boundaries(&vertical, |x| (0..height).all(|y| is_background(x, y)));
+            horizontal = self.refine_boundaries(&horizontal, |y| (0..width).all(|x| is_background(x, y)));
+        }
+
+        let mut frames = Vec::new();
+        for (x, frame_width) in boundary_spans(&vertical) {
+            for (y, frame_height) in boundary_spans(&horizontal) {
+                if !self.long_enough(frame_width) || !self.long_enough(frame_height)
+                    || frame_width > self.config.max_sprite_size || frame_height > self.config.max_sprite_size {
+                    continue;
+                }
+                let content: Vec<(u32, u32)> = (y..y + frame_height)
+                    .flat_map(|py| (x..x + frame_width).map(move |px| (px, py)))
+                    .filter(|&(px, py)| !is_background(px, py))
+                    .collect();
+                let content_pixels = content.len() as u32;
+                if content_pixels as f32 / (frame_width * frame_height) as f32 <= self.config.content_threshold
+                    || self.config.min_content_pixels.is_some_and(|min| content_pixels < min) {
+                    continue;
+                }
+                let min_x = content.iter().map(|&(px, _)| px).min().unwrap();
+                let max_x = content.iter().map(|&(px, _)| px).max().unwrap();
+                let min_y = content.iter().map(|&(_, py)| py).min().unwrap();
+                let max_y = content.iter().map(|&(_, py)| py).max().unwrap();
+                frames.push(SpriteFrame::new(min_x, min_y, max_x - min_x + 1, max_y - min_y + 1));
+            }
+        }
+        frames
+    }
+
+    /// Fallback detection method for spritesheets that the main algorithm
+    /// misses. It works on 8-bit gray levels, which is plenty for telling
+    /// empty space from sprites.
+    fn fallback_detection(&self, img: &DynamicImage, gray_img: &Image<image::Luma<u8>>) -> Result<Detection> {
+        let (width, height) = img.dimensions();
+        let mut frames = Vec::new();
+        let rgba_img = img.to_rgba8();
+        let gutter = self.gutter_color(&rgba_img).map(|color| (&rgba_img, color));
+
+        // Try to detect horizontal spritesheets by finding actual empty space boundaries
+        let vertical_boundaries = self.find_empty_space_boundaries_horizontal(gray_img, gutter)?;
+        debug!("    → Found {} vertical boundaries: {:?}", vertical_boundaries.len(), vertical_boundaries);
+        
+        if vertical_boundaries.len() > 1 {
+            for (x, frame_width) in boundary_spans(&vertical_boundaries) {
+                // Validate frame size
+                if self.long_enough(frame_width)
+                    && frame_width <= self.config.max_sprite_size {
+                    
+                    // Check if frame contains content
+                    if self.frame_has_content(img, x, 0, frame_width, height) {
+                        frames.push(SpriteFrame::new(x, 0, frame_width, height));
+                    }
+                }
+            }
+        }
+
+        if !frames.is_empty() {
+            return Ok(Detection { frames, method: DetectionMethod::HorizontalFallback });
+        }
+
+        // Otherwise try vertical spritesheets
+        let horizontal_boundaries = self.find_empty_space_boundaries_vertical(gray_img, gutter)?;
+        debug!("    → Found {} horizontal boundaries: {:?}", horizontal_boundaries.len(), horizontal_boundaries);
+        
+        if horizontal_boundaries.len() > 1 {
+            for (y, frame_height) in boundary_spans(&horizontal_boundaries) {
+                // Validate frame size
+                if self.long_enough(frame_height)
+                    && frame_height <= self.config.max_sprite_size {
+                    
+                    // Check if frame contains content
+                    if self.frame_has_content(img, 0, y, width, frame_height) {
+                        frames.push(SpriteFrame::new(0, y, width, frame_height));
+                    }
+                }
+            }
+        }
+
+        Ok(Detection { frames, method: DetectionMethod::VerticalFallback })
+    }
+
+    /// Find vertical boundaries by detecting empty space columns. Pixels of
+    /// the gutter color, when given with the sheet, count as empty too.
+    fn find_empty_space_boundaries_horizontal(&self, gray_img: &Image<image::Luma<u8>>, gutter: Option<(&RgbaImage, Rgba<u8>)>)
+        -> Result<Vec<u32>> {
+        let (width, height) = gray_img.dimensions();
+        
+        // Detect the most common background color
+        let background_color = self.detect_most_common_color(gray_img);
+        
+        Ok(self.empty_line_boundaries(width, height, |x, y| {
+            let pixel = gray_img.get_pixel(x, y);
+            (pixel[0] as i32 - background_color as i32).abs() <= 15
+                || gutter.is_some_and(|(rgba_img, color)| self.matches_color(rgba_img.get_pixel(x, y), &color))
+        }))
+    }
+
+    /// Find horizontal boundaries by detecting empty space rows, like
+    /// [`Self::find_empty_space_boundaries_horizontal`]
+    fn find_empty_space_boundaries_vertical(&self, gray_img: &Image<image::Luma<u8>>, gutter: Option<(&RgbaImage, Rgba<u8>)>)
+        -> Result<Vec<u32>> {
+        let (width, height) = gray_img.dimensions();
+        
+        // Detect the most common background color
+        let background_color = self.detect_most_common_color(gray_img);
+        
+        Ok(self.empty_line_boundaries(height, width, |y, x| {
+            let pixel = gray_img.get_pixel(x, y);
+            (pixel[0] as i32 - background_color as i32).abs() <= 15
+                || gutter.is_some_and(|(rgba_img, color)| self.matches_color(rgba_img.get_pixel(x, y), &color))
+        }))
+    }
+
+    /// Boundaries at the lines (columns or rows) across `extent` in which
+    /// more than 85% of the `length` pixels are empty. `is_empty` takes the
+    /// line and the position along it.
+    fn empty_line_boundaries(&self, extent: u32, length: u32, is_empty: impl Fn(u32, u32) -> bool) -> Vec<u32> {
+        let mut candidates = Vec::new();
+        for line in 1..extent.saturating_sub(1) {
+            let empty_pixels = (0..length).filter(|&i| is_empty(line, i)).count();
+            
+            // If more than 85% of the line is background, it's a boundary
+            let empty_ratio = empty_pixels as f32 / length as f32;
+            if empty_ratio > 0.85 {
+                candidates.push((line, empty_ratio));
+            }
+        }
+        
+        // Boundaries closer together than min_sprite_size can't frame a sprite
+        merge_close_boundaries(&candidates, self.config.min_sprite_size.saturating_sub(1), extent)
+    }
+
+    /// Detect the most common gray level (likely background), sampling the
+    /// whole image unless another background strategy is configured
+    fn detect_most_common_color(&self, gray_img: &Image<image::Luma<u8>>) -> u8 {
+        let strategy = self.config.background_strategy.unwrap_or(BackgroundStrategy::GlobalMode);
+        most_common_pixel(gray_img, strategy).map_or(0, |pixel| pixel[0])
+    }
+
+    /// Find vertical boundaries (column separators). When `rgba_img` is given,
+    /// transparency is read from its alpha channel instead of guessed from luma.
+    ///
+    /// Columns are scored independently, so they are scanned in parallel.
+    pub fn find_vertical_boundaries(&self, gray_img: &Image<image::Luma<u16>>, rgba_img: Option<&RgbaImage>) -> Vec<u32> {
+        let width = gray_img.width();
+        // Candidate positions with how confident we are in each
+        let candidates: Vec<(u32, f32)> = (1..width.saturating_sub(1)).into_par_iter()
+            .filter_map(|x| self.column_boundary_score(gray_img, rgba_img, x).map(|score| (x, score)))
+            .collect();
+
+        merge_close_boundaries(&candidates, self.config.boundary_merge_distance, width)
+    }
+
+    /// Find horizontal boundaries (row separators). When `rgba_img` is given,
+    /// transparency is read from its alpha channel instead of guessed from luma.
+    ///
+    /// Rows are scored independently, so they are scanned in parallel.
+    pub fn find_horizontal_boundaries(&self, gray_img: &Image<image::Luma<u16>>, rgba_img: Option<&RgbaImage>) -> Vec<u32> {
+        let height = gray_img.height();
+        // Candidate positions with how confident we are in each
+        let candidates: Vec<(u32, f32)> = (1..height.saturating_sub(1)).into_par_iter()
+            .filter_map(|y| self.row_boundary_score(gray_img, rgba_img, y).map(|score| (y, score)))
+            .collect();
+
+        merge_close_boundaries(&candidates, self.config.boundary_merge_distance, height)
+    }
+
+    /// Vertical and horizontal boundaries at the valleys of the sheet's
+    /// projection profiles: how many foreground pixels each column and each
+    /// row holds. Foreground is read from alpha when the sheet has it, and
+    /// otherwise means not matching the background color.
+    ///
+    /// Unlike the transparency test, this still splits at gutters that hold
+    /// some stray pixels, as long as they are clearly sparser than the
+    /// sprites on both sides.
+    pub fn find_boundaries_by_projection(&self, img: &DynamicImage) -> (Vec<u32>, Vec<u32>) {
+        let rgba_img = img.to_rgba8();
+        let (width, height) = rgba_img.dimensions();
+        let has_alpha = img.color().has_alpha();
+        let background_colors = self.background_colors(&rgba_img);
+
+        let mut column_counts = vec![0u32; width as usize];
+        let mut row_counts = vec![0u32; height as usize];
+        for (x, y, pixel) in rgba_img.enumerate_pixels() {
+            let is_foreground = if has_alpha {
+                pixel[3] > self.config.alpha_threshold
+            } else {
+                !self.is_background_pixel(pixel, &background_colors)
+            };
+            if is_foreground {
+                column_counts[x as usize] += 1;
+                row_counts[y as usize] += 1;
+            }
+        }
+
+        (projection_valleys(&column_counts, self.config.boundary_merge_distance),
+         projection_valleys(&row_counts, self.config.boundary_merge_distance))
+    }
+
+    /// How confidently column `x` is a boundary: 1.0 when it is mostly
+    /// transparent, else its share of sharp color changes if that is high
+    /// enough, else `None`
+    fn column_boundary_score(&self, gray_img: &Image<image::Luma<u16>>, rgba_img: Option<&RgbaImage>, x: u32) -> Option<f32> {
+        let height = gray_img.height();
+        let is_empty = match rgba_img {
+            Some(rgba_img) => self.column_is_empty_alpha(rgba_img, x),
+            None => {
+                // Check if this column is mostly transparent or uniform
+                let mut transparent_count = 0;
+                for y in 0..height {
+                    let pixel = gray_img.get_pixel(x, y);
+                    if pixel[0] < DARK_LUMA { // Very dark/transparent
+                        transparent_count += 1;
+                    }
+                }
+                transparent_count as f32 / height as f32 > self.config.transparent_column_ratio
+            }
+        };
+        
+        // If enough of the column is transparent, it's likely a boundary
+        if is_empty {
+            return Some(1.0);
+        }
+
+        // Check for sudden color changes (edge detection) - more sensitive
+        let mut color_changes = 0;
+        for y in 0..height.saturating_sub(1) {
+            let current = gray_img.get_pixel(x, y)[0] as i32;
+            let next = gray_img.get_pixel(x, y + 1)[0] as i32;
+            if (current - next).abs() > self.config.color_change_delta as i32 * LEVEL_SCALE {
+                color_changes += 1;
+            }
+        }
+        
+        let change_ratio = color_changes as f32 / height as f32;
+        (change_ratio > self.config.color_change_ratio).then_some(change_ratio)
+    }
+
+    /// How confidently row `y` is a boundary, like [`Self::column_boundary_score`]
+    fn row_boundary_score(&self, gray_img: &Image<image::Luma<u16>>, rgba_img: Option<&RgbaImage>, y: u32) -> Option<f32> {
+        let width = gray_img.width();
+        let is_empty = match rgba_img {
+            Some(rgba_img) => self.row_is_empty_alpha(rgba_img, y),
+            None => {
+                // Check if this row is mostly transparent
+                let mut transparent_count = 0;
+                for x in 0..width {
+                    let pixel = gray_img.get_pixel(x, y);
+                    if pixel[0] < DARK_LUMA { // Very dark/transparent
+                        transparent_count += 1;
+                    }
+                }
+                transparent_count as f32 / width as f32 > self.config.transparent_column_ratio
+            }
+        };
+        
+        // If enough of the row is transparent, it's likely a boundary
+        if is_empty {
+            return Some(1.0);
+        }
+
+        // Check for sudden color changes - more sensitive
+        let mut color_changes = 0;
+        for x in 0..width.saturating_sub(1) {
+            let current = gray_img.get_pixel(x, y)[0] as i32;
+            let next = gray_img.get_pixel(x + 1, y)[0] as i32;
+            if (current - next).abs() > self.config.color_change_delta as i32 * LEVEL_SCALE {
+                color_changes += 1;
+            }
+        }
+        
+        let change_ratio = color_changes as f32 / width as f32;
+        (change_ratio > self.config.color_change_ratio).then_some(change_ratio)
+    }
+
+    /// Check whether enough of a column is at or below `alpha_threshold` to count as empty
+    fn column_is_empty_alpha(&self, rgba_img: &RgbaImage, x: u32) -> bool {
+        let height = rgba_img.height();
+        let transparent_count = (0..height)
+            .filter(|&y| rgba_img.get_pixel(x, y)[3] <= self.config.alpha_threshold)
+            .count();
+        transparent_count as f32 / height as f32 > self.config.transparent_column_ratio
+    }
+
+    /// Check whether enough of a row is at or below `alpha_threshold` to count as empty
+    fn row_is_empty_alpha(&self, rgba_img: &RgbaImage, y: u32) -> bool {
+        let width = rgba_img.width();
+        let transparent_count = (0..width)
+            .filter(|&x| rgba_img.get_pixel(x, y)[3] <= self.config.alpha_threshold)
+            .count();
+        transparent_count as f32 / width as f32 > self.config.transparent_column_ratio
+    }
+
+    /// Whether a frame extent passes the `min_sprite_size` check. Every
+    /// extent does when `min_content_pixels` is set, since the content pixel
+    /// count decides instead.
+    fn long_enough(&self, extent: u32) -> bool {
+        self.config.min_content_pixels.is_some() || extent >= self.config.min_sprite_size
+    }
+
+    /// Check if a frame contains meaningful content
+    fn frame_has_content(&self, img: &DynamicImage, x: u32, y: u32, width: u32, height: u32) -> bool {
+        let non_transparent_pixels = self.content_pixels(img, x, y, width, height);
+        let total_pixels = width * height;
+
+        // Frame has content if enough of its pixels are non-transparent
+        non_transparent_pixels as f32 / total_pixels as f32 > self.config.content_threshold
+            && self.config.min_content_pixels.is_none_or(|min| non_transparent_pixels >= min)
+    }
+
+    /// Number of pixels in a frame with alpha above `alpha_threshold` that,
+    /// on a sheet without alpha, don't match its background colors either
+    fn content_pixels(&self, img: &DynamicImage, x: u32, y: u32, width: u32, height: u32) -> u32 {
+        self.count_content_pixels(img, x, y, width, height, &self.content_backgrounds(img))
+    }
+
+    /// `content_pixels` with the sheet's `content_backgrounds` already known
+    fn count_content_pixels(&self, img: &DynamicImage, x: u32, y: u32, width: u32, height: u32,
+                            backgrounds: &[Rgba<u8>]) -> u32 {
+        let mut non_transparent_pixels = 0;
+        
+        for py in y..y + height {
+            for px in x..x + width {
+                if px < img.width() && py < img.height() {
+                    let pixel = img.get_pixel(px, py);
+                    if pixel[3] > self.config.alpha_threshold && !self.is_background_pixel(&pixel, backgrounds) {
+                        non_transparent_pixels += 1;
+                    }
+                }
+            }
+        }
+        non_transparent_pixels
+    }
+
+    /// Colors that aren't content on `img`: none on a sheet with alpha,
+    /// where transparency tells what is empty, or else its background colors
+    fn content_backgrounds(&self, img: &DynamicImage) -> Vec<Rgba<u8>> {
+        if img.color().has_alpha() {
+            return Vec::new();
+        }
+        match (self.config.background_key, &self.sheet_background) {
+            (Some(key), _) => vec![key],
+            (None, Some(sheet_background)) => sheet_background.colors.clone(),
+            (None, None) => self.detect_background_colors(&img.to_rgba8()),
+        }
+    }
+
+    /// Second stage of processing a sheet: cut every frame out of it and
+    /// remove the background if configured. Trimming, padding and scaling
+    /// happen as frames are saved.
+    ///
+    /// All frames are held in memory at once; see [`Self::extract_iter`] to
+    /// handle them one at a time instead.
+    pub fn extract_all(&self, img: &DynamicImage, frames: &[SpriteFrame]) -> Result<Vec<DynamicImage>> {
+        self.extract_iter(img, frames).collect()
+    }
+
+    /// Like [`Self::extract_all`], but cuts each frame only when the iterator
+    /// gets to it. A caller that saves and drops every frame before taking
+    /// the next keeps a single frame in memory besides the sheet, which
+    /// matters on sheets with hundreds of large frames. The frames can't be
+    /// compared with each other that way, so deduplication is up to the caller.
+    pub fn extract_iter<'a>(&'a self, img: &'a DynamicImage, frames: &'a [SpriteFrame])
+        -> impl Iterator<Item = Result<DynamicImage>> + 'a {
+        self.extract_frames(img, frames).map(|extracted| extracted.map(|(frame, _, _)| frame))
+    }
+
+    /// `extract_iter`, along with the opaque share of each frame whose
+    /// background was kept because of `background_opacity_floor` and the
+    /// first background color cleared from the others
+    fn extract_frames<'a>(&'a self, img: &'a DynamicImage, frames: &'a [SpriteFrame])
+        -> impl Iterator<Item = Result<(DynamicImage, Option<f32>, Option<Rgba<u8>>)>> + 'a {
+        // Found once for the sheet rather than for every frame
+        let backgrounds = self.config.background_opacity_floor.map(|_| self.content_backgrounds(img));
+        frames.iter()
+            .map(move |frame| {
+                let cropped = self.extract_frame(img, frame)?;
+                if !self.config.remove_background {
+                    return Ok((cropped, None, None));
+                }
+                let kept_background = backgrounds.as_ref()
+                    .and_then(|backgrounds| self.below_opacity_floor(img, frame, backgrounds));
+                if kept_background.is_some() {
+                    return Ok((cropped, kept_background, None));
+                }
+                let (processed, colors) = self.timed(Phase::BackgroundRemoval, || self.clear_background(&cropped));
+                Ok((processed, None, colors.first().copied()))
+            })
+    }
+
+    /// The opaque share of a frame, when it is too low for background
+    /// removal per `background_opacity_floor`
+    fn below_opacity_floor(&self, img: &DynamicImage, frame: &SpriteFrame, backgrounds: &[Rgba<u8>]) -> Option<f32> {
+        let floor = self.config.background_opacity_floor?;
+        let opaque = self.count_content_pixels(img, frame.x, frame.y, frame.width, frame.height, backgrounds);
+        let ratio = opaque as f32 / (frame.width * frame.height) as f32;
+        (ratio < floor).then_some(ratio)
+    }
+
+    /// Extract a frame from the image
+    pub fn extract_frame(&self, img: &DynamicImage, frame: &SpriteFrame) -> Result<DynamicImage> {
+        let cropped = img.crop_imm(frame.x, frame.y, frame.width, frame.height);
+        Ok(cropped)
+    }
+
+    /// Crop the image to the tight bounding box of pixels with alpha above
+    /// `alpha_threshold`, grown by `trim_margin` on every side within the image,
+    /// returning the trimmed image and the x/y offset that was cut away.
+    /// Fully transparent images are returned unchanged.
+    fn trim_transparent(&self, img: &DynamicImage) -> (DynamicImage, u32, u32) {
+        let rgba_img = img.to_rgba8();
+        let (width, height) = rgba_img.dimensions();
+        let mut bounds: Option<(u32, u32, u32, u32)> = None;
+
+        for (x, y, pixel) in rgba_img.enumerate_pixels() {
+            if pixel[3] > self.config.alpha_threshold {
+                bounds = Some(match bounds {
+                    None => (x, y, x, y),
+                    Some((min_x, min_y, max_x, max_y)) => (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)),
+                });
+            }
+        }
+
+        let margin = self.config.trim_margin;
+        let bounds = bounds.map(|(min_x, min_y, max_x, max_y)| {
+            (min_x.saturating_sub(margin), min_y.saturating_sub(margin),
+             max_x.saturating_add(margin).min(width - 1), max_y.saturating_add(margin).min(height - 1))
+        });
+
+        match bounds {
+            Some((min_x, min_y, max_x, max_y)) if (max_x - min_x + 1, max_y - min_y + 1) != (width, height) => {
+                let trimmed = img.crop_imm(min_x, min_y, max_x - min_x + 1, max_y - min_y + 1);
+                (trimmed, min_x, min_y)
+            }
+            _ => (img.clone(), 0, 0),
+        }
+    }
+
+    /// Average and most common color of the pixels with alpha above
+    /// `alpha_threshold`, both opaque, for swatches and placeholders. A frame
+    /// with no visible pixels gets transparent black for both.
+    pub fn frame_colors(&self, img: &DynamicImage) -> (Rgba<u8>, Rgba<u8>) {
+        let mut sums = [0u64; 3];
+        let mut counts: BTreeMap<[u8; 3], u32> = BTreeMap::new();
+        for pixel in img.to_rgba8().pixels().filter(|pixel| pixel[3] > self.config.alpha_threshold) {
+            for (sum, &channel) in sums.iter_mut().zip(&pixel.0[..3]) {
+                *sum += channel as u64;
+            }
+            *counts.entry([pixel[0], pixel[1], pixel[2]]).or_default() += 1;
+        }
+
+        let visible: u64 = counts.values().map(|&count| count as u64).sum();
+        if visible == 0 {
+            return (Rgba([0, 0, 0, 0]), Rgba([0, 0, 0, 0]));
+        }
+        let average = sums.map(|sum| ((sum + visible / 2) / visible) as u8);
+        // Ties go to the lowest color, so the result doesn't depend on pixel order
+        let (&dominant, _) = counts.iter().rev().max_by_key(|&(_, &count)| count).unwrap();
+        (Rgba([average[0], average[1], average[2], 255]), Rgba([dominant[0], dominant[1], dominant[2], 255]))
+    }
+
+    /// Center the image on a transparent canvas `padding` pixels larger on every side
+    fn pad_frame(&self, img: &DynamicImage, padding: u32) -> DynamicImage {
+        let (width, height) = img.dimensions();
+        let mut canvas = RgbaImage::new(width + 2 * padding, height + 2 * padding);
+        image::imageops::overlay(&mut canvas, &img.to_rgba8(), padding as i64, padding as i64);
+        DynamicImage::ImageRgba8(canvas)
+    }
+
+    /// Multiply each pixel's color by its alpha. Images without an alpha
+    /// channel are returned unchanged.
+    fn premultiply_alpha(&self, img: DynamicImage) -> DynamicImage {
+        if !img.color().has_alpha() {
+            return img;
+        }
+        let mut rgba_img = img.to_rgba8();
+        for pixel in rgba_img.pixels_mut() {
+            let alpha = pixel[3] as u32;
+            for channel in 0..3 {
+                pixel[channel] = ((pixel[channel] as u32 * alpha + 127) / 255) as u8;
+            }
+        }
+        DynamicImage::ImageRgba8(rgba_img)
+    }
+
+    /// Apply `scale` or `scale_down` with nearest-neighbor sampling, so
+    /// pixel art stays crisp. Shrinking never goes below 1x1.
+    fn scale_frame(&self, img: DynamicImage) -> DynamicImage {
+        let (width, height) = img.dimensions();
+        let (new_width, new_height) = match (self.config.scale, self.config.scale_down) {
+            (Some(factor), _) if factor > 1 => (width * factor, height * factor),
+            (_, Some(divisor)) if divisor > 1 => ((width / divisor).max(1), (height / divisor).max(1)),
+            _ => return img,
+        };
+        img.resize_exact(new_width, new_height, FilterType::Nearest)
+    }
+
+    /// Remove background from the image
+    pub fn remove_background(&self, img: &DynamicImage) -> Result<DynamicImage> {
+        Ok(self.clear_background(img).0)
+    }
+
+    /// `remove_background`, along with the background colors it cleared
+    fn clear_background(&self, img: &DynamicImage) -> (DynamicImage, Vec<Rgba<u8>>) {
+        let mut rgba_img = img.to_rgba8();
+        let (width, height) = rgba_img.dimensions();
+        
+        let background_colors = self.background_colors(&rgba_img);
+        
+        if self.config.background_mode == BackgroundMode::FloodFill {
+            self.flood_fill_background(&mut rgba_img, &background_colors);
+            return (DynamicImage::ImageRgba8(rgba_img), background_colors);
+        }
+
+        // Make background transparent (or the replacement color)
+        let fill = self.background_fill();
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = rgba_img.get_pixel(x, y);
+                if self.is_background_pixel(pixel, &background_colors) {
+                    rgba_img.put_pixel(x, y, fill);
+                }
+            }
+        }
+        
+        (DynamicImage::ImageRgba8(rgba_img), background_colors)
+    }
+
+    /// How `remove_background` clears frames: its mode, and where the
+    /// background color comes from
+    fn background_removal(&self) -> serde_json::Value {
+        let color = match self.config.background_key {
+            Some(_) => serde_json::json!("key"),
+            None => serde_json::json!(self.config.background_strategy.unwrap_or(BackgroundStrategy::Corners)),
+        };
+        serde_json::json!({ "mode": self.config.background_mode, "color": color })
+    }
+
+    /// Add the background cleared from most of `saved_frames` to the `meta`
+    /// of a TexturePacker hash as `#rrggbbaa`, along with how it was removed
+    fn record_removed_background(&self, atlas: &mut serde_json::Value, saved_frames: &[SavedFrame]) {
+        if let Some(Rgba([r, g, b, a])) = removed_background(saved_frames) {
+            atlas["meta"]["background_color"] = serde_json::json!(format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a));
+            atlas["meta"]["background_removal"] = self.background_removal();
+        }
+    }
+
+    /// What removed background pixels become: the replacement color if one
+    /// is configured, else fully transparent
+    fn background_fill(&self) -> Rgba<u8> {
+        self.config.background_replacement.unwrap_or(Rgba([0, 0, 0, 0]))
+    }
+
+    /// Clear background-colored pixels reachable from the image border through
+    /// other background-colored pixels (4-connected)
+    fn flood_fill_background(&self, rgba_img: &mut RgbaImage, background_colors: &[Rgba<u8>]) {
+        let (width, height) = rgba_img.dimensions();
+        let fill = self.background_fill();
+        let mut visited = vec![false; (width as usize) * (height as usize)];
+        let mut stack = Vec::new();
+
+        // Seed with every border pixel
+        for x in 0..width {
+            stack.push((x, 0));
+            stack.push((x, height.saturating_sub(1)));
+        }
+        for y in 0..height {
+            stack.push((0, y));
+            stack.push((width.saturating_sub(1), y));
+        }
+
+        while let Some((x, y)) = stack.pop() {
+            let index = (y * width + x) as usize;
+            if visited[index] || !self.is_background_pixel(rgba_img.get_pixel(x, y), background_colors) {
+                continue;
+            }
+            visited[index] = true;
+            rgba_img.put_pixel(x, y, fill);
+
+            if x > 0 { stack.push((x - 1, y)); }
+            if x + 1 < width { stack.push((x + 1, y)); }
+            if y > 0 { stack.push((x, y - 1)); }
+            if y + 1 < height { stack.push((x, y + 1)); }
+        }
+    }
+
+    /// The colors that count as background: those of the sheet and the
+    /// configured gutter color, if any
+    fn background_colors(&self, img: &RgbaImage) -> Vec<Rgba<u8>> {
+        let mut colors = self.sheet_background_colors(img);
+        colors.extend(self.config.gutter_color);
+        colors
+    }
+
+    /// The configured key color, or else the detected background colors
+    fn sheet_background_colors(&self, img: &RgbaImage) -> Vec<Rgba<u8>> {
+        match (self.config.background_key, &self.sheet_background) {
+            (Some(key), _) => vec![key],
+            (None, Some(sheet_background)) => sheet_background.colors.clone(),
+            (None, None) => self.detect_background_colors(img),
+        }
+    }
+
+    /// The configured gutter color, or else, with `detect_gutter`, the color
+    /// of the first thin uniform line that crosses the whole sheet, is opaque
+    /// and isn't a background color. Columns are checked before rows.
+    fn gutter_color(&self, img: &RgbaImage) -> Option<Rgba<u8>> {
+        if self.config.gutter_color.is_some() || !self.config.detect_gutter {
+            return self.config.gutter_color;
+        }
+        // Every line of a sheet too thin to hold a frame would look uniform
+        let (width, height) = img.dimensions();
+        if width.min(height) < self.config.min_sprite_size.max(3) {
+            return None;
+        }
+        let backgrounds = self.sheet_background_colors(img);
+        let uniform_color = |pixel_at: &dyn Fn(u32) -> Rgba<u8>, length: u32| {
+            let first = pixel_at(0);
+            let is_gutter = first[3] > self.config.alpha_threshold && !self.is_background_pixel(&first, &backgrounds);
+            (is_gutter && (1..length).all(|i| self.matches_color(&pixel_at(i), &first))).then_some(first)
+        };
+        let columns: Vec<_> = (1..width.saturating_sub(1))
+            .map(|x| uniform_color(&|y| *img.get_pixel(x, y), height))
+            .collect();
+        let rows: Vec<_> = (1..height.saturating_sub(1))
+            .map(|y| uniform_color(&|x| *img.get_pixel(x, y), width))
+            .collect();
+        // Wide bands of color are more likely sprites or panels than gutters
+        [columns, rows].iter()
+            .flat_map(|lines| lines.chunk_by(|a, b| a.is_some() == b.is_some()))
+            .find_map(|run| run.first().copied().flatten().filter(|_| run.len() <= MAX_GUTTER_WIDTH))
+    }
+
+    /// Detect the `background_color_count` most common colors among the
+    /// corner pixels, unless another background strategy is configured,
+    /// leaving out the gutter color. Colors after the first need at least
+    /// `MIN_BACKGROUND_COLOR_SHARE` of the samples.
+    fn detect_background_colors(&self, img: &RgbaImage) -> Vec<Rgba<u8>> {
+        let strategy = self.config.background_strategy.unwrap_or(BackgroundStrategy::Corners);
+        let counts = pixel_counts(img, strategy);
+        let samples: usize = counts.iter().map(|&(_, count)| count).sum();
+        // A frame's corners may sit on the gutter lines around it
+        let colors: Vec<_> = counts.into_iter()
+            .filter(|(color, _)| self.config.gutter_color.is_none_or(|gutter| !self.matches_color(color, &gutter)))
+            .enumerate()
+            .take_while(|&(i, (_, count))| i == 0 || count as f32 >= samples as f32 * MIN_BACKGROUND_COLOR_SHARE)
+            .map(|(_, (color, _))| color)
+            .take(self.config.background_color_count)
+            .collect();
+        if colors.is_empty() {
+            return vec![Rgba([255, 255, 255, 255])];
+        }
+        colors
+    }
+
+    /// Check if a pixel matches any of the background colors
+    fn is_background_pixel(&self, pixel: &Rgba<u8>, backgrounds: &[Rgba<u8>]) -> bool {
+        backgrounds.iter().any(|background| self.matches_color(pixel, background))
+    }
+
+    /// Check if a pixel is within the background tolerance of `background`
+    fn matches_color(&self, pixel: &Rgba<u8>, background: &Rgba<u8>) -> bool {
+        let tolerance = self.config.background_tolerance as i32;
+        if self.config.background_match_alpha && (pixel[3] as i32 - background[3] as i32).abs() > tolerance {
+            return false;
+        }
+        
+        match self.config.color_distance {
+            ColorMetric::PerChannel => {
+                (pixel[0] as i32 - background[0] as i32).abs() <= tolerance &&
+                (pixel[1] as i32 - background[1] as i32).abs() <= tolerance &&
+                (pixel[2] as i32 - background[2] as i32).abs() <= tolerance
+            }
+            ColorMetric::RgbEuclidean => {
+                let squared: i32 = (0..3).map(|c| (pixel[c] as i32 - background[c] as i32).pow(2)).sum();
+                squared <= tolerance * tolerance
+            }
+            ColorMetric::CielabDeltaE => {
+                let (a, b) = (srgb_to_lab(pixel), srgb_to_lab(background));
+                let delta_e = ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt();
+                delta_e <= tolerance as f32 * 100.0 / 255.0
+            }
+        }
+    }
+}
+
+/// Convert an sRGB color to CIE L*a*b* under the D65 white point
+fn srgb_to_lab(pixel: &Rgba<u8>) -> [f32; 3] {
+    let linear = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    };
+    let (r, g, b) = (linear(pixel[0]), linear(pixel[1]), linear(pixel[2]));
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+    let f = |t: f32| if t > 0.008856 { t.cbrt() } else { 7.787 * t + 16.0 / 116.0 };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// Positions sampled by a background `strategy` in a `width`x`height` image,
+/// each listed once
+fn background_samples(width: u32, height: u32, strategy: BackgroundStrategy) -> Vec<(u32, u32)> {
+    // The leading and trailing `size` positions along an axis, without overlap
+    let ends = |extent: u32, size: u32| (0..size.min(extent)).chain(size.max(extent.saturating_sub(size))..extent);
+    match strategy {
+        BackgroundStrategy::Corners => ends(height, 10)
+            .flat_map(|y| ends(width, 10).map(move |x| (x, y)))
+            .collect(),
+        BackgroundStrategy::GlobalMode => (0..height).step_by(4)
+            .flat_map(|y| (0..width).step_by(4).map(move |x| (x, y)))
+            .collect(),
+        BackgroundStrategy::EdgePixels => ends(height, 1)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .chain((1..height.saturating_sub(1)).flat_map(|y| ends(width, 1).map(move |x| (x, y))))
+            .collect(),
+    }
+}
+
+/// The most common pixel among those a background `strategy` samples, or
+/// `None` for an empty image
+fn most_common_pixel<P>(img: &image::ImageBuffer<P, Vec<P::Subpixel>>, strategy: BackgroundStrategy) -> Option<P>
+where
+    P: image::Pixel + Eq + Hash,
+{
+    most_common_pixels(img, strategy, 1).into_iter().next()
+}
+
+/// Up to `count` of the most common pixels among those a background
+/// `strategy` samples, most common first
+fn most_common_pixels<P>(img: &image::ImageBuffer<P, Vec<P::Subpixel>>, strategy: BackgroundStrategy,
+                         count: usize) -> Vec<P>
+where
+    P: image::Pixel + Eq + Hash,
+{
+    pixel_counts(img, strategy).into_iter().take(count).map(|(pixel, _)| pixel).collect()
+}
+
+/// How often each pixel occurs among those a background `strategy` samples,
+/// most common first. Equally common pixels come lowest channel values
+/// first, so the order doesn't depend on hashing.
+fn pixel_counts<P>(img: &image::ImageBuffer<P, Vec<P::Subpixel>>, strategy: BackgroundStrategy) -> Vec<(P, usize)>
+where
+    P: image::Pixel + Eq + Hash,
+{
+    let mut counts: HashMap<P, usize> = HashMap::new();
+    for (x, y) in background_samples(img.width(), img.height(), strategy) {
+        *counts.entry(*img.get_pixel(x, y)).or_insert(0) += 1;
+    }
+    let mut counts: Vec<(P, usize)> = counts.into_iter().collect();
+    counts.sort_by(|(a, a_count), (b, b_count)| {
+        b_count.cmp(a_count).then_with(|| a.channels().partial_cmp(b.channels()).unwrap_or(std::cmp::Ordering::Equal))
+    });
+    counts
+}
+
+/// Frame rects of a TexturePacker JSON hash (`frames` is an object keyed by
+/// name) or Aseprite/TexturePacker JSON array (`frames` is a list). Rotated
+/// frames are cut as they lie on the sheet, i.e. with width and height swapped.
+fn atlas_frames(atlas: &serde_json::Value) -> Result<Vec<SpriteFrame>, String> {
+    let entries: Vec<&serde_json::Value> = match &atlas["frames"] {
+        serde_json::Value::Object(frames) => frames.values().collect(),
+        serde_json::Value::Array(frames) => frames.iter().collect(),
+        _ => return Err("expected a TexturePacker or Aseprite atlas with a \"frames\" object or array".to_string()),
+    };
+
+    entries.into_iter().enumerate()
+        .map(|(index, entry)| {
+            let field = |name: &str| entry["frame"][name].as_u64()
+                .and_then(|value| u32::try_from(value).ok())
+                .ok_or_else(|| format!("frame {} has no valid \"{}\"", index + 1, name));
+            let (x, y, w, h) = (field("x")?, field("y")?, field("w")?, field("h")?);
+            if w == 0 || h == 0 {
+                return Err(format!("frame {} is empty", index + 1));
+            }
+            let (width, height) = if entry["rotated"] == true { (h, w) } else { (w, h) };
+            Ok(SpriteFrame::new(x, y, width, height))
+        })
+        .collect()
+}
+
+/// Compile file name glob patterns; matching ignores case, like the
+/// extension check
+fn glob_set(patterns: &[String]) -> Result<globset::GlobSet> {
+    let mut builder = globset::GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = globset::GlobBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| CutterError::InvalidConfig(format!("invalid file pattern '{}': {}", pattern, e)))?;
+        builder.add(glob);
+    }
+    builder.build()
+        .map_err(|e| CutterError::InvalidConfig(format!("invalid file patterns: {}", e)))
+}
+
+/// File name `--generate-sample` writes `sample_sheet` to
+pub const SAMPLE_SHEET_FILE: &str = "sample_sheet.png";
+
+/// A small spritesheet to try the cutter on: a 4x2 grid of 32x32 cells on a
+/// transparent background, each holding a 24x24 square, octagon, rounded
+/// square or ring in its own color. Cutting it with the defaults gives 8
+/// frames, each covering its whole shape.
+pub fn sample_sheet() -> RgbaImage {
+    const CELL: u32 = 32;
+    const SHAPE: i32 = 24;
+    const COLORS: [[u8; 3]; 8] = [
+        [230, 60, 60], [60, 170, 230], [250, 200, 40], [120, 200, 80],
+        [170, 90, 220], [240, 130, 40], [40, 200, 180], [220, 80, 160],
+    ];
+
+    let mut sheet = RgbaImage::new(4 * CELL, 2 * CELL);
+    for (index, [r, g, b]) in COLORS.into_iter().enumerate() {
+        let (col, row) = (index as u32 % 4, index as u32 / 4);
+        let (left, top) = (col * CELL + 4, row * CELL + 4);
+        for y in 0..SHAPE {
+            for x in 0..SHAPE {
+                // Distances from the shape's center, doubled to stay in integers.
+                // Pointed shapes would leave rows and columns so sparse near
+                // their tips that detection cuts there, so every shape is bulky.
+                let (dx, dy) = ((2 * x + 1 - SHAPE).abs(), (2 * y + 1 - SHAPE).abs());
+                let inside = match (col + row) % 4 {
+                    0 => true,
+                    1 => dx + dy <= 2 * SHAPE - 8,
+                    2 => dx <= 12 || dy <= 12 || (dx - 12).pow(2) + (dy - 12).pow(2) <= 144,
+                    _ => dx > 12 || dy > 12,
+                };
+                if inside {
+                    sheet.put_pixel(left + x as u32, top + y as u32, Rgba([r, g, b, 255]));
+                }
+            }
+        }
+    }
+    sheet
+}
+
+/// Parse a hex color such as `ff00ff`, `#FF00FF` or `ff00ff80` (with alpha)
+pub fn parse_hex_color(value: &str) -> Result<Rgba<u8>, String> {
+    let hex = value.trim().trim_start_matches('#');
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("expected a hex color like ff00ff, got '{}'", value));
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+    let alpha = if hex.len() == 8 { channel(6) } else { 255 };
+    Ok(Rgba([channel(0), channel(2), channel(4), alpha]))
+}
+
+/// The background color removed from most of `saved_frames`, the earliest
+/// one on a tie, or `None` if no frame had its background removed
+fn removed_background(saved_frames: &[SavedFrame]) -> Option<Rgba<u8>> {
+    let mut counts: Vec<(Rgba<u8>, usize)> = Vec::new();
+    for background in saved_frames.iter().filter_map(|saved| saved.background) {
+        match counts.iter_mut().find(|(color, _)| *color == background) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((background, 1)),
+        }
+    }
+    counts.into_iter().rev().max_by_key(|&(_, count)| count).map(|(color, _)| color)
+}
+
+/// `color` as a hex string, with alpha only when it isn't opaque
+fn hex_string(color: Rgba<u8>) -> String {
+    match color {
+        Rgba([r, g, b, 255]) => format!("{:02x}{:02x}{:02x}", r, g, b),
+        Rgba([r, g, b, a]) => format!("{:02x}{:02x}{:02x}{:02x}", r, g, b, a),
+    }
+}
+
+/// Serde adapter storing optional colors as hex strings, with alpha only
+/// when it isn't opaque
+mod hex_color {
+    use image::Rgba;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Option<Rgba<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match color {
+            Some(color) => serializer.serialize_str(&super::hex_string(*color)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Rgba<u8>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|hex| super::parse_hex_color(&hex).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+/// Serde adapter writing `f32` settings as the shortest decimal that reads
+/// back the same, so `0.6` isn't printed as `0.6000000238418579`
+mod compact_f32 {
+    use serde::Serializer;
+
+    fn widen(value: f32) -> f64 {
+        value.to_string().parse().unwrap_or(value as f64)
+    }
+
+    pub fn serialize<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(widen(*value))
+    }
+
+    pub fn serialize_option<S: Serializer>(value: &Option<f32>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serializer.serialize_some(&widen(*value)),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+/// Open and decode an image file, turning it upright according to its EXIF
+/// orientation if it has one
+fn open_image(path: &Path) -> Result<DynamicImage> {
+    // TGA and DDS are listed even when their Cargo feature is off, so they
+    // fail here by name rather than as an undecodable file
+    if let Ok(format) = image::ImageFormat::from_path(path) {
+        if !format.reading_enabled() {
+            let format = format.extensions_str().first().copied().unwrap_or_default().to_string();
+            return Err(CutterError::UnsupportedFormat { path: path.to_path_buf(), format });
+        }
+    }
+    let img = image::open(path)
+        .map_err(|source| CutterError::OpenImage { path: path.to_path_buf(), source })?;
+    Ok(apply_orientation(img, exif_orientation(path)))
+}
+
+/// Lay the frames of an animated GIF side by side in a horizontal strip,
+/// one detected frame per GIF frame in playback order. `image::open` only
+/// decodes the first frame, so a GIF with one frame is left to it and gets
+/// `None`.
+fn gif_animation_strip(path: &Path) -> Result<Option<(DynamicImage, Detection)>> {
+    if !matches!(image::ImageFormat::from_path(path), Ok(image::ImageFormat::Gif)) {
+        return Ok(None);
+    }
+    let open_error = |source: image::ImageError| CutterError::OpenImage { path: path.to_path_buf(), source };
+    let file = fs::File::open(path).map_err(|source| open_error(source.into()))?;
+    let decoder = image::codecs::gif::GifDecoder::new(io::BufReader::new(file)).map_err(open_error)?;
+    // Frames come out composited onto the full canvas, so all share its size
+    let frames = decoder.into_frames().collect_frames().map_err(open_error)?;
+    if frames.len() < 2 {
+        return Ok(None);
+    }
+
+    let (width, height) = frames[0].buffer().dimensions();
+    let mut strip = RgbaImage::new(width * frames.len() as u32, height);
+    let mut sprite_frames = Vec::with_capacity(frames.len());
+    for (index, frame) in frames.iter().enumerate() {
+        let x = index as u32 * width;
+        image::imageops::replace(&mut strip, frame.buffer(), x as i64, 0);
+        sprite_frames.push(SpriteFrame::new(x, 0, width, height));
+    }
+    Ok(Some((DynamicImage::ImageRgba8(strip), Detection { frames: sprite_frames, method: DetectionMethod::GifFrames })))
+}
+
+/// The RGBA color that `index` decodes to: a palette entry (with its tRNS
+/// alpha) for indexed PNGs, or that gray level for 8-bit grayscale images.
+///
+/// Decoding expands palettes, so this reads the PNG header directly.
+fn palette_color(path: &Path, index: u8) -> Result<Rgba<u8>> {
+    let unusable = |reason: String| CutterError::BackgroundIndex { path: path.to_path_buf(), index, reason };
+
+    if matches!(image::ImageFormat::from_path(path), Ok(image::ImageFormat::Png)) {
+        let file = fs::File::open(path)
+            .map_err(|source| CutterError::OpenImage { path: path.to_path_buf(), source: source.into() })?;
+        let reader = png::Decoder::new(io::BufReader::new(file)).read_info()
+            .map_err(|e| unusable(e.to_string()))?;
+        let info = reader.info();
+        match (info.color_type, info.bit_depth) {
+            (png::ColorType::Indexed, _) => {
+                let palette = info.palette.as_deref().unwrap_or_default();
+                let slot = index as usize * 3;
+                let Some(rgb) = palette.get(slot..slot + 3) else {
+                    return Err(unusable(format!("the palette only has {} entries", palette.len() / 3)));
+                };
+                let alpha = info.trns.as_deref().and_then(|trns| trns.get(index as usize)).copied().unwrap_or(255);
+                return Ok(Rgba([rgb[0], rgb[1], rgb[2], alpha]));
+            }
+            (png::ColorType::Grayscale, depth) if depth != png::BitDepth::Sixteen => {
+                // Low bit depths are scaled up to 0-255 when decoded
+                let max_level = (1u16 << depth as u8) - 1;
+                if index as u16 > max_level {
+                    return Err(unusable(format!("the image only has gray levels 0-{}", max_level)));
+                }
+                let level = (index as u16 * 255 / max_level) as u8;
+                return Ok(Rgba([level, level, level, 255]));
+            }
+            _ => {}
+        }
+    } else if let DynamicImage::ImageLuma8(_) = open_image(path)? {
+        return Ok(Rgba([index, index, index, 255]));
+    }
+    Err(unusable("it isn't an indexed or 8-bit grayscale image".to_string()))
+}
+
+/// Read the EXIF orientation tag (1-8) of a JPEG, TIFF or WebP file.
+///
+/// Other formats, files without EXIF data and unreadable tags all count as
+/// 1, "already upright".
+fn exif_orientation(path: &Path) -> u32 {
+    let carries_exif = matches!(image::ImageFormat::from_path(path),
+                                Ok(image::ImageFormat::Jpeg | image::ImageFormat::Tiff | image::ImageFormat::WebP));
+    if !carries_exif {
+        return 1;
+    }
+
+    let Ok(file) = fs::File::open(path) else { return 1 };
+    let Ok(exif) = exif::Reader::new().read_from_container(&mut io::BufReader::new(file)) else { return 1 };
+    exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        .unwrap_or(1)
+}
+
+/// Rotate and/or flip an image so that EXIF `orientation` becomes upright
+fn apply_orientation(img: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+/// Build a TexturePacker JSON hash for `saved_frames`, where `origins` gives
+/// the top-left corner of each frame's (unpadded) pixels in the atlas image.
+/// Frames centered on a uniform canvas take that canvas as their source size,
+/// with the centering offset in `spriteSourceSize`. The detection path and
+/// the source sheet's path within its input folder are recorded in `meta`
+/// next to the usual fields.
+fn texture_packer_hash(image_name: &str, image_size: (u32, u32), saved_frames: &[SavedFrame],
+                       origins: &[(u32, u32)], method: DetectionMethod, source: &str) -> serde_json::Value {
+    let mut frames = serde_json::Map::new();
+    for (saved, &(x, y)) in saved_frames.iter().zip(origins) {
+        let (source_x, source_y, source_size) = saved.source_rect();
+        let mut entry = serde_json::json!({
+            "frame": { "x": x, "y": y, "w": saved.width, "h": saved.height },
+            "rotated": false,
+            "trimmed": saved.is_trimmed(),
+            "spriteSourceSize": { "x": source_x, "y": source_y, "w": saved.width, "h": saved.height },
+            "sourceSize": { "w": source_size.0, "h": source_size.1 },
+        });
+        if let Some((pivot_x, pivot_y)) = saved.pivot {
+            // Three decimals are finer than a pixel on any frame this tool cuts
+            let round = |value: f32| (value as f64 * 1000.0).round() / 1000.0;
+            entry["pivot"] = serde_json::json!({ "x": round(pivot_x), "y": round(pivot_y) });
+        }
+        if let Some((average, dominant)) = saved.colors {
+            entry["averageColor"] = serde_json::json!(hex_string(average));
+            entry["dominantColor"] = serde_json::json!(hex_string(dominant));
+        }
+        frames.insert(saved.filename.clone(), entry);
+    }
+
+    serde_json::json!({
+        "frames": frames,
+        "meta": {
+            "app": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+            "image": image_name,
+            "format": "RGBA8888",
+            "size": { "w": image_size.0, "h": image_size.1 },
+            "scale": "1",
+            "detection": method,
+            "source": source,
+        },
+    })
+}
+
+/// Build a Sparrow texture atlas for `saved_frames`, with `origins` as in
+/// [`texture_packer_hash`]. Each `SubTexture` is named after its frame file
+/// without the extension, which is how Starling and HaxeFlixel look frames
+/// up. Frames whose pixels don't fill their source size (trimmed, padded or
+/// on a uniform canvas) get `frameX`/`frameY`/`frameWidth`/`frameHeight`,
+/// and frames with a pivot get `pivotX`/`pivotY` in pixels.
+fn sparrow_xml(image_name: &str, saved_frames: &[SavedFrame], origins: &[(u32, u32)]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml += &format!("<TextureAtlas imagePath=\"{}\">\n", xml_escape(image_name));
+    for (saved, &(x, y)) in saved_frames.iter().zip(origins) {
+        let name = Path::new(&saved.filename).file_stem().unwrap_or_default().to_string_lossy();
+        xml += &format!("    <SubTexture name=\"{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
+                        xml_escape(&name), x, y, saved.width, saved.height);
+        let (source_x, source_y, source_size) = saved.source_rect();
+        if (source_x, source_y, source_size) != (0, 0, (saved.width, saved.height)) {
+            xml += &format!(" frameX=\"{}\" frameY=\"{}\" frameWidth=\"{}\" frameHeight=\"{}\"",
+                            -(source_x as i64), -(source_y as i64), source_size.0, source_size.1);
+        }
+        if let Some((pivot_x, pivot_y)) = saved.pivot {
+            let pixels = |fraction: f32, size: u32| (fraction as f64 * size as f64 * 1000.0).round() / 1000.0;
+            xml += &format!(" pivotX=\"{}\" pivotY=\"{}\"", pixels(pivot_x, source_size.0), pixels(pivot_y, source_size.1));
+        }
+        xml += "/>\n";
+    }
+    xml += "</TextureAtlas>\n";
+    xml
+}
+
+/// Escape text for use in an XML attribute value
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Alpha-weighted center of mass of an image, measured from its top-left
+/// corner with each pixel counted at its middle, or `None` when it is fully
+/// transparent
+fn alpha_centroid(img: &DynamicImage) -> Option<(f32, f32)> {
+    let (mut total, mut sum_x, mut sum_y) = (0u64, 0u64, 0u64);
+    for (x, y, pixel) in img.to_rgba8().enumerate_pixels() {
+        let weight = pixel[3] as u64;
+        total += weight;
+        sum_x += weight * (2 * x as u64 + 1);
+        sum_y += weight * (2 * y as u64 + 1);
+    }
+    (total > 0).then(|| (sum_x as f32 / total as f32 / 2.0, sum_y as f32 / total as f32 / 2.0))
+}
+
+/// Collapse boundary candidates into a sorted list of boundaries.
+///
+/// Candidates are `(position, confidence)` pairs. Runs of candidates each
+/// within `distance` pixels of the previous one become a single boundary at
+/// the most confident position, or the middle of the most confident ones when
+/// several tie. `0` and `extent` are always boundaries and absorb any run
+/// they fall into.
+fn merge_close_boundaries(candidates: &[(u32, f32)], distance: u32, extent: u32) -> Vec<u32> {
+    let mut sorted: Vec<(u32, f32)> = candidates.iter()
+        .copied()
+        .filter(|&(position, _)| position > 0 && position < extent)
+        .collect();
+    sorted.push((0, f32::INFINITY));
+    sorted.push((extent, f32::INFINITY));
+    sorted.sort_by_key(|&(position, _)| position);
+    sorted.dedup_by_key(|&mut (position, _)| position);
+
+    let mut boundaries = Vec::new();
+    let mut run_start = 0;
+    for i in 1..=sorted.len() {
+        if i < sorted.len() && sorted[i].0 - sorted[i - 1].0 <= distance {
+            continue;
+        }
+
+        let run = &sorted[run_start..i];
+        let best = run.iter().map(|&(_, confidence)| confidence).fold(f32::MIN, f32::max);
+        let tied: Vec<u32> = run.iter()
+            .filter(|&&(_, confidence)| confidence == best)
+            .map(|&(position, _)| position)
+            .collect();
+        if best == f32::INFINITY {
+            // Image edges; both survive even if the run spans the whole image
+            boundaries.extend(tied);
+        } else {
+            boundaries.push(tied[tied.len() / 2]);
+        }
+        run_start = i;
+    }
+    boundaries
+}
+
+/// Remove the boundaries that only look like a gap because they run next to
+/// a sprite touching the image edge. Starting from an edge whose outermost
+/// line has content, boundaries are dropped inward for as long as they are
+/// adjacent and still have content; the first empty line is a real gap and
+/// stays. `boundaries` includes `0` and `extent`, which are kept.
+fn drop_edge_gutters(boundaries: &mut Vec<u32>, extent: u32, has_content: impl Fn(u32) -> bool) {
+    if extent == 0 {
+        return;
+    }
+    if has_content(0) {
+        let mut next = 1;
+        boundaries.retain(|&position| {
+            if position != next || !has_content(position) {
+                return true;
+            }
+            next += 1;
+            false
+        });
+    }
+    // The last line is never a boundary candidate, so the edge run ends one before it
+    if extent >= 2 && has_content(extent - 1) {
+        let mut next = extent - 2;
+        let mut dropped = Vec::new();
+        for &position in boundaries.iter().rev().skip(1) {
+            if position != next || position == 0 || !has_content(position) {
+                break;
+            }
+            dropped.push(position);
+            next = position.saturating_sub(1);
+        }
+        boundaries.retain(|position| !dropped.contains(position));
+    }
+}
+
+/// How much the areas of `frames` vary: their standard deviation over their
+/// mean, 0 when all frames are the same size
+fn area_spread(frames: &[SpriteFrame]) -> f32 {
+    let areas: Vec<f32> = frames.iter().map(|frame| (frame.width * frame.height) as f32).collect();
+    let mean = areas.iter().sum::<f32>() / areas.len() as f32;
+    let variance = areas.iter().map(|area| (area - mean).powi(2)).sum::<f32>() / areas.len() as f32;
+    variance.sqrt() / mean
+}
+
+/// Attempts of the adaptive retry, from mild to aggressive, as factors for
+/// `(transparent_column_ratio, color_change_ratio, content_threshold)`
+const RELAXED_THRESHOLDS: [(f32, f32, f32); 3] = [(0.85, 0.75, 0.5), (0.7, 0.5, 0.25), (0.55, 0.25, 0.125)];
+
+/// Factor from the 8-bit levels thresholds are given in to the 16-bit levels
+/// boundary detection compares; 8-bit level `v` widens to exactly `v * 257`
+const LEVEL_SCALE: i32 = 257;
+
+/// 16-bit luma below which a pixel counts as very dark or transparent
+/// (8-bit level 10) when a sheet has no alpha channel to ask
+const DARK_LUMA: u16 = 10 * LEVEL_SCALE as u16;
+
+/// Fraction of the sheet a single frame may cover before `validate_frames`
+/// warns that detection probably missed the gaps
+const LARGE_FRAME_COVERAGE: f64 = 0.9;
+
+/// How many times larger than the smallest frame the largest may be before
+/// `validate_frames` warns about the spread of sizes
+const FRAME_AREA_SPREAD: f64 = 10.0;
+
+/// Frames on one sheet from which `suggest_detector` takes the sheet to be
+/// cut into specks rather than sprites
+const MANY_FRAMES: usize = 1000;
+
+/// Widest run of uniform lines, in pixels, that is taken for a gutter when
+/// the gutter color is detected
+const MAX_GUTTER_WIDTH: usize = 4;
+
+/// How far a gap may sit from where an even grid puts it, as a fraction of
+/// the cell size, for `infer_grid` to still call the spacing even
+const GRID_SPACING_TOLERANCE: f32 = 0.1;
+
+/// Number and size of the cells of an evenly spaced grid along one axis,
+/// from that axis's boundaries (which include `0` and `extent`), or `None`
+/// if the spacing isn't even. Each run of adjacent boundaries is one gap,
+/// cut in its middle; gaps at the image edges are margins of the outer cells.
+fn even_spacing(boundaries: &[u32], extent: u32) -> Option<(u32, u32)> {
+    let mut cuts = vec![0];
+    for run in boundaries.chunk_by(|a, b| b - a == 1) {
+        let (first, last) = (run[0], run[run.len() - 1]);
+        // Boundary candidates stop one pixel short of each edge
+        if first <= 1 || last + 2 >= extent {
+            continue;
+        }
+        cuts.push((first + last).div_ceil(2));
+    }
+    cuts.push(extent);
+
+    let count = cuts.len() as u32 - 1;
+    let cell = extent as f32 / count as f32;
+    let slack = (cell * GRID_SPACING_TOLERANCE).max(1.0);
+    cuts.iter().enumerate()
+        .all(|(i, &cut)| (cut as f32 - i as f32 * cell).abs() <= slack)
+        .then_some((count, cell.round() as u32))
+}
+
+/// How deep a projection valley must be: at most this fraction of the
+/// highest count on its lower side
+const PROJECTION_VALLEY_RATIO: f32 = 0.5;
+
+/// Boundaries at the valleys of a projection `profile`, including `0` and
+/// its length. A position is in a valley when its count is at most
+/// `PROJECTION_VALLEY_RATIO` of the peaks on both sides of it; each run of
+/// such positions becomes one boundary at its lowest count. Margins before
+/// the first or after the last content have no peak on one side, so they
+/// stay part of the outer frames.
+fn projection_valleys(profile: &[u32], merge_distance: u32) -> Vec<u32> {
+    let mut left_peaks = Vec::with_capacity(profile.len());
+    let mut peak = 0;
+    for &count in profile {
+        peak = peak.max(count);
+        left_peaks.push(peak);
+    }
+    let mut right_peaks = vec![0; profile.len()];
+    peak = 0;
+    for (i, &count) in profile.iter().enumerate().rev() {
+        peak = peak.max(count);
+        right_peaks[i] = peak;
+    }
+
+    // Lower counts are more confident boundaries
+    let candidates: Vec<(u32, f32)> = profile.iter().enumerate()
+        .filter(|&(i, &count)| {
+            let lower_peak = left_peaks[i].min(right_peaks[i]);
+            lower_peak > 0 && count as f32 <= lower_peak as f32 * PROJECTION_VALLEY_RATIO
+        })
+        .map(|(i, &count)| (i as u32, -(count as f32)))
+        .collect();
+    merge_close_boundaries(&candidates, merge_distance.max(1), profile.len() as u32)
+}
+
+/// Order frames by the rows they form, left to right within each row, or
+/// with `FrameOrder::ColumnMajor` by columns, top to bottom within each.
+///
+/// Frames are taken in order of their centers across the rows; a frame
+/// that reaches into the rows the current cluster already spans joins it,
+/// and otherwise starts the next one. Sprites staggered up and down within
+/// a row so stay in that row, where sorting by their top edge would
+/// interleave them with the rows around it.
+fn cluster_frames(mut frames: Vec<SpriteFrame>, order: FrameOrder) -> Vec<SpriteFrame> {
+    // (start, size) across the clusters and position along them
+    let across = |frame: &SpriteFrame| match order {
+        FrameOrder::RowMajor => (frame.y, frame.height, frame.x),
+        FrameOrder::ColumnMajor => (frame.x, frame.width, frame.y),
+    };
+    frames.sort_by_key(|frame| {
+        let (start, size, along) = across(frame);
+        (2 * start + size, along)
+    });
+
+    let mut clusters: Vec<(u32, Vec<SpriteFrame>)> = Vec::new();
+    for frame in frames {
+        let (start, size, _) = across(&frame);
+        match clusters.last_mut() {
+            Some((end, members)) if start < *end => {
+                *end = (*end).max(start + size);
+                members.push(frame);
+            }
+            _ => clusters.push((start + size, vec![frame])),
+        }
+    }
+
+    clusters.into_iter()
+        .flat_map(|(_, mut members)| {
+            members.sort_by_key(|frame| across(frame).2);
+            members
+        })
+        .collect()
+}
+
+/// Transparent gap between the cells of a contact sheet
+const CONTACT_SHEET_SPACING: u32 = 2;
+
+/// Lay `frames` out in rows of `columns`, in order, each centered in a cell
+/// as large as the biggest frame, with `CONTACT_SHEET_SPACING` between cells
+fn contact_sheet(frames: &[DynamicImage], columns: u32) -> RgbaImage {
+    let cell_width = frames.iter().map(|frame| frame.width()).max().unwrap_or(0);
+    let cell_height = frames.iter().map(|frame| frame.height()).max().unwrap_or(0);
+    let columns = columns.clamp(1, (frames.len() as u32).max(1));
+    let rows = (frames.len() as u32).div_ceil(columns);
+    let extent = |count: u32, cell: u32| (count * (cell + CONTACT_SHEET_SPACING)).saturating_sub(CONTACT_SHEET_SPACING);
+
+    let mut sheet = RgbaImage::new(extent(columns, cell_width), extent(rows, cell_height));
+    for (i, frame) in frames.iter().enumerate() {
+        let (column, row) = (i as u32 % columns, i as u32 / columns);
+        let x = column * (cell_width + CONTACT_SHEET_SPACING) + (cell_width - frame.width()) / 2;
+        let y = row * (cell_height + CONTACT_SHEET_SPACING) + (cell_height - frame.height()) / 2;
+        image::imageops::overlay(&mut sheet, &frame.to_rgba8(), x as i64, y as i64);
+    }
+    sheet
+}
+
+/// `(start, size)` of the span between each pair of consecutive boundaries,
+/// skipping pairs that don't enclose any pixels
+fn boundary_spans(boundaries: &[u32]) -> impl Iterator<Item = (u32, u32)> + '_ {
+    boundaries.windows(2)
+        .filter(|pair| pair[1] > pair[0])
+        .map(|pair| (pair[0], pair[1] - pair[0]))
+}
+
+/// Outline color for `debug_overlay`, chosen to stand out on most sheets
+const OVERLAY_COLOR: Rgba<u8> = Rgba([0, 255, 0, 255]);
+
+/// 3x5 bitmaps of the digits 0-9, one row per byte (low three bits)
+const OVERLAY_DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// Copy of the sheet with every frame outlined and labelled with its
+/// number counted from `first_index`, matching the saved frame files
+fn debug_overlay(img: &DynamicImage, frames: &[SpriteFrame], first_index: usize) -> RgbaImage {
+    let mut overlay = img.to_rgba8();
+    for (index, frame) in frames.iter().enumerate() {
+        if frame.width == 0 || frame.height == 0 {
+            continue;
+        }
+        let rect = imageproc::rect::Rect::at(frame.x as i32, frame.y as i32).of_size(frame.width, frame.height);
+        imageproc::drawing::draw_hollow_rect_mut(&mut overlay, rect, OVERLAY_COLOR);
+
+        // Label in the top-left corner, on a black plate so it reads on any sheet
+        let label = (first_index + index).to_string();
+        let scale = if frame.width >= 32 { 2 } else { 1 };
+        let plate = imageproc::rect::Rect::at(frame.x as i32 + 1, frame.y as i32 + 1)
+            .of_size(label.len() as u32 * 4 * scale + scale, 7 * scale);
+        imageproc::drawing::draw_filled_rect_mut(&mut overlay, plate, Rgba([0, 0, 0, 255]));
+        for (position, digit) in label.bytes().enumerate() {
+            let glyph = OVERLAY_DIGITS[(digit - b'0') as usize];
+            let left = frame.x + 1 + scale + position as u32 * 4 * scale;
+            let top = frame.y + 1 + scale;
+            for (row, bits) in glyph.iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) == 0 {
+                        continue;
+                    }
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            let (x, y) = (left + column * scale + dx, top + row as u32 * scale + dy);
+                            if x < overlay.width() && y < overlay.height() {
+                                overlay.put_pixel(x, y, OVERLAY_COLOR);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    overlay
+}
+
+/// Cell size and cell count along one axis of a grid `extent` pixels long,
+/// given either the number of cells or their size. Returns `None` when not a
+/// single cell fits inside the margins.
+fn grid_axis(extent: u32, count: Option<u32>, cell: Option<u32>, margin: u32, spacing: u32) -> Option<(u32, u32)> {
+    if margin == 0 && spacing == 0 {
+        return match (count, cell) {
+            (Some(count), _) => Some(((extent / count).max(1), count.min(extent))),
+            (_, Some(cell)) => Some((cell, extent.div_ceil(cell))),
+            _ => None,
+        };
+    }
+
+    let inner = extent.checked_sub(margin.saturating_mul(2))?;
+    let (cell, count) = match (count, cell) {
+        (Some(count), _) => {
+            let cells_total = inner.checked_sub((count - 1) * spacing)?;
+            (cells_total / count, count)
+        }
+        (_, Some(cell)) => (cell, (inner + spacing) / (cell + spacing)),
+        _ => return None,
+    };
+    (cell > 0 && count > 0).then_some((cell, count))
+}
+
+/// Whether two images have the same size and no channel differs by more
+/// than `tolerance`
+fn images_within_tolerance(a: &RgbaImage, b: &RgbaImage, tolerance: u8) -> bool {
+    a.dimensions() == b.dimensions()
+        && a.as_raw().iter().zip(b.as_raw()).all(|(x, y)| x.abs_diff(*y) <= tolerance)
+}
+
+/// Result of `pack_shelves`
+#[derive(Debug, PartialEq)]
+struct ShelfPacking {
+    /// Top-left corner of each rect, in input order
+    positions: Vec<(u32, u32)>,
+    /// Size of the area actually used
+    width: u32,
+    height: u32,
+}
+
+/// Pack rectangles into shelves no wider or taller than `max_size`.
+///
+/// Rects are placed tallest first, left to right, opening a new shelf below
+/// when the current one is full. Returns `None` if they don't fit.
+fn pack_shelves(sizes: &[(u32, u32)], max_size: u32) -> Option<ShelfPacking> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(sizes[i].1));
+
+    let mut positions = vec![(0, 0); sizes.len()];
+    let (mut cursor_x, mut shelf_y, mut shelf_height) = (0u32, 0u32, 0u32);
+    let mut used_width = 0;
+    for i in order {
+        let (width, height) = sizes[i];
+        if width > max_size {
+            return None;
+        }
+        if cursor_x + width > max_size {
+            shelf_y += shelf_height;
+            cursor_x = 0;
+            shelf_height = 0;
+        }
+        if shelf_y + height > max_size {
+            return None;
+        }
+        positions[i] = (cursor_x, shelf_y);
+        cursor_x += width;
+        shelf_height = shelf_height.max(height);
+        used_width = used_width.max(cursor_x);
+    }
+
+    Some(ShelfPacking {
+        positions,
+        width: used_width.max(1),
+        height: (shelf_y + shelf_height).max(1),
+    })
+}
+
+/// Repeatedly merge frames whose intersection covers more than `min_overlap`
+/// of the smaller frame's area, until no such pair remains
+fn merge_overlapping_frames(mut frames: Vec<SpriteFrame>, min_overlap: f32) -> Vec<SpriteFrame> {
+    let mut merged_any = true;
+    while merged_any {
+        merged_any = false;
+        'search: for i in 0..frames.len() {
+            for j in i + 1..frames.len() {
+                let (a, b) = (&frames[i], &frames[j]);
+                let overlap_width = (a.x + a.width).min(b.x + b.width).saturating_sub(a.x.max(b.x));
+                let overlap_height = (a.y + a.height).min(b.y + b.height).saturating_sub(a.y.max(b.y));
+                let overlap_area = overlap_width as u64 * overlap_height as u64;
+                if overlap_area == 0 {
+                    continue;
+                }
+
+                let smaller_area = (a.width as u64 * a.height as u64).min(b.width as u64 * b.height as u64);
+                if overlap_area as f32 / smaller_area as f32 > min_overlap {
+                    let x = a.x.min(b.x);
+                    let y = a.y.min(b.y);
+                    let union = SpriteFrame::new(x, y, (a.x + a.width).max(b.x + b.width) - x,
+                                                 (a.y + a.height).max(b.y + b.height) - y);
+                    frames[i] = union;
+                    frames.remove(j);
+                    merged_any = true;
+                    break 'search;
+                }
+            }
+        }
+    }
+    frames
+}
+
+/// Expand a frame filename template.
+///
+/// Placeholders are `{name}` (source file stem), `{index}` (frame number,
+/// counted from `frame_index_start`), `{x}`, `{y}`, `{w}` and `{h}` (the
+/// frame's rect in the source sheet), and `{row}` and `{col}` (0-based grid
+/// cell, for frames cut on a grid; row 0 and the frame's 0-based `position`
+/// on the sheet for any other frame). Numbers accept a zero-padded width
+/// such as `{index:03}`, and `{{` / `}}` produce literal braces. Unknown
+/// placeholders are an error.
+fn format_frame_filename(template: &str, name: &str, index: usize, position: usize, frame: &SpriteFrame)
+    -> Result<String, String> {
+    let mut output = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                output.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                output.push('}');
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => return Err(format!("unclosed placeholder '{{{}'", placeholder)),
+                    }
+                }
+
+                let (key, spec) = match placeholder.split_once(':') {
+                    Some((key, spec)) => (key, Some(spec)),
+                    None => (placeholder.as_str(), None),
+                };
+                let number = match key {
+                    "name" if spec.is_none() => {
+                        output.push_str(name);
+                        continue;
+                    }
+                    "name" => return Err("{name} does not take a width".to_string()),
+                    "index" => index as u64,
+                    "x" => frame.x as u64,
+                    "y" => frame.y as u64,
+                    "w" => frame.width as u64,
+                    "h" => frame.height as u64,
+                    // Frames that weren't cut from a grid cell count as one row
+                    "row" => frame.cell.map_or(0, |(row, _)| row) as u64,
+                    "col" => frame.cell.map_or(position as u64, |(_, col)| col as u64),
+                    _ => return Err(format!("unknown placeholder '{{{}}}'", placeholder)),
+                };
+                let width: usize = match spec {
+                    None => 0,
+                    // "03" and "3" both pad to three digits
+                    Some(spec) => spec.parse()
+                        .map_err(|_| format!("invalid width '{}' in '{{{}}}'", spec, placeholder))?,
+                };
+                output.push_str(&format!("{:0width$}", number, width = width));
+            }
+            '}' => return Err("unmatched '}'".to_string()),
+            c => output.push(c),
+        }
+    }
+
+    Ok(output)
+}
+
+/// `value` with everything but ASCII letters, digits, `-` and `_` replaced
+/// by `-`, for use in file names
+fn slugify(value: &str) -> String {
+    value.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect()
+}
+
+/// Name used for a folder's output subdirectory and progress messages
+fn folder_display_name(folder_path: &Path) -> String {
+    // `.` and `..` have no file name of their own, so resolve them first
+    folder_path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .or_else(|| {
+            folder_path.canonicalize().ok()
+                .and_then(|path| path.file_name().map(|name| name.to_string_lossy().to_string()))
+        })
+        .unwrap_or_else(|| "input".to_string())
+}
+
+/// Quote a string for a POSIX shell using single quotes
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_default() {
+        let config = CutterConfig::default();
+        assert_eq!(config.min_sprite_size, 8);
+        assert_eq!(config.max_sprite_size, 1024);
+        assert_eq!(config.background_tolerance, 20);
+        assert!(config.remove_background);
+        assert_eq!(config.output_dir, "assets2");
+    }
+
+    #[test]
+    fn test_adaptive_retry() {
+        // The gap between two sprites is bridged for almost half its height,
+        // so it's just under the default transparent ratio
+        let mut sheet = sheet_with_sprites(64, 32, &[(2, 2, 28, 28), (34, 2, 28, 28)]).to_rgba8();
+        for y in 2..16 {
+            for x in 30..34 {
+                sheet.put_pixel(x, y, Rgba([200, 60, 60, 255]));
+            }
+        }
+        let img = DynamicImage::ImageRgba8(sheet);
+
+        // The retry is opt-in
+        let detection = SpritesheetCutter::new(CutterConfig::default()).detect_with_method(&img).unwrap();
+        assert_eq!((detection.frames.len(), detection.method), (1, DetectionMethod::Boundaries));
+
+        let retrying = SpritesheetCutter::new(CutterConfig { adaptive_retry: true, ..CutterConfig::default() });
+        let detection = retrying.detect_with_method(&img).unwrap();
+        assert_eq!((detection.frames.len(), detection.method), (2, DetectionMethod::RelaxedBoundaries));
+
+        // A sheet that really holds one sprite stays that way
+        let single = sheet_with_sprites(64, 32, &[(2, 2, 60, 28)]);
+        let detection = retrying.detect_with_method(&single).unwrap();
+        assert_eq!((detection.frames.len(), detection.method), (1, DetectionMethod::Boundaries));
+
+        // Too short for two frames of the minimum size, however long it is
+        let flat = CutterConfig { adaptive_retry: true, min_sprite_size: 16, ..CutterConfig::default() };
+        let gray = img.crop_imm(0, 0, 64, 12).to_luma16();
+        assert!(SpritesheetCutter::new(flat).relaxed_boundary_frames(&img.crop_imm(0, 0, 64, 12), &gray).is_none());
+
+        // Attempts are ranked by how evenly sized their frames are
+        let frame = |width, height| SpriteFrame::new(0, 0, width, height);
+        assert_eq!(area_spread(&[frame(8, 8), frame(4, 16)]), 0.0);
+        assert!(area_spread(&[frame(8, 8), frame(8, 9)]) < area_spread(&[frame(8, 8), frame(2, 2)]));
+    }
+
+    #[test]
+    fn test_include_edge_frames() {
+        // Two tall sprites and a short one flush against the right edge, whose
+        // columns are mostly empty and so look like one wide gap
+        let img = sheet_with_sprites(96, 64, &[(4, 4, 24, 56), (36, 4, 24, 56), (72, 4, 24, 24)]);
+        let rects = |include_edge_frames| {
+            let cutter = SpritesheetCutter::new(CutterConfig { include_edge_frames, ..CutterConfig::default() });
+            cutter.detect_sprite_frames(&img).unwrap().iter()
+                .map(|frame| (frame.x, frame.y, frame.width, frame.height))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(rects(false).len(), 2);
+        let with_edges = rects(true);
+        assert_eq!(with_edges.len(), 3);
+        let (x, _, width, _) = with_edges[2];
+        // The real gap before it still splits it off from its neighbour
+        assert_eq!((x, x + width), (71, 96));
+
+        let mut boundaries = vec![0, 1, 2, 5, 6, 7, 8, 10];
+        drop_edge_gutters(&mut boundaries, 10, |position| position != 6);
+        assert_eq!(boundaries, vec![0, 5, 6, 10]);
+    }
+
+    #[test]
+    fn test_refine_boundaries() {
+        // Sprites sitting off-center in their cells, with wider gutters on one side
+        let img = sheet_with_sprites(64, 32, &[(3, 4, 20, 24), (41, 4, 17, 24)]);
+        let rects = |refine_boundaries| {
+            let cutter = SpritesheetCutter::new(CutterConfig { refine_boundaries, ..CutterConfig::default() });
+            cutter.detect_sprite_frames(&img).unwrap().iter()
+                .map(|frame| (frame.x, frame.y, frame.width, frame.height))
+                .collect::<Vec<_>>()
+        };
+
+        // Coarse boundaries sit a pixel out in the gutter
+        assert_eq!(rects(false), vec![(2, 3, 21, 25), (40, 3, 18, 25)]);
+        assert_eq!(rects(true), vec![(3, 4, 20, 24), (41, 4, 17, 24)]);
+
+        // A span whose content is too narrow for a sprite keeps its coarse edges
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        let content = |line: u32| (2..5).contains(&line) || (12..30).contains(&line);
+        assert_eq!(cutter.refine_boundaries(&[0, 10, 32], |line| !content(line)), vec![0, 10, 12, 30, 32]);
+    }
+
+    #[test]
+    fn test_16_bit_gutters() {
+        // A gutter just under the near-black threshold: a clear boundary at
+        // 16 bits, but it rounds up to the threshold itself in 8 bits
+        let mut sheet = image::ImageBuffer::from_pixel(64, 32, image::Rgb([30000u16, 30000, 30000]));
+        for y in 0..32 {
+            for x in [31, 32] {
+                sheet.put_pixel(x, y, image::Rgb([2500, 2500, 2500]));
+            }
+        }
+        let img = DynamicImage::ImageRgb16(sheet);
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+
+        assert_eq!(cutter.find_vertical_boundaries(&img.to_luma16(), None), vec![0, 31, 32, 64]);
+        let rounded = DynamicImage::ImageLuma8(img.to_luma8()).to_luma16();
+        assert_eq!(cutter.find_vertical_boundaries(&rounded, None), vec![0, 64]);
+
+        // 8-bit sheets see the same levels, just scaled
+        let img8 = sheet_with_sprites(32, 16, &[(4, 4, 8, 8)]);
+        let (luma8, luma16) = (img8.to_luma8(), img8.to_luma16());
+        assert!(luma8.pixels().zip(luma16.pixels()).all(|(a, b)| a[0] as u16 * 257 == b[0]));
+    }
+
+    #[test]
+    fn test_boundary_thresholds_are_configurable() {
+        // A 2px dark gutter band that is 50% of the column height
+        let mut gray = image::ImageBuffer::from_pixel(30, 10, image::Luma([200u16 * 257]));
+        for y in 0..5 {
+            gray.put_pixel(15, y, image::Luma([0]));
+        }
+
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        assert!(!cutter.find_vertical_boundaries(&gray, None).contains(&15));
+
+        let relaxed = SpritesheetCutter::new(CutterConfig {
+            transparent_column_ratio: 0.4,
+            ..CutterConfig::default()
+        });
+        assert!(relaxed.find_vertical_boundaries(&gray, None).contains(&15));
+    }
+
+    #[test]
+    fn test_parallel_boundary_scan_matches_serial() {
+        // A large, busy sheet: a grid of sprites with noisy stripes between them
+        let rects: Vec<(u32, u32, u32, u32)> = (0..16)
+            .flat_map(|row| (0..16).map(move |col| (col * 64 + 5, row * 64 + 7, 50 + col % 5, 48 + row % 7)))
+            .collect();
+        let mut sheet = sheet_with_sprites(1024, 1024, &rects).to_rgba8();
+        for x in (0..1024).step_by(97) {
+            for y in 0..1024 {
+                sheet.put_pixel(x, y, Rgba([(y * 7 % 256) as u8, 90, 30, 255]));
+            }
+        }
+        let img = DynamicImage::ImageRgba8(sheet);
+        let gray = img.to_luma16();
+        let rgba = img.to_rgba8();
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+
+        for alpha in [None, Some(&rgba)] {
+            let serial: Vec<(u32, f32)> = (1..1023)
+                .filter_map(|x| cutter.column_boundary_score(&gray, alpha, x).map(|score| (x, score)))
+                .collect();
+            let expected = merge_close_boundaries(&serial, 0, 1024);
+            assert_eq!(cutter.find_vertical_boundaries(&gray, alpha), expected);
+
+            let serial: Vec<(u32, f32)> = (1..1023)
+                .filter_map(|y| cutter.row_boundary_score(&gray, alpha, y).map(|score| (y, score)))
+                .collect();
+            assert_eq!(cutter.find_horizontal_boundaries(&gray, alpha), merge_close_boundaries(&serial, 0, 1024));
+        }
+    }
+
+    #[test]
+    fn test_degenerate_boundaries() {
+        // Repeated and out-of-order boundaries enclose no pixels
+        assert_eq!(boundary_spans(&[0, 8, 8, 16, 12, 20]).collect::<Vec<_>>(), vec![(0, 8), (8, 8), (12, 8)]);
+        assert_eq!(boundary_spans(&[0]).count(), 0);
+
+        let img = sheet_with_sprites(20, 20, &[(0, 0, 20, 20)]);
+        let cutter = SpritesheetCutter::new(CutterConfig { min_sprite_size: 0, ..CutterConfig::default() });
+        let frames = cutter.frames_between_boundaries(&img, &[0, 10, 10, 20], &[0, 0, 20]);
+        assert!(frames.iter().all(|frame| frame.width > 0 && frame.height > 0));
+        assert_eq!(frames.len(), 2);
+
+        // Images with no pixels at all go through every detection path without panicking
+        for image_size in [(0, 0), (0, 12), (12, 0), (1, 1)] {
+            let empty = DynamicImage::ImageRgba8(RgbaImage::new(image_size.0, image_size.1));
+            for mode in [DetectionMode::Boundaries, DetectionMode::ConnectedComponents] {
+                let config = CutterConfig { min_sprite_size: 0, detection_mode: mode, ..CutterConfig::default() };
+                let frames = SpritesheetCutter::new(config).detect_sprite_frames(&empty).unwrap();
+                assert!(frames.iter().all(|frame| frame.width > 0 && frame.height > 0));
+            }
+        }
+        assert!(atlas_frames(&serde_json::json!({ "frames": [{ "frame": { "x": 0, "y": 0, "w": 0, "h": 4 } }] }))
+            .is_err());
+    }
+
+    #[test]
+    fn test_dark_sprites_on_transparent_sheet() {
+        let mut sheet = RgbaImage::new(32, 14);
+        for &(x0, y0) in &[(2, 2), (20, 2)] {
+            for y in y0..y0 + 10 {
+                for x in x0..x0 + 10 {
+                    sheet.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+                }
+            }
+        }
+        let img = DynamicImage::ImageRgba8(sheet);
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+
+        assert!(cutter.column_is_empty_alpha(&img.to_rgba8(), 15));
+        assert!(!cutter.column_is_empty_alpha(&img.to_rgba8(), 5));
+
+        let frames = cutter.detect_sprite_frames(&img).unwrap();
+        let rects: Vec<_> = frames.iter().map(|f| (f.x, f.y, f.width, f.height)).collect();
+        assert_eq!(rects, vec![(1, 1, 11, 11), (19, 1, 11, 11)]);
+    }
+
+    #[test]
+    fn test_background_pixel_detection() {
+        let config = CutterConfig::default();
+        let cutter = SpritesheetCutter::new(config);
+        
+        let background = Rgba([255, 255, 255, 255]);
+        let similar_pixel = Rgba([250, 250, 250, 255]);
+        let different_pixel = Rgba([100, 100, 100, 255]);
+        
+        assert!(cutter.is_background_pixel(&similar_pixel, &[background]));
+        assert!(!cutter.is_background_pixel(&different_pixel, &[background]));
+    }
+
+    #[test]
+    fn test_folder_display_name() {
+        assert_eq!(folder_display_name(Path::new("/work/sheets/Ships")), "Ships");
+        assert!(!folder_display_name(Path::new(".")).is_empty());
+    }
+
+    #[test]
+    fn test_subfolder_per_sheet() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        let input = dir.join("Ships");
+        fs::create_dir_all(&input).unwrap();
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]).save(input.join("attack.png")).unwrap();
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]).save(input.join("idle.png")).unwrap();
+        RgbaImage::new(16, 16).save(input.join("blank.png")).unwrap();
+
+        let config = CutterConfig {
+            input_dirs: vec![input.clone()],
+            output_dir: dir.join("out").to_string_lossy().to_string(),
+            subfolder_per_sheet: true,
+            ..CutterConfig::default()
+        };
+        let summary = SpritesheetCutter::new(config).process_directory().unwrap();
+        assert_eq!(summary.processed, 3);
+
+        let output = dir.join("out").join("Ships");
+        for sheet in ["attack", "idle"] {
+            assert!(output.join(sheet).join("frame_001.png").is_file());
+            assert!(output.join(sheet).join("frame_002.png").is_file());
+        }
+        assert!(output.join("blank").join("blank.png").is_file());
+        assert!(!output.join("frame_001.png").exists());
+
+        // Sheets that only differ by extension get a folder each
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24)]).save(input.join("idle.bmp")).unwrap();
+        let config = CutterConfig {
+            input_dirs: vec![input.clone()],
+            output_dir: dir.join("out2").to_string_lossy().to_string(),
+            subfolder_per_sheet: true,
+            ..CutterConfig::default()
+        };
+        let summary = SpritesheetCutter::new(config).process_directory().unwrap();
+        assert_eq!(summary.processed, 4);
+        let output = dir.join("out2").join("Ships");
+        assert!(output.join("idle_png").join("frame_002.png").is_file());
+        assert!(output.join("idle_bmp").join("frame_001.png").is_file());
+        assert!(!output.join("idle_bmp").join("frame_002.png").exists());
+        assert!(output.join("attack").join("frame_001.png").is_file());
+
+        // Only sheets the run cuts count, not every file next to them
+        let config = CutterConfig {
+            input_dirs: vec![input.clone()],
+            output_dir: dir.join("out3").to_string_lossy().to_string(),
+            include: vec!["*.png".to_string()],
+            ..CutterConfig::default()
+        };
+        SpritesheetCutter::new(config).process_directory().unwrap();
+        let output = dir.join("out3").join("Ships");
+        assert!(output.join("idle_frame_001.png").is_file());
+        assert!(!output.join("idle_png_frame_001.png").exists());
+    }
+
+    #[test]
+    fn test_recursive_output_layout() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        for fleet in ["fighters", "bombers"] {
+            fs::create_dir_all(dir.join("Ships").join(fleet)).unwrap();
+            sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)])
+                .save(dir.join("Ships").join(fleet).join("x.png")).unwrap();
+        }
+        let run = |flatten_output| {
+            let config = CutterConfig {
+                input_dirs: vec![dir.join("Ships")],
+                output_dir: dir.join("out").to_string_lossy().to_string(),
+                recursive: true,
+                flatten_output,
+                atlas_format: AtlasFormat::TexturePackerHash,
+                ..CutterConfig::default()
+            };
+            SpritesheetCutter::new(config).process_directory().unwrap()
+        };
+
+        assert_eq!(run(false).processed, 2);
+        let output = dir.join("out").join("Ships");
+        assert!(output.join("fighters").join("x_frame_001.png").is_file());
+        assert!(output.join("bombers").join("x_frame_001.png").is_file());
+        let atlas: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(output.join("bombers").join("x.json")).unwrap()).unwrap();
+        assert_eq!(atlas["meta"]["source"], "bombers/x.png");
+
+        fs::remove_dir_all(dir.join("out")).unwrap();
+        assert_eq!(run(true).processed, 2);
+        assert!(output.join("fighters_x_frame_001.png").is_file());
+        assert!(output.join("bombers_x_frame_002.png").is_file());
+        assert!(!output.join("fighters").exists());
+    }
+
+    #[test]
+    fn test_recursive_same_named_sheets() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        for fleet in ["fighters", "bombers"] {
+            fs::create_dir_all(dir.join("Ships").join(fleet)).unwrap();
+            sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)])
+                .save(dir.join("Ships").join(fleet).join("x.png")).unwrap();
+        }
+        let run = |incremental, emit_montage| {
+            let config = CutterConfig {
+                input_dirs: vec![dir.join("Ships")],
+                output_dir: dir.join("out").to_string_lossy().to_string(),
+                recursive: true,
+                incremental,
+                emit_montage,
+                ..CutterConfig::default()
+            };
+            SpritesheetCutter::new(config).process_directory().unwrap()
+        };
+
+        // Each sheet's montage goes next to its frames
+        assert_eq!(run(false, true).processed, 2);
+        let output = dir.join("out").join("Ships");
+        let montage = fs::read_to_string(output.join("montage.txt")).unwrap();
+        assert!(montage.contains("# fighters/x.png: 2 frames"));
+        assert!(montage.contains(" 'fighters/x_montage.png'\n"));
+        assert!(montage.contains(" 'bombers/x_montage.png'\n"));
+
+        // Each sheet's outputs are recorded apart, so losing one sheet's
+        // frames redoes only that sheet
+        assert_eq!(run(true, false).processed, 2);
+        fs::remove_file(output.join("fighters").join("x_frame_001.png")).unwrap();
+        let summary = run(true, false);
+        assert_eq!((summary.processed, summary.unchanged), (1, 1));
+        assert!(output.join("fighters").join("x_frame_001.png").is_file());
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("sea ships"), "sea-ships");
+        assert_eq!(slugify("boss_2-final"), "boss_2-final");
+        assert_eq!(slugify("été/x"), "-t--x");
+
+        // Sheets whose flattened names coincide fail rather than overwrite each other
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        for (folder, sheet) in [("a_b", "x.png"), ("a", "b_x.png"), ("a b", "y.png"), ("a-b", "y.png")] {
+            fs::create_dir_all(dir.join("Ships").join(folder)).unwrap();
+            sheet_with_sprites(64, 32, &[(4, 4, 24, 24)]).save(dir.join("Ships").join(folder).join(sheet)).unwrap();
+        }
+        let config = CutterConfig {
+            input_dirs: vec![dir.join("Ships")],
+            output_dir: dir.join("out").to_string_lossy().to_string(),
+            recursive: true,
+            flatten_output: true,
+            ..CutterConfig::default()
+        };
+        let result = SpritesheetCutter::new(config).process_directory();
+        assert!(matches!(result, Err(CutterError::ImagesFailed { failed: 2 })));
+        let output = dir.join("out").join("Ships");
+        assert!(output.join("a_b_x_frame_001.png").is_file());
+        assert!(output.join("a-b_y_frame_001.png").is_file());
+    }
+
+    #[test]
+    fn test_csv_manifest() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        let input = dir.join("Ships");
+        fs::create_dir_all(&input).unwrap();
+        sheet_with_sprites(160, 64, &[(40, 20, 24, 24), (84, 20, 20, 24)]).save(input.join("hero.png")).unwrap();
+
+        let config = CutterConfig {
+            input_dirs: vec![input],
+            output_dir: dir.join("out").to_string_lossy().to_string(),
+            autocrop: true,
+            trim: true,
+            remove_background: false,
+            manifest: Some(ManifestFormat::Csv),
+            ..CutterConfig::default()
+        };
+        SpritesheetCutter::new(config).process_directory().unwrap();
+
+        let manifest = fs::read_to_string(dir.join("out").join("manifest.csv")).unwrap();
+        let mut lines = manifest.lines();
+        assert_eq!(lines.next(), Some("source_file,output_file,x,y,width,height,trimmed_x,trimmed_y"));
+        let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0][0].ends_with("Ships/hero.png") && !rows[0][0].starts_with("//"));
+        assert_eq!(rows[0][1], "Ships/hero_frame_001.png");
+        // The trimmed pixels land exactly on the sprites of the original sheet
+        for (row, sprite) in rows.iter().zip([(40, 20), (84, 20)]) {
+            let number = |i: usize| row[i].parse::<u32>().unwrap();
+            assert_eq!((number(2) + number(6), number(3) + number(7)), sprite);
+        }
+
+        assert_eq!(csv_field("plain.png"), "plain.png");
+        assert_eq!(csv_field("a,\"b\".png"), "\"a,\"\"b\"\".png\"");
+    }
+
+    #[test]
+    fn test_compare_with_earlier_manifest() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        let input = dir.join("Ships");
+        fs::create_dir_all(&input).unwrap();
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]).save(input.join("hero.png")).unwrap();
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]).save(input.join("ship.png")).unwrap();
+        let config = CutterConfig {
+            input_dirs: vec![input.clone()],
+            output_dir: dir.join("out").to_string_lossy().to_string(),
+            manifest: Some(ManifestFormat::Json),
+            ..CutterConfig::default()
+        };
+        SpritesheetCutter::new(config.clone()).process_directory().unwrap();
+        let earlier = dir.join("earlier.json");
+        fs::rename(dir.join("out").join("manifest.json"), &earlier).unwrap();
+
+        // The same settings change nothing; a third sprite on one sheet does
+        let compare = CutterConfig { manifest: None, compare: Some(earlier.clone()), ..config };
+        let summary = SpritesheetCutter::new(compare.clone()).process_directory().unwrap();
+        assert!(summary.frame_changes.is_empty());
+        sheet_with_sprites(96, 32, &[(4, 4, 24, 24), (36, 4, 24, 24), (68, 4, 24, 24)])
+            .save(input.join("ship.png")).unwrap();
+        let summary = SpritesheetCutter::new(compare.clone()).process_directory().unwrap();
+        assert_eq!(summary.frame_changes.len(), 1);
+        assert!(summary.frame_changes[0].ends_with("Ships/ship.png: 3 frame(s), 2 before"), "{:?}",
+                summary.frame_changes);
+        // Sheets left out of a run aren't reported as lost
+        let single = CutterConfig { input_dirs: vec![input.join("hero.png")], ..compare.clone() };
+        assert!(SpritesheetCutter::new(single).process_directory().unwrap().frame_changes.is_empty());
+
+        let frame = |source_file: &str, x| ReportFrame { source_file: source_file.to_string(), x, y: 0, width: 8,
+                                                          height: 8 };
+        let row = |source_file: &str, x| ManifestRow { source_file: source_file.to_string(), output_file: String::new(),
+                                                       x, y: 0, width: 8, height: 8, trimmed_x: 0, trimmed_y: 0,
+                                                       average_color: None, dominant_color: None };
+        let previous = [frame("a.png", 0), frame("a.png", 8), frame("b.png", 0), frame("gone.png", 0),
+                        frame("other.png", 0)];
+        let current = [row("a.png", 0), row("a.png", 9), row("b.png", 0), row("new.png", 0)];
+        let attempted = ["a.png", "b.png", "gone.png", "new.png"].map(str::to_string).into();
+        assert_eq!(compare_frames(&previous, &current, &attempted), vec!["a.png: 1 of 2 frame rect(s) changed",
+                                                                         "gone.png: no frames this run, 1 before",
+                                                                         "new.png: new, 1 frame(s)"]);
+
+        fs::write(&earlier, "not json").unwrap();
+        let err = SpritesheetCutter::new(compare.clone()).process_directory().unwrap_err();
+        assert!(matches!(err, CutterError::ParseReport { .. }));
+        assert!(CutterConfig { incremental: true, ..compare }.validate().is_err());
+    }
+
+    #[test]
+    fn test_frame_colors() {
+        let mut img = RgbaImage::new(4, 4);
+        for x in 0..3 {
+            img.put_pixel(x, 0, Rgba([255, 0, 0, 255]));
+        }
+        img.put_pixel(0, 1, Rgba([0, 0, 255, 255]));
+        // Below the alpha threshold, so it doesn't count
+        img.put_pixel(3, 3, Rgba([0, 255, 0, 5]));
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        let (average, dominant) = cutter.frame_colors(&DynamicImage::ImageRgba8(img));
+        assert_eq!((average, dominant), (Rgba([191, 0, 64, 255]), Rgba([255, 0, 0, 255])));
+
+        let empty = DynamicImage::ImageRgba8(RgbaImage::new(4, 4));
+        assert_eq!(cutter.frame_colors(&empty), (Rgba([0, 0, 0, 0]), Rgba([0, 0, 0, 0])));
+
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]).save(dir.join("hero.png")).unwrap();
+        let config = CutterConfig {
+            input_dirs: vec![dir.clone()],
+            output_dir: dir.join("out").to_string_lossy().to_string(),
+            remove_background: false,
+            manifest: Some(ManifestFormat::Json),
+            emit_colors: true,
+            ..CutterConfig::default()
+        };
+        SpritesheetCutter::new(config).process_directory().unwrap();
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(dir.join("out").join("manifest.json")).unwrap()).unwrap();
+        let rows = manifest.as_array().unwrap();
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|row| row["average_color"] == "c83c3c" && row["dominant_color"] == "c83c3c"));
+
+        // Colors only go into JSON outputs
+        let csv_only = CutterConfig { emit_colors: true, manifest: Some(ManifestFormat::Csv), ..CutterConfig::default() };
+        assert!(csv_only.validate().is_err());
+    }
+
+    #[test]
+    fn test_output_relative_to_input() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        let input = dir.join("Ships");
+        fs::create_dir_all(&input).unwrap();
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]).save(input.join("hero.png")).unwrap();
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24)]).save(dir.join("boss.png")).unwrap();
+
+        let config = CutterConfig {
+            input_dirs: vec![input.clone(), dir.join("boss.png")],
+            output_dir: "frames".to_string(),
+            output_relative_to_input: true,
+            recursive: true,
+            ..CutterConfig::default()
+        };
+        let cutter = SpritesheetCutter::new(config);
+        assert_eq!(cutter.process_directory().unwrap().frames_total, 3);
+        assert!(input.join("frames").join("hero_frame_002.png").is_file());
+        assert!(dir.join("frames").join("boss_frame_001.png").is_file());
+        // The frames inside the input folder aren't picked up as sheets next time
+        assert_eq!(cutter.process_directory().unwrap().frames_total, 3);
+
+        // An absolute output directory is used as it is
+        let absolute = CutterConfig {
+            output_dir: dir.join("out").to_string_lossy().to_string(),
+            output_relative_to_input: true,
+            ..CutterConfig::default()
+        };
+        assert_eq!(absolute.output_root(Path::new("/work"), &input), dir.join("out"));
+        assert_eq!(CutterConfig::default().output_root(Path::new("/work"), &input), Path::new("/work").join("assets2"));
+
+        let with_manifest = CutterConfig { manifest: Some(ManifestFormat::Csv), ..cutter.config.clone() };
+        assert!(with_manifest.validate().is_err());
+    }
+
+    #[test]
+    fn test_overwrite_policy() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        let sheet_path = dir.join("hero.png");
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]).save(&sheet_path).unwrap();
+        let output_dir = dir.join("out");
+        let run = |overwrite| SpritesheetCutter::new(CutterConfig {
+            input_dirs: vec![sheet_path.clone()],
+            output_dir: output_dir.to_string_lossy().to_string(),
+            overwrite,
+            ..CutterConfig::default()
+        }).process_directory();
+
+        assert_eq!(run(OverwritePolicy::Overwrite).unwrap().frames_total, 2);
+        // A frame touched up by hand after the first run
+        let edited = output_dir.join("hero_frame_001.png");
+        RgbaImage::new(1, 1).save(&edited).unwrap();
+        let is_edited = || image::image_dimensions(&edited).unwrap() == (1, 1);
+
+        let summary = run(OverwritePolicy::Skip).unwrap();
+        assert_eq!((summary.frames_total, summary.frames_kept), (0, 2));
+        assert!(is_edited());
+
+        assert!(matches!(run(OverwritePolicy::Error), Err(CutterError::ImagesFailed { failed: 1 })));
+        let cutter = SpritesheetCutter::new(CutterConfig { overwrite: OverwritePolicy::Error, ..CutterConfig::default() });
+        let err = cutter.process_spritesheet(&sheet_path, &output_dir).unwrap_err();
+        assert!(matches!(err, CutterError::OutputExists { ref path } if *path == edited));
+        assert!(is_edited());
+
+        assert_eq!(run(OverwritePolicy::Overwrite).unwrap().frames_total, 2);
+        assert!(!is_edited());
+
+        // Side files and packed atlases follow the same policy
+        let packed = |overwrite| SpritesheetCutter::new(CutterConfig {
+            pack_mode: PackMode::Shelf,
+            atlas_format: AtlasFormat::SparrowXml,
+            overwrite,
+            ..CutterConfig::default()
+        }).process_spritesheet(&sheet_path, &output_dir);
+        packed(OverwritePolicy::Overwrite).unwrap();
+        let atlas = output_dir.join("hero.xml");
+        fs::write(&atlas, "edited").unwrap();
+        fs::write(output_dir.join("hero_packed.xml"), "edited").unwrap();
+        packed(OverwritePolicy::Skip).unwrap();
+        assert_eq!(fs::read_to_string(output_dir.join("hero_packed.xml")).unwrap(), "edited");
+        let err = packed(OverwritePolicy::Error).unwrap_err();
+        assert!(matches!(err, CutterError::OutputExists { ref path } if *path == output_dir.join("hero_packed.png")));
+        assert_eq!(fs::read_to_string(&atlas).unwrap(), "edited");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_exec_hook() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        let sheet_path = dir.join("hero.png");
+        // Sprites of different sizes, so the only warnings are the hook's
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 20, 16)]).save(&sheet_path).unwrap();
+        let output_dir = dir.join("out");
+        let run = |exec: &str| SpritesheetCutter::new(CutterConfig {
+            input_dirs: vec![sheet_path.clone()],
+            output_dir: output_dir.to_string_lossy().to_string(),
+            exec: Some(exec.to_string()),
+            ..CutterConfig::default()
+        }).process_directory().unwrap();
+
+        let summary = run("cp {file} {file}.bak");
+        assert!(summary.warnings.is_empty());
+        assert!(output_dir.join("hero_frame_001.png.bak").is_file());
+        assert!(output_dir.join("hero_frame_002.png.bak").is_file());
+
+        // Failures are warnings, one per frame, and don't fail the sheet
+        let summary = run("false");
+        assert_eq!((summary.processed, summary.warnings.len()), (1, 2));
+        assert!(summary.warnings[0].contains("'false' failed on hero_frame_001.png"));
+        let summary = run("no-such-optimizer-here {file}");
+        assert!(summary.warnings[1].contains("couldn't run 'no-such-optimizer-here' on hero_frame_002.png"));
+
+        assert!(CutterConfig { exec: Some("  ".to_string()), ..CutterConfig::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_single_file_input() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        let sheet_path = dir.join("hero.png");
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]).save(&sheet_path).unwrap();
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24)]).save(dir.join("other.png")).unwrap();
+
+        let config = CutterConfig {
+            input_dirs: vec![sheet_path],
+            output_dir: dir.join("out").to_string_lossy().to_string(),
+            ..CutterConfig::default()
+        };
+        let summary = SpritesheetCutter::new(config).process_directory().unwrap();
+        assert_eq!(summary.processed, 1);
+        assert!(dir.join("out").join("hero_frame_001.png").is_file());
+        assert!(dir.join("out").join("hero_frame_002.png").is_file());
+        assert!(!dir.join("out").join("other_frame_001.png").exists());
+    }
+
+    #[test]
+    fn test_jobs() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        let input = dir.join("Ships");
+        fs::create_dir_all(&input).unwrap();
+        for name in ["hero", "ship", "coin"] {
+            sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]).save(input.join(format!("{}.png", name))).unwrap();
+        }
+        let run = |jobs, output: &str| {
+            let config = CutterConfig {
+                input_dirs: vec![input.clone()],
+                output_dir: dir.join(output).to_string_lossy().to_string(),
+                jobs,
+                ..CutterConfig::default()
+            };
+            SpritesheetCutter::new(config).process_directory().unwrap()
+        };
+
+        // A single worker thread cuts the same frames as the default pool
+        let serial = run(Some(1), "serial");
+        let parallel = run(None, "parallel");
+        assert_eq!((serial.processed, serial.frames_total), (3, 6));
+        assert_eq!((parallel.processed, parallel.frames_total), (3, 6));
+        assert!(dir.join("serial").join("Ships").join("coin_frame_002.png").is_file());
+
+        assert!(CutterConfig { jobs: Some(0), ..CutterConfig::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_max_inflight() {
+        // However many threads there are, as many as the limit run at once
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        let (inflight, most) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        let items: Vec<usize> = (0..16).collect();
+        let doubled = pool.install(|| bounded_map(&items, 2, |&item| {
+            most.fetch_max(inflight.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(5));
+            inflight.fetch_sub(1, Ordering::SeqCst);
+            item * 2
+        }));
+        assert_eq!(most.load(Ordering::SeqCst), 2);
+        assert_eq!(doubled, (0..16).map(|item| item * 2).collect::<Vec<_>>());
+
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        let input = dir.join("Ships");
+        fs::create_dir_all(&input).unwrap();
+        for name in ["hero", "ship", "coin"] {
+            sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]).save(input.join(format!("{}.png", name))).unwrap();
+        }
+        sheet_with_sprites(16, 16, &[]).save(input.join("gem.png")).unwrap();
+        let config = CutterConfig {
+            input_dirs: vec![input],
+            output_dir: dir.join("out").to_string_lossy().to_string(),
+            max_inflight: Some(1),
+            timings: true,
+            ..CutterConfig::default()
+        };
+        let summary = SpritesheetCutter::new(config).process_directory().unwrap();
+        assert_eq!((summary.processed, summary.frames_total), (4, 7));
+        assert_eq!(summary.detection_methods.get(&DetectionMethod::SingleSprite), Some(&1));
+        // Each sheet is timed on its own
+        let mut timed: Vec<_> = summary.sheet_timings.iter()
+            .map(|(path, _)| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        timed.sort();
+        assert_eq!(timed, vec!["coin.png", "gem.png", "hero.png", "ship.png"]);
+
+        assert!(CutterConfig { max_inflight: Some(0), ..CutterConfig::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_file_list() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]).save(dir.join("hero.png")).unwrap();
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24)]).save(dir.join("coin.png")).unwrap();
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24)]).save(dir.join("unlisted.png")).unwrap();
+        let list_path = dir.join("sheets.txt");
+        fs::write(&list_path, format!("# sheets to cut\n{}\n\n  {}  \n{}\n", dir.join("hero.png").display(),
+                                      dir.join("coin.png").display(), dir.join("missing.png").display())).unwrap();
+
+        let config = CutterConfig {
+            file_list: Some(list_path.clone()),
+            output_dir: dir.join("out").to_string_lossy().to_string(),
+            jobs: Some(1),
+            ..CutterConfig::default()
+        };
+        let summary = SpritesheetCutter::new(config.clone()).process_directory().unwrap();
+        assert_eq!((summary.processed, summary.files_skipped), (2, 1));
+        assert!(dir.join("out").join("hero_frame_002.png").is_file());
+        assert!(dir.join("out").join("coin_frame_001.png").is_file());
+        assert!(!dir.join("out").join("unlisted_frame_001.png").exists());
+
+        let with_inputs = CutterConfig { input_dirs: vec![dir.clone()], ..config.clone() };
+        assert!(matches!(with_inputs.validate(), Err(CutterError::InvalidConfig(_))));
+        let unreadable = CutterConfig { file_list: Some(dir.join("nope.txt")), ..config };
+        assert!(matches!(SpritesheetCutter::new(unreadable).process_directory(), Err(CutterError::ReadFileList { .. })));
+    }
+
+    #[test]
+    fn test_normal_map_follows_diffuse_sheet() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]).save(dir.join("hero.png")).unwrap();
+        // A flat normal map with the sprites' bumps in slightly different places
+        let mut normal = RgbaImage::from_pixel(64, 32, Rgba([128, 128, 255, 255]));
+        for (x, y) in [(10, 10), (40, 20)] {
+            normal.put_pixel(x, y, Rgba([200, 90, 230, 255]));
+        }
+        normal.save(dir.join("hero_N.png")).unwrap();
+
+        let config = CutterConfig { normal_map_suffixes: vec!["_n".to_string()], ..CutterConfig::default() };
+        let cutter = SpritesheetCutter::new(config);
+        assert!(cutter.is_normal_map(&dir.join("hero_N.png")));
+        assert!(!cutter.is_normal_map(&dir.join("hero.png")));
+        assert!(!cutter.is_normal_map(&dir.join("_n.png")));
+
+        let output_dir = dir.join("out");
+        fs::create_dir_all(&output_dir).unwrap();
+        let (diffuse, _) = cutter.process_spritesheet(&dir.join("hero.png"), &output_dir).unwrap();
+        let (normals, _) = cutter.process_spritesheet(&dir.join("hero_N.png"), &output_dir).unwrap();
+        let rects = |saved: &[SavedFrame]| saved.iter()
+            .map(|saved| (saved.frame.x, saved.frame.y, saved.frame.width, saved.frame.height))
+            .collect::<Vec<_>>();
+        assert_eq!(rects(&normals), rects(&diffuse));
+        // The flat blue background survives
+        let frame = image::open(output_dir.join(&normals[0].filename)).unwrap().to_rgba8();
+        assert_eq!(frame.dimensions(), (normals[0].frame.width, normals[0].frame.height));
+        assert!(frame.pixels().all(|pixel| pixel[3] == 255));
+
+        RgbaImage::new(32, 32).save(dir.join("hero.png")).unwrap();
+        let err = cutter.process_spritesheet(&dir.join("hero_N.png"), &output_dir).unwrap_err();
+        assert!(matches!(err, CutterError::NormalMapSize { .. }));
+    }
+
+    #[test]
+    fn test_montage_entry() {
+        let config = CutterConfig::default();
+        let cutter = SpritesheetCutter::new(config);
+
+        let saved = vec![
+            saved_frame("it's_frame_001.png", SpriteFrame::new(0, 0, 16, 16)),
+            saved_frame("it's_frame_002.png", SpriteFrame::new(16, 0, 16, 16)),
+        ];
+        let entry = cutter.montage_entry(Path::new("sheets/it's.png"), &saved);
+
+        assert!(entry.contains("#   'it'\\''s_frame_002.png' 16x16+16+0"));
+        assert!(entry.contains("montage 'it'\\''s_frame_001.png' 'it'\\''s_frame_002.png' -tile 2x"));
+        assert!(entry.ends_with("'it'\\''s_montage.png'\n"));
+    }
+
+    /// Build a transparent sheet with opaque squares at the given rectangles
+    fn sheet_with_sprites(width: u32, height: u32, sprites: &[(u32, u32, u32, u32)]) -> DynamicImage {
+        let mut sheet = RgbaImage::new(width, height);
+        for &(x, y, w, h) in sprites {
+            for py in y..y + h {
+                for px in x..x + w {
+                    sheet.put_pixel(px, py, Rgba([200, 60, 60, 255]));
+                }
+            }
+        }
+        DynamicImage::ImageRgba8(sheet)
+    }
+
+    /// A saved frame written at full size, with no trimming
+    fn saved_frame(filename: &str, frame: SpriteFrame) -> SavedFrame {
+        SavedFrame {
+            filename: filename.to_string(),
+            trim_x: 0,
+            trim_y: 0,
+            width: frame.width,
+            height: frame.height,
+            padding: 0,
+            uniform: None,
+            duplicate_of: None,
+            duplicate_transform: None,
+            pivot: None,
+            colors: None,
+            kept: false,
+            kept_background: None,
+            background: None,
+            frame,
+        }
+    }
+
+    #[test]
+    fn test_alpha_threshold_trim() {
+        // A faint anti-aliased fringe (alpha 40) around an opaque core
+        let mut img = RgbaImage::new(12, 12);
+        for y in 2..10 {
+            for x in 2..10 {
+                let core = (4..8).contains(&x) && (4..8).contains(&y);
+                img.put_pixel(x, y, Rgba([255, 255, 255, if core { 255 } else { 40 }]));
+            }
+        }
+        let img = DynamicImage::ImageRgba8(img);
+
+        let keep_edges = SpritesheetCutter::new(CutterConfig::default());
+        let (trimmed, x, y) = keep_edges.trim_transparent(&img);
+        assert_eq!((trimmed.dimensions(), x, y), ((8, 8), 2, 2));
+
+        let tight = SpritesheetCutter::new(CutterConfig { alpha_threshold: 40, ..CutterConfig::default() });
+        let (trimmed, x, y) = tight.trim_transparent(&img);
+        assert_eq!((trimmed.dimensions(), x, y), ((4, 4), 4, 4));
+        assert!(!keep_edges.column_is_empty_alpha(&img.to_rgba8(), 3));
+        assert!(tight.column_is_empty_alpha(&img.to_rgba8(), 3));
+    }
+
+    #[test]
+    fn test_content_threshold() {
+        // A 1px laser across a 20x20 cell covers 5% of it
+        let img = sheet_with_sprites(20, 20, &[(0, 10, 20, 1)]);
+
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        assert!(cutter.frame_has_content(&img, 0, 0, 20, 20));
+
+        let strict = SpritesheetCutter::new(CutterConfig {
+            content_threshold: 0.1,
+            ..CutterConfig::default()
+        });
+        assert!(!strict.frame_has_content(&img, 0, 0, 20, 20));
+    }
+
+    #[test]
+    fn test_trim_transparent() {
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        let img = sheet_with_sprites(32, 32, &[(5, 7, 10, 4), (12, 9, 3, 12)]);
+
+        let (trimmed, offset_x, offset_y) = cutter.trim_transparent(&img);
+        assert_eq!((offset_x, offset_y), (5, 7));
+        assert_eq!(trimmed.dimensions(), (10, 14));
+
+        let empty = DynamicImage::ImageRgba8(RgbaImage::new(8, 8));
+        let (untouched, offset_x, offset_y) = cutter.trim_transparent(&empty);
+        assert_eq!((untouched.dimensions(), offset_x, offset_y), ((8, 8), 0, 0));
+    }
+
+    #[test]
+    fn test_trim_margin() {
+        // A solid core with a faint glow two pixels wide around it
+        let mut img = RgbaImage::new(20, 20);
+        for y in 6..14 {
+            for x in 6..14 {
+                let core = (8..12).contains(&x) && (8..12).contains(&y);
+                img.put_pixel(x, y, Rgba([255, 220, 120, if core { 255 } else { 8 }]));
+            }
+        }
+        let img = DynamicImage::ImageRgba8(img);
+
+        let tight = SpritesheetCutter::new(CutterConfig { trim: true, ..CutterConfig::default() });
+        let (trimmed, x, y) = tight.trim_transparent(&img);
+        assert_eq!((trimmed.dimensions(), x, y), ((4, 4), 8, 8));
+
+        let config = CutterConfig { trim: true, trim_margin: 3, ..CutterConfig::default() };
+        let with_margin = SpritesheetCutter::new(config);
+        let (trimmed, x, y) = with_margin.trim_transparent(&img);
+        assert_eq!((trimmed.dimensions(), x, y), ((10, 10), 5, 5));
+        // The whole halo survives, with one clear pixel beyond it
+        assert_eq!(trimmed.get_pixel(1, 1)[3], 8);
+        assert_eq!(trimmed.get_pixel(0, 0)[3], 0);
+
+        // The margin stops at the edges of the frame
+        let config = CutterConfig { trim: true, trim_margin: 50, ..CutterConfig::default() };
+        let (untouched, x, y) = SpritesheetCutter::new(config).trim_transparent(&img);
+        assert_eq!((untouched.dimensions(), x, y), ((20, 20), 0, 0));
+
+        let invalid = CutterConfig { trim_margin: 2, ..CutterConfig::default() };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_contact_sheet() {
+        let frame = |width, height, level| DynamicImage::ImageRgba8(RgbaImage::from_pixel(width, height, Rgba([level, 0, 0, 255])));
+        let frames = [frame(10, 8, 1), frame(6, 4, 2), frame(10, 8, 3)];
+
+        // Cells are as large as the biggest frame, two pixels apart
+        let grid = contact_sheet(&frames, 2);
+        assert_eq!(grid.dimensions(), (22, 18));
+        assert_eq!(grid.get_pixel(0, 0)[0], 1);
+        assert_eq!(grid.get_pixel(14, 2)[0], 2);
+        assert_eq!(grid.get_pixel(12, 0)[3], 0);
+        assert_eq!(grid.get_pixel(0, 10)[0], 3);
+        // The gap between cells stays clear
+        assert_eq!(grid.get_pixel(10, 0)[3], 0);
+
+        assert_eq!(contact_sheet(&frames, 1).dimensions(), (10, 28));
+        assert_eq!(contact_sheet(&frames, 5).dimensions(), (34, 8));
+
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        let sheet_path = dir.join("hero.png");
+        sheet_with_sprites(96, 32, &[(4, 4, 24, 24), (36, 4, 24, 24), (68, 4, 24, 24)]).save(&sheet_path).unwrap();
+        let cutter = SpritesheetCutter::new(CutterConfig { contact_sheet: true, ..CutterConfig::default() });
+        let (saved, _) = cutter.process_spritesheet(&sheet_path, &dir).unwrap();
+        let (width, height) = image::image_dimensions(dir.join("hero_contact.png")).unwrap();
+        // Three frames make a 2x2 grid
+        let (frame_width, frame_height) = (saved[0].width, saved[0].height);
+        assert_eq!((width, height), (2 * frame_width + 2, 2 * frame_height + 2));
+
+        assert!(CutterConfig { contact_sheet_columns: Some(3), ..CutterConfig::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_pad_frame() {
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(4, 3, Rgba([10, 20, 30, 255])));
+
+        let padded = cutter.pad_frame(&img, 2).to_rgba8();
+        assert_eq!(padded.dimensions(), (8, 7));
+        assert_eq!(padded.get_pixel(0, 0), &Rgba([0, 0, 0, 0]));
+        assert_eq!(padded.get_pixel(2, 2), &Rgba([10, 20, 30, 255]));
+        assert_eq!(padded.get_pixel(5, 4), &Rgba([10, 20, 30, 255]));
+        assert_eq!(padded.get_pixel(6, 5), &Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_slice_grid_even() {
+        let config = CutterConfig {
+            grid: Some(GridSpec::Count { cols: 2, rows: 2 }),
+            ..CutterConfig::default()
+        };
+        let cutter = SpritesheetCutter::new(config);
+        let img = sheet_with_sprites(64, 64, &[(4, 4, 8, 8), (36, 4, 8, 8), (4, 36, 8, 8), (36, 36, 8, 8)]);
+
+        let frames = cutter.detect_sprite_frames(&img).unwrap();
+        let rects: Vec<_> = frames.iter().map(|f| (f.x, f.y, f.width, f.height)).collect();
+        assert_eq!(rects, vec![(0, 0, 32, 32), (32, 0, 32, 32), (0, 32, 32, 32), (32, 32, 32, 32)]);
+    }
+
+    /// Run detection and return each frame as (x, y, width, height)
+    fn detected_rects(cutter: &SpritesheetCutter, img: &DynamicImage) -> Vec<(u32, u32, u32, u32)> {
+        cutter.detect_sprite_frames(img).unwrap().iter().map(|f| (f.x, f.y, f.width, f.height)).collect()
+    }
+
+    /// Assert every sprite lies inside exactly one detected frame
+    fn assert_one_frame_per_sprite(rects: &[(u32, u32, u32, u32)], sprites: &[(u32, u32, u32, u32)]) {
+        assert_eq!(rects.len(), sprites.len(), "detected {:?} for sprites {:?}", rects, sprites);
+        for &(sx, sy, sw, sh) in sprites {
+            let containing = rects.iter()
+                .filter(|&&(x, y, w, h)| x <= sx && y <= sy && sx + sw <= x + w && sy + sh <= y + h)
+                .count();
+            assert_eq!(containing, 1, "sprite {:?} not isolated by {:?}", (sx, sy, sw, sh), rects);
+        }
+    }
+
+    #[test]
+    fn test_detect_synthetic_grid_2x2() {
+        let sprites = [(4, 4, 12, 12), (24, 4, 12, 12), (4, 24, 12, 12), (24, 24, 12, 12)];
+        let img = sheet_with_sprites(40, 40, &sprites);
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+
+        let rects = detected_rects(&cutter, &img);
+        assert_one_frame_per_sprite(&rects, &sprites);
+        assert_eq!(rects, vec![(3, 3, 13, 13), (23, 3, 13, 13), (3, 23, 13, 13), (23, 23, 13, 13)]);
+    }
+
+    #[test]
+    fn test_detect_synthetic_horizontal_strip() {
+        let sprites = [(2, 3, 10, 10), (18, 3, 10, 10), (34, 3, 10, 10), (50, 3, 10, 10)];
+        let img = sheet_with_sprites(64, 16, &sprites);
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+
+        let rects = detected_rects(&cutter, &img);
+        assert_one_frame_per_sprite(&rects, &sprites);
+        assert_eq!(rects, vec![(1, 2, 11, 11), (17, 2, 11, 11), (33, 2, 11, 11), (49, 2, 11, 11)]);
+    }
+
+    #[test]
+    fn test_detect_synthetic_single_sprite() {
+        let sprites = [(6, 6, 12, 12)];
+        let img = sheet_with_sprites(24, 24, &sprites);
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+
+        let rects = detected_rects(&cutter, &img);
+        assert_one_frame_per_sprite(&rects, &sprites);
+        assert_eq!(rects, vec![(5, 5, 13, 13)]);
+    }
+
+    #[test]
+    fn test_frame_order() {
+        // Boundary detection walks columns first; numbering must still read row by row
+        let sprites = [(2, 2, 10, 10), (18, 2, 10, 10), (2, 18, 10, 10), (18, 18, 10, 10)];
+        let img = sheet_with_sprites(30, 30, &sprites);
+
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        let origins: Vec<_> = cutter.detect_sprite_frames(&img).unwrap().iter().map(|f| (f.x, f.y)).collect();
+        assert_eq!(origins, vec![(1, 1), (17, 1), (1, 17), (17, 17)]);
+
+        let cutter = SpritesheetCutter::new(CutterConfig {
+            frame_order: FrameOrder::ColumnMajor,
+            ..CutterConfig::default()
+        });
+        let origins: Vec<_> = cutter.detect_sprite_frames(&img).unwrap().iter().map(|f| (f.x, f.y)).collect();
+        assert_eq!(origins, vec![(1, 1), (1, 17), (17, 1), (17, 17)]);
+    }
+
+    #[test]
+    fn test_slice_grid_clamps_uneven_edges() {
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        let img = sheet_with_sprites(50, 20, &[(0, 0, 50, 20)]);
+
+        let frames = cutter.slice_grid(&img, GridSpec::CellSize { width: 16, height: 16 }).unwrap();
+        let rects: Vec<_> = frames.iter().map(|f| (f.x, f.y, f.width, f.height)).collect();
+        assert_eq!(rects.len(), 8);
+        assert_eq!(rects[3], (48, 0, 2, 16));
+        assert_eq!(rects[7], (48, 16, 2, 4));
+
+        let frames = cutter.slice_grid(&img, GridSpec::Count { cols: 3, rows: 1 }).unwrap();
+        let widths: Vec<_> = frames.iter().map(|f| f.width).collect();
+        assert_eq!(widths, vec![16, 16, 18]);
+    }
+
+    #[test]
+    fn test_slice_grid_margin_and_spacing() {
+        // 3x2 cells of 16px with a 2px margin and 1px spacing: 2 + 3*16 + 2*1 + 2 = 54 wide
+        let img = sheet_with_sprites(54, 37, &[(0, 0, 54, 37)]);
+        let cutter = SpritesheetCutter::new(CutterConfig { grid_margin: 2, grid_spacing: 1, ..CutterConfig::default() });
+        let expected = vec![(2, 2, 16, 16), (19, 2, 16, 16), (36, 2, 16, 16),
+                            (2, 19, 16, 16), (19, 19, 16, 16), (36, 19, 16, 16)];
+
+        let by_size = cutter.slice_grid(&img, GridSpec::CellSize { width: 16, height: 16 }).unwrap();
+        let rects: Vec<_> = by_size.iter().map(|f| (f.x, f.y, f.width, f.height)).collect();
+        assert_eq!(rects, expected);
+
+        let by_count = cutter.slice_grid(&img, GridSpec::Count { cols: 3, rows: 2 }).unwrap();
+        let rects: Vec<_> = by_count.iter().map(|f| (f.x, f.y, f.width, f.height)).collect();
+        assert_eq!(rects, expected);
+
+        let cutter = SpritesheetCutter::new(CutterConfig { grid_margin: 30, ..CutterConfig::default() });
+        assert!(cutter.slice_grid(&img, GridSpec::Count { cols: 3, rows: 2 }).is_err());
+    }
+
+    #[test]
+    fn test_connected_components() {
+        let config = CutterConfig {
+            detection_mode: DetectionMode::ConnectedComponents,
+            ..CutterConfig::default()
+        };
+        let cutter = SpritesheetCutter::new(config);
+        // Two tightly packed sprites, one L-shaped, plus a 2x2 speck of noise
+        let img = sheet_with_sprites(40, 30, &[(2, 2, 10, 4), (2, 6, 4, 12), (14, 3, 12, 20), (35, 25, 2, 2)]);
+
+        let frames = cutter.detect_sprite_frames(&img).unwrap();
+        let rects: Vec<_> = frames.iter().map(|f| (f.x, f.y, f.width, f.height)).collect();
+        assert_eq!(rects, vec![(2, 2, 10, 16), (14, 3, 12, 20)]);
+    }
+
+    #[test]
+    fn test_luma_source() {
+        // Magenta sprites on a red background, both about as bright
+        let mut sheet = image::RgbImage::from_pixel(64, 32, image::Rgb([200, 0, 0]));
+        for (sx, sy) in [(4, 4), (36, 4)] {
+            for y in sy..sy + 24 {
+                for x in sx..sx + 24 {
+                    sheet.put_pixel(x, y, image::Rgb([200, 0, 200]));
+                }
+            }
+        }
+        let img = DynamicImage::ImageRgb8(sheet);
+        assert_eq!(LumaSource::Blue.gray8(&img).get_pixel(0, 0)[0], 0);
+        assert_eq!(LumaSource::Max.gray8(&img).get_pixel(0, 0)[0], 200);
+        assert_eq!(LumaSource::Alpha.gray16(&img).get_pixel(0, 0)[0], u16::MAX);
+
+        let rects = |luma_source| -> Vec<_> {
+            let config = CutterConfig { luma_source, ..CutterConfig::default() };
+            SpritesheetCutter::new(config).detect_sprite_frames(&img).unwrap().iter()
+                .map(|frame| (frame.x, frame.y, frame.width, frame.height))
+                .collect()
+        };
+        // By brightness the gutters don't stand out, so only the plain red
+        // background splits the sheet
+        assert_eq!(rects(LumaSource::Luminance), vec![(4, 4, 24, 24), (36, 4, 24, 24)]);
+        assert_eq!(rects(LumaSource::Blue), vec![(3, 3, 25, 25), (35, 3, 25, 25)]);
+    }
+
+    #[test]
+    fn test_min_content_pixels() {
+        // A thin rope, a small solid gem and a sparse diagonal scratch
+        let mut sheet = sheet_with_sprites(40, 40, &[(2, 2, 2, 30), (30, 30, 4, 4)]).to_rgba8();
+        for i in 0..10 {
+            sheet.put_pixel(20 + i, 2 + i, Rgba([200, 60, 60, 255]));
+        }
+        let img = DynamicImage::ImageRgba8(sheet);
+        let rects = |min_content_pixels| -> Vec<_> {
+            let config = CutterConfig {
+                detection_mode: DetectionMode::ConnectedComponents,
+                min_content_pixels,
+                ..CutterConfig::default()
+            };
+            SpritesheetCutter::new(config).detect_sprite_frames(&img).unwrap().iter()
+                .map(|frame| (frame.x, frame.y, frame.width, frame.height))
+                .collect()
+        };
+
+        // By size the scratch is kept and the gem is dropped
+        assert_eq!(rects(None), vec![(2, 2, 2, 30), (20, 2, 10, 10)]);
+        // By content pixels it's the other way around
+        assert_eq!(rects(Some(16)), vec![(2, 2, 2, 30), (30, 30, 4, 4)]);
+
+        // Without alpha, content is whatever isn't background
+        let mut opaque = image::RgbImage::from_pixel(40, 20, image::Rgb([255, 255, 255]));
+        for (x, y) in (0..4).flat_map(|x| (0..4).map(move |y| (x, y))) {
+            opaque.put_pixel(4 + x, 8 + y, image::Rgb([20, 20, 20]));
+        }
+        let opaque = DynamicImage::ImageRgb8(opaque);
+        let config = CutterConfig {
+            grid: Some(GridSpec::Count { cols: 2, rows: 1 }),
+            min_content_pixels: Some(16),
+            ..CutterConfig::default()
+        };
+        let cutter = SpritesheetCutter::new(config);
+        assert_eq!(cutter.content_pixels(&opaque, 0, 0, 20, 20), 16);
+        let frames = cutter.detect_sprite_frames(&opaque).unwrap();
+        assert_eq!(frames.iter().map(|frame| (frame.x, frame.width)).collect::<Vec<_>>(), vec![(0, 20)]);
+
+        let config = CutterConfig { min_content_pixels: Some(0), ..CutterConfig::default() };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_clustered_detection() {
+        // Two rows of sprites bobbing up and down, so the second sprite of
+        // the top row starts above the first
+        let sprites = [(4, 12, 16, 16), (28, 4, 16, 16), (52, 18, 16, 16),
+                       (4, 44, 16, 16), (28, 52, 16, 16), (52, 40, 16, 16)];
+        let img = sheet_with_sprites(72, 72, &sprites);
+        let rects = |detection_mode, frame_order| -> Vec<_> {
+            let config = CutterConfig { detection_mode, frame_order, ..CutterConfig::default() };
+            SpritesheetCutter::new(config).detect_sprite_frames(&img).unwrap().iter()
+                .map(|frame| (frame.x, frame.y, frame.width, frame.height))
+                .collect()
+        };
+
+        assert_eq!(rects(DetectionMode::Clustered, FrameOrder::RowMajor), sprites.to_vec());
+        // Sorting by position puts the raised sprites first
+        let sorted = rects(DetectionMode::ConnectedComponents, FrameOrder::RowMajor);
+        assert_eq!(sorted[0], sprites[1]);
+
+        let by_columns = rects(DetectionMode::Clustered, FrameOrder::ColumnMajor);
+        let expected: Vec<_> = [0, 3, 1, 4, 2, 5].iter().map(|&i| sprites[i]).collect();
+        assert_eq!(by_columns, expected);
+    }
+
+    #[test]
+    fn test_merge_adjacent() {
+        // The striped column reads as an edge and splits the wide sprite in two
+        let mut sheet = sheet_with_sprites(96, 32, &[(4, 4, 40, 24), (56, 4, 24, 24)]).to_rgba8();
+        for y in 4..28 {
+            let level = if y % 2 == 0 { 0 } else { 255 };
+            sheet.put_pixel(24, y, Rgba([level, level, level, 255]));
+        }
+        let sheet = DynamicImage::ImageRgba8(sheet);
+        let rects = |config: CutterConfig| -> Vec<_> {
+            SpritesheetCutter::new(config).detect_sprite_frames(&sheet).unwrap().iter()
+                .map(|frame| (frame.x, frame.y, frame.width, frame.height))
+                .collect()
+        };
+        let split = rects(CutterConfig::default());
+        assert_eq!(split.len(), 3);
+
+        let config = CutterConfig { merge_adjacent: true, ..CutterConfig::default() };
+        let merged = rects(config.clone());
+        assert_eq!(merged.len(), 2);
+        let (x, _, width, _) = merged[0];
+        assert!(x <= 4 && x + width >= 44, "{:?}", merged[0]);
+        assert_eq!(merged[1], split[2]);
+
+        // Not when the merged frame would be too large
+        assert_eq!(rects(CutterConfig { max_sprite_size: 32, ..config }), split);
+    }
+
+    #[test]
+    fn test_merge_overlapping_frames() {
+        let frames = vec![
+            SpriteFrame::new(0, 0, 10, 10),
+            SpriteFrame::new(2, 2, 10, 10),
+            SpriteFrame::new(30, 0, 10, 10),
+        ];
+
+        let merged = merge_overlapping_frames(frames.clone(), 0.5);
+        let rects: Vec<_> = merged.iter().map(|f| (f.x, f.y, f.width, f.height)).collect();
+        assert_eq!(rects, vec![(0, 0, 12, 12), (30, 0, 10, 10)]);
+
+        // 64% overlap is not enough at a 0.9 threshold
+        assert_eq!(merge_overlapping_frames(frames, 0.9).len(), 3);
+    }
+
+    #[test]
+    fn test_pivot_points() {
+        // An L-shaped sprite: a heavy bottom bar and a thin post on the left
+        let mut sheet = sheet_with_sprites(64, 32, &[(4, 24, 24, 4), (36, 4, 24, 24)]).to_rgba8();
+        for y in 4..24 {
+            for x in 4..6 {
+                sheet.put_pixel(x, y, Rgba([200, 60, 60, 255]));
+            }
+        }
+        let img = DynamicImage::ImageRgba8(sheet);
+        assert_eq!(alpha_centroid(&DynamicImage::ImageRgba8(RgbaImage::new(4, 4))), None);
+        let (x, y) = alpha_centroid(&img.crop_imm(36, 4, 24, 24)).unwrap();
+        assert!((x - 12.0).abs() < 1e-4 && (y - 12.0).abs() < 1e-4);
+
+        let scratch = tempfile::tempdir().unwrap();
+        let output_dir = scratch.path().to_path_buf();
+        let sheet_path = output_dir.join("hero.png");
+        img.save(&sheet_path).unwrap();
+        let pivots = |pivot| {
+            let config = CutterConfig { pivot: Some(pivot), grid: Some(GridSpec::Count { cols: 2, rows: 1 }),
+                                        atlas_format: AtlasFormat::TexturePackerHash, ..CutterConfig::default() };
+            SpritesheetCutter::new(config).process_spritesheet(&sheet_path, &output_dir).unwrap();
+            let atlas: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(output_dir.join("hero.json")).unwrap()).unwrap();
+            atlas["frames"].as_object().unwrap().values()
+                .map(|frame| (frame["pivot"]["x"].as_f64().unwrap(), frame["pivot"]["y"].as_f64().unwrap()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(pivots(PivotMode::Center)[0], (0.5, 0.5));
+        assert_eq!(pivots(PivotMode::BottomCenter)[1], (0.5, 1.0));
+        // The L's mass sits low and to the left of its cell's middle
+        let (x, y) = pivots(PivotMode::Centroid)[0];
+        assert!(x < 0.5 && y > 0.5, "{:?}", (x, y));
+
+        assert!(CutterConfig { pivot: Some(PivotMode::Center), ..CutterConfig::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_suggest_detector() {
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        let sheet = sheet_with_sprites(64, 32, &[(2, 2, 12, 12), (18, 2, 12, 12), (34, 2, 12, 12), (50, 18, 8, 6)]);
+        let detection = |frames, method| Detection { frames, method };
+
+        let merged = detection(vec![SpriteFrame::new(0, 0, 64, 32)], DetectionMethod::Boundaries);
+        let suggestion = cutter.suggest_detector(&sheet, &merged).unwrap();
+        assert_eq!(suggestion, "one frame covers most of the sheet; consider --grid-cell 16x32 \
+                                (3 blocks of content repeat at that spacing)");
+        let fallback = detection(vec![SpriteFrame::new(0, 0, 32, 32), SpriteFrame::new(32, 0, 32, 32)],
+                                 DetectionMethod::HorizontalFallback);
+        assert!(cutter.suggest_detector(&sheet, &fallback).unwrap().contains("--grid-cell 16x32 ("));
+        let specks = detection(vec![SpriteFrame::new(0, 0, 1, 1); MANY_FRAMES], DetectionMethod::Boundaries);
+        assert!(cutter.suggest_detector(&sheet, &specks).unwrap().starts_with("cut into 1000 frames"));
+
+        // Cells are the spacing of the sprites, not their size, offset by a
+        // margin when the sprites don't start at the first cell
+        let spaced = sheet_with_sprites(96, 64, &[(4, 4, 24, 24), (36, 4, 24, 24), (68, 4, 24, 24),
+                                                  (4, 36, 24, 24), (36, 36, 24, 24), (68, 36, 24, 24)]);
+        let merged = detection(vec![SpriteFrame::new(0, 0, 96, 64)], DetectionMethod::Boundaries);
+        assert!(cutter.suggest_detector(&spaced, &merged).unwrap().ends_with("--grid-cell 32x32 (6 blocks of content \
+                                                                              repeat at that spacing)"));
+        let offset = sheet_with_sprites(80, 80, &[(10, 10, 24, 24), (42, 10, 24, 24), (10, 42, 24, 24), (42, 42, 24, 24)]);
+        let merged = detection(vec![SpriteFrame::new(0, 0, 80, 80)], DetectionMethod::Boundaries);
+        assert!(cutter.suggest_detector(&offset, &merged).unwrap().contains("--grid-cell 32x32 --grid-margin 10 ("));
+
+        // Cut well, or a lone sprite that nothing repeats
+        let cut = detection(cutter.detect(&sheet).unwrap(), DetectionMethod::Boundaries);
+        assert_eq!(cutter.suggest_detector(&sheet, &cut), None);
+        let icon = sheet_with_sprites(32, 32, &[(4, 4, 24, 24)]);
+        let copied = detection(vec![SpriteFrame::new(0, 0, 32, 32)], DetectionMethod::SingleSprite);
+        assert_eq!(cutter.suggest_detector(&icon, &copied), None);
+    }
+
+    #[test]
+    fn test_validate_frames() {
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        let frame = |x, y, width, height| SpriteFrame::new(x, y, width, height);
+
+        assert!(cutter.validate_frames(&[frame(0, 0, 24, 24), frame(32, 0, 20, 28)], (64, 32)).is_empty());
+        assert!(cutter.validate_frames(&[], (64, 32)).is_empty());
+
+        let uniform = cutter.validate_frames(&[frame(0, 0, 24, 24), frame(32, 0, 24, 24)], (64, 32));
+        assert_eq!(uniform, vec!["all 2 frames are 24x24; --grid or --auto-grid may cut this sheet more cleanly"]);
+
+        let covering = cutter.validate_frames(&[frame(0, 0, 62, 30)], (64, 32));
+        assert_eq!(covering, vec!["one frame covers 91% of the sheet; the gaps between sprites may have been missed"]);
+
+        let spread = cutter.validate_frames(&[frame(0, 0, 60, 30), frame(60, 0, 4, 4)], (64, 32));
+        assert_eq!(spread, vec!["frame sizes vary from 16 to 1800 pixels; some sprites may be split or merged"]);
+        assert!(DetectionMethod::Boundaries.is_heuristic() && !DetectionMethod::Grid.is_heuristic());
+    }
+
+    #[test]
+    fn test_sparrow_xml() {
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        let scratch = tempfile::tempdir().unwrap();
+        let output_dir = scratch.path().to_path_buf();
+
+        let trimmed = SavedFrame { trim_x: 3, trim_y: 2, width: 10, height: 20, pivot: Some((0.5, 1.0)),
+                                   ..saved_frame("hero_frame_002.png", SpriteFrame::new(16, 0, 16, 24)) };
+        let saved = vec![
+            saved_frame("hero_frame_001.png", SpriteFrame::new(0, 0, 16, 24)),
+            trimmed,
+        ];
+        cutter.write_sparrow_xml(Path::new("sheets/hero & \"co\".png"), &saved, &output_dir).unwrap();
+        let xml = fs::read_to_string(output_dir.join("hero & \"co\".xml")).unwrap();
+
+        assert_eq!(xml, concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<TextureAtlas imagePath=\"hero &amp; &quot;co&quot;.png\">\n",
+            "    <SubTexture name=\"hero_frame_001\" x=\"0\" y=\"0\" width=\"16\" height=\"24\"/>\n",
+            "    <SubTexture name=\"hero_frame_002\" x=\"19\" y=\"2\" width=\"10\" height=\"20\" ",
+            "frameX=\"-3\" frameY=\"-2\" frameWidth=\"16\" frameHeight=\"24\" pivotX=\"8\" pivotY=\"24\"/>\n",
+            "</TextureAtlas>\n",
+        ));
+        assert_eq!(xml_escape("<a & 'b'>"), "&lt;a &amp; &apos;b&apos;&gt;");
+
+        // A frame trimmed on one axis only has no offset on the other
+        let padded = SavedFrame { trim_y: 4, height: 20, ..saved_frame("a.png", SpriteFrame::new(0, 0, 16, 24)) };
+        assert!(sparrow_xml("a.png", &[padded], &[(0, 0)]).contains(" frameX=\"0\" frameY=\"-4\" "));
+    }
+
+    #[test]
+    fn test_texture_packer_hash() {
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        let scratch = tempfile::tempdir().unwrap();
+        let output_dir = scratch.path().to_path_buf();
+
+        let trimmed = SavedFrame { trim_x: 3, trim_y: 2, width: 10, height: 20,
+                                   ..saved_frame("hero_frame_002.png", SpriteFrame::new(16, 0, 16, 24)) };
+        let padded = SavedFrame { padding: 2,
+                                  ..saved_frame("hero_frame_003.png", SpriteFrame::new(32, 0, 16, 24)) };
+        let saved = vec![
+            saved_frame("hero_frame_001.png", SpriteFrame::new(0, 0, 16, 24)),
+            trimmed,
+            padded,
+        ];
+        cutter.write_texture_packer_hash(Path::new("sheets/hero.png"), (32, 24), &saved, DetectionMethod::Boundaries,
+                                         &output_dir).unwrap();
+
+        let atlas: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(output_dir.join("hero.json")).unwrap()).unwrap();
+
+        let keys: Vec<_> = atlas["frames"].as_object().unwrap().keys().cloned().collect();
+        assert_eq!(keys, vec!["hero_frame_001.png", "hero_frame_002.png", "hero_frame_003.png"]);
+        let first = &atlas["frames"]["hero_frame_001.png"];
+        assert_eq!(first["frame"], serde_json::json!({ "x": 0, "y": 0, "w": 16, "h": 24 }));
+        assert_eq!(first["trimmed"], false);
+        assert_eq!(first["spriteSourceSize"], serde_json::json!({ "x": 0, "y": 0, "w": 16, "h": 24 }));
+        let second = &atlas["frames"]["hero_frame_002.png"];
+        assert_eq!(second["frame"], serde_json::json!({ "x": 19, "y": 2, "w": 10, "h": 20 }));
+        assert_eq!(second["trimmed"], true);
+        assert_eq!(second["spriteSourceSize"], serde_json::json!({ "x": 3, "y": 2, "w": 10, "h": 20 }));
+        assert_eq!(second["sourceSize"], serde_json::json!({ "w": 16, "h": 24 }));
+        let third = &atlas["frames"]["hero_frame_003.png"];
+        assert_eq!(third["frame"], serde_json::json!({ "x": 32, "y": 0, "w": 16, "h": 24 }));
+        assert_eq!(third["spriteSourceSize"], serde_json::json!({ "x": 2, "y": 2, "w": 16, "h": 24 }));
+        assert_eq!(third["sourceSize"], serde_json::json!({ "w": 20, "h": 28 }));
+        assert_eq!(atlas["meta"]["image"], "hero.png");
+        assert_eq!(atlas["meta"]["size"], serde_json::json!({ "w": 32, "h": 24 }));
+        assert_eq!(atlas["meta"]["detection"], "boundaries");
+    }
+
+    #[test]
+    fn test_texture_packer_hash_background() {
+        let mut img = RgbaImage::from_pixel(64, 32, Rgba([255, 0, 255, 255]));
+        for (x, y) in [(4, 4), (36, 8)] {
+            for dy in 0..16 {
+                for dx in 0..20 {
+                    img.put_pixel(x + dx, y + dy, Rgba([200, 60, 60, 255]));
+                }
+            }
+        }
+        let scratch = tempfile::tempdir().unwrap();
+        let output_dir = scratch.path().to_path_buf();
+        let sheet_path = output_dir.join("hero.png");
+        img.save(&sheet_path).unwrap();
+        let meta = |config: CutterConfig| {
+            let atlas_file = if config.pack_mode == PackMode::None { "hero.json" } else { "hero_packed.json" };
+            let config = CutterConfig { grid: Some(GridSpec::Count { cols: 2, rows: 1 }),
+                                        atlas_format: AtlasFormat::TexturePackerHash, ..config };
+            SpritesheetCutter::new(config).process_spritesheet(&sheet_path, &output_dir).unwrap();
+            let atlas: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(output_dir.join(atlas_file)).unwrap()).unwrap();
+            atlas["meta"].clone()
+        };
+
+        let removed = meta(CutterConfig { background_mode: BackgroundMode::FloodFill, ..CutterConfig::default() });
+        assert_eq!(removed["background_color"], "#ff00ffff");
+        assert_eq!(removed["background_removal"], serde_json::json!({ "mode": "flood-fill", "color": "corners" }));
+        let keyed = meta(CutterConfig { background_key: Some(Rgba([255, 0, 255, 255])), ..CutterConfig::default() });
+        assert_eq!(keyed["background_removal"], serde_json::json!({ "mode": "color-match", "color": "key" }));
+        let packed = meta(CutterConfig { pack_mode: PackMode::Shelf, ..CutterConfig::default() });
+        assert_eq!(packed["background_color"], "#ff00ffff");
+        assert_eq!(packed["background_removal"], serde_json::json!({ "mode": "color-match", "color": "corners" }));
+        let kept = meta(CutterConfig { remove_background: false, ..CutterConfig::default() });
+        assert!(kept.get("background_color").is_none() && kept.get("background_removal").is_none());
+    }
+
+    #[test]
+    fn test_pack_shelves() {
+        // Tallest first: the 8x16 rect opens the first shelf, then the rest
+        // fill it until 24px is used up and a second shelf starts below
+        let sizes = [(8, 8), (8, 16), (8, 8), (8, 4)];
+        let packing = pack_shelves(&sizes, 24).unwrap();
+        assert_eq!(packing, ShelfPacking { positions: vec![(8, 0), (0, 0), (16, 0), (0, 16)], width: 24, height: 20 });
+
+        assert!(pack_shelves(&[(21, 4)], 20).is_none());
+        assert!(pack_shelves(&[(16, 16), (16, 16)], 20).is_none());
+    }
+
+    #[test]
+    fn test_pack_mode_writes_single_atlas() {
+        let scratch = tempfile::tempdir().unwrap();
+        let output_dir = scratch.path().to_path_buf();
+        let sheet_path = output_dir.join("hero.png");
+        sheet_with_sprites(96, 32, &[(4, 4, 24, 24), (36, 4, 24, 24), (68, 4, 24, 24)])
+            .save(&sheet_path).unwrap();
+
+        let config = CutterConfig { pack_mode: PackMode::Shelf, atlas_size: 64, ..CutterConfig::default() };
+        let cutter = SpritesheetCutter::new(config);
+        let (saved, _) = cutter.process_spritesheet(&sheet_path, &output_dir).unwrap();
+        assert_eq!(saved.len(), 3);
+        assert!(!output_dir.join(&saved[0].filename).exists());
+
+        let packed = image::open(output_dir.join("hero_packed.png")).unwrap();
+        let atlas: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(output_dir.join("hero_packed.json")).unwrap()).unwrap();
+        assert_eq!(atlas["meta"]["image"], "hero_packed.png");
+        assert_eq!(atlas["meta"]["size"], serde_json::json!({ "w": packed.width(), "h": packed.height() }));
+        assert!(packed.width() <= 64 && packed.height() <= 64);
+        assert_eq!(atlas["frames"].as_object().unwrap().len(), 3);
+
+        let config = CutterConfig { pack_mode: PackMode::Shelf, atlas_size: 16, ..CutterConfig::default() };
+        let err = SpritesheetCutter::new(config).process_spritesheet(&sheet_path, &output_dir).unwrap_err();
+        assert!(matches!(err, CutterError::AtlasOverflow { size: 16, .. }));
+    }
+
+    #[test]
+    fn test_uniform_frame_size() {
+        let scratch = tempfile::tempdir().unwrap();
+        let output_dir = scratch.path().to_path_buf();
+        let sheet_path = output_dir.join("hero.png");
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (40, 8, 12, 16)]).save(&sheet_path).unwrap();
+
+        let grid = Some(GridSpec::Count { cols: 2, rows: 1 });
+        let config = CutterConfig { grid, trim: true, remove_background: false, uniform_frame_size: Some(32),
+                                    atlas_format: AtlasFormat::TexturePackerHash, ..CutterConfig::default() };
+        let (saved, _) = SpritesheetCutter::new(config).process_spritesheet(&sheet_path, &output_dir).unwrap();
+        let second = image::open(output_dir.join(&saved[1].filename)).unwrap().to_rgba8();
+        assert_eq!(second.dimensions(), (32, 32));
+        assert_eq!(second.get_pixel(10, 8)[3], 255);
+        assert_eq!(second.get_pixel(9, 8)[3], 0);
+
+        let atlas: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(output_dir.join("hero.json")).unwrap()).unwrap();
+        let entry = &atlas["frames"][&saved[1].filename];
+        assert_eq!(entry["spriteSourceSize"], serde_json::json!({ "x": 10, "y": 8, "w": 12, "h": 16 }));
+        assert_eq!(entry["sourceSize"], serde_json::json!({ "w": 32, "h": 32 }));
+
+        let config = CutterConfig { grid, trim: true, remove_background: false, uniform_frame_size: Some(16),
+                                    ..CutterConfig::default() };
+        let err = SpritesheetCutter::new(config).process_spritesheet(&sheet_path, &output_dir).unwrap_err();
+        assert!(matches!(err, CutterError::FrameTooLarge { width: 24, height: 24, size: 16, .. }));
+    }
+
+    #[test]
+    fn test_format_frame_filename() {
+        let frame = SpriteFrame::new(32, 16, 24, 8);
+        assert_eq!(format_frame_filename("{name}_frame_{index:03}", "hero", 7, 6, &frame).unwrap(), "hero_frame_007");
+        assert_eq!(format_frame_filename("{name}-{index}@{x},{y}_{w}x{h}", "hero", 12, 11, &frame).unwrap(),
+                   "hero-12@32,16_24x8");
+        assert_eq!(format_frame_filename("{{{index:2}}}", "hero", 3, 2, &frame).unwrap(), "{03}");
+        assert!(format_frame_filename("{name}_{frame}", "hero", 1, 0, &frame).is_err());
+        assert!(format_frame_filename("{name", "hero", 1, 0, &frame).is_err());
+        assert!(format_frame_filename("{index:abc}", "hero", 1, 0, &frame).is_err());
+        // Frames without a cell count as one row, by position whatever the index
+        assert_eq!(format_frame_filename("{row}_{col}", "hero", 10, 4, &frame).unwrap(), "0_4");
+        let in_cell = SpriteFrame { cell: Some((2, 11)), ..frame };
+        assert_eq!(format_frame_filename("tile_{row}_{col:02}", "hero", 1, 0, &in_cell).unwrap(), "tile_2_11");
+    }
+
+    #[test]
+    fn test_grid_cell_filenames() {
+        let scratch = tempfile::tempdir().unwrap();
+        let output_dir = scratch.path().to_path_buf();
+        let sheet_path = output_dir.join("tiles.png");
+        // A 3x2 grid of 16px tiles with the middle of the top row empty
+        sheet_with_sprites(48, 32, &[(2, 2, 12, 12), (34, 2, 12, 12), (2, 18, 12, 12), (18, 18, 12, 12), (34, 18, 12, 12)])
+            .save(&sheet_path).unwrap();
+        let filenames = |sheet_path: &Path, config: CutterConfig| -> Vec<String> {
+            let config = CutterConfig { filename_template: "tile_{row}_{col}".to_string(), ..config };
+            config.validate().unwrap();
+            let (saved, _) = SpritesheetCutter::new(config).process_spritesheet(sheet_path, &output_dir).unwrap();
+            saved.into_iter().map(|saved| saved.filename).collect()
+        };
+
+        let grid = CutterConfig { grid: Some(GridSpec::Count { cols: 3, rows: 2 }), ..CutterConfig::default() };
+        assert_eq!(filenames(&sheet_path, grid),
+                   ["tile_0_0.png", "tile_0_2.png", "tile_1_0.png", "tile_1_1.png", "tile_1_2.png"]);
+
+        // An inferred 4x2 grid
+        let inferred_path = output_dir.join("inferred.png");
+        let sprites: Vec<_> = (0..8).map(|i| ((i % 4) * 32 + 4, (i / 4) * 32 + 6, 24, 20)).collect();
+        sheet_with_sprites(128, 64, &sprites).save(&inferred_path).unwrap();
+        let inferred = filenames(&inferred_path, CutterConfig { auto_grid: true, ..CutterConfig::default() });
+        assert_eq!(inferred[3..5], ["tile_0_3.png", "tile_1_0.png"]);
+
+        // Without a grid there are no cells to name frames after
+        let config = CutterConfig { filename_template: "tile_{row}_{col}".to_string(), ..CutterConfig::default() };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_animated_gif_input() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        let colors = [Rgba([255, 0, 0, 255]), Rgba([0, 255, 0, 255]), Rgba([0, 0, 255, 255])];
+        let frames = colors.iter().map(|&color| {
+            let mut canvas = RgbaImage::new(16, 16);
+            for (x, y) in (4..12).flat_map(|x| (4..12).map(move |y| (x, y))) {
+                canvas.put_pixel(x, y, color);
+            }
+            DynamicImage::ImageRgba8(canvas)
+        }).collect::<Vec<_>>();
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        cutter.export_gif(&frames, &dir.join("walk.gif"), 10).unwrap();
+        cutter.export_gif(&frames[..1], &dir.join("still.gif"), 10).unwrap();
+
+        let (strip, detection) = gif_animation_strip(&dir.join("walk.gif")).unwrap().unwrap();
+        assert_eq!(strip.dimensions(), (48, 16));
+        assert_eq!(detection.method, DetectionMethod::GifFrames);
+        let rects: Vec<_> = detection.frames.iter().map(|frame| (frame.x, frame.y, frame.width, frame.height)).collect();
+        assert_eq!(rects, vec![(0, 0, 16, 16), (16, 0, 16, 16), (32, 0, 16, 16)]);
+        assert!(gif_animation_strip(&dir.join("still.gif")).unwrap().is_none());
+
+        let out = dir.join("out");
+        fs::create_dir_all(&out).unwrap();
+        let (saved, method) = cutter.process_spritesheet(&dir.join("walk.gif"), &out).unwrap();
+        assert_eq!(method, DetectionMethod::GifFrames);
+        assert_eq!(saved.len(), 3);
+        // Written in playback order, one frame each
+        for (saved, color) in saved.iter().zip(colors) {
+            let frame = image::open(out.join(&saved.filename)).unwrap().to_rgba8();
+            assert_eq!(frame.get_pixel(8, 8), &color);
+        }
+    }
+
+    #[test]
+    fn test_export_gif_centers_frames() {
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        let scratch = tempfile::tempdir().unwrap();
+        let path = scratch.path().join("preview.gif");
+        let small = DynamicImage::ImageRgba8(RgbaImage::from_pixel(4, 2, Rgba([255, 0, 0, 255])));
+        let large = DynamicImage::ImageRgba8(RgbaImage::from_pixel(8, 6, Rgba([0, 0, 255, 255])));
+
+        cutter.export_gif(&[small, large], &path, 20).unwrap();
+        let decoder = image::codecs::gif::GifDecoder::new(fs::File::open(&path).unwrap()).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+
+        assert_eq!(frames.len(), 2);
+        let first = frames[0].buffer();
+        assert_eq!(first.dimensions(), (8, 6));
+        assert_eq!(first.get_pixel(0, 0)[3], 0);
+        assert_eq!(first.get_pixel(2, 2), &Rgba([255, 0, 0, 255]));
+        assert_eq!(frames[1].delay().numer_denom_ms(), (50, 1));
+    }
+
+    #[test]
+    fn test_structured_errors() {
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        let scratch = tempfile::tempdir().unwrap();
+        let output_dir = scratch.path().to_path_buf();
+
+        let missing = output_dir.join("missing.png");
+        let err = cutter.process_spritesheet(&missing, &output_dir).unwrap_err();
+        assert!(matches!(err, CutterError::OpenImage { ref path, .. } if *path == missing));
+
+        let blank = output_dir.join("blank.png");
+        RgbaImage::new(32, 32).save(&blank).unwrap();
+        let err = cutter.process_spritesheet(&blank, &output_dir).unwrap_err();
+        assert!(matches!(err, CutterError::NoFramesDetected { ref path } if *path == blank));
+
+        let config = CutterConfig { gif_fps: 0, ..CutterConfig::default() };
+        assert!(matches!(config.validate(), Err(CutterError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_atlas_file_frames() {
+        let hash = serde_json::json!({ "frames": {
+            "run_0.png": { "frame": { "x": 0, "y": 0, "w": 16, "h": 24 }, "rotated": false },
+            "run_1.png": { "frame": { "x": 16, "y": 0, "w": 16, "h": 24 }, "rotated": true },
+        }});
+        let rects: Vec<_> = atlas_frames(&hash).unwrap().iter().map(|f| (f.x, f.y, f.width, f.height)).collect();
+        assert_eq!(rects, vec![(0, 0, 16, 24), (16, 0, 24, 16)]);
+        assert!(atlas_frames(&serde_json::json!({ "frames": [{ "frame": { "x": 0 } }] })).is_err());
+
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        let sheet_path = dir.join("hero.png");
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]).save(&sheet_path).unwrap();
+        let aseprite = serde_json::json!({ "frames": [
+            { "filename": "hero 0.ase", "frame": { "x": 0, "y": 0, "w": 32, "h": 32 } },
+            { "filename": "hero 1.ase", "frame": { "x": 32, "y": 0, "w": 32, "h": 32 } },
+        ], "meta": { "app": "https://www.aseprite.org/" }});
+        fs::write(dir.join("hero.json"), aseprite.to_string()).unwrap();
+
+        let output_dir = dir.join("out");
+        fs::create_dir_all(&output_dir).unwrap();
+        let (saved, method) = SpritesheetCutter::new(CutterConfig::default())
+            .process_spritesheet(&sheet_path, &output_dir).unwrap();
+        assert_eq!(method, DetectionMethod::AtlasFile);
+        assert_eq!(saved.iter().map(|s| (s.frame.x, s.frame.width)).collect::<Vec<_>>(), vec![(0, 32), (32, 32)]);
+
+        let config = CutterConfig { read_atlas_files: false, ..CutterConfig::default() };
+        let (_, method) = SpritesheetCutter::new(config).process_spritesheet(&sheet_path, &output_dir).unwrap();
+        assert_ne!(method, DetectionMethod::AtlasFile);
+
+        // An explicit grid wins over the atlas file
+        let config = CutterConfig { grid: Some(GridSpec::Count { cols: 4, rows: 1 }), ..CutterConfig::default() };
+        let (saved, method) = SpritesheetCutter::new(config).process_spritesheet(&sheet_path, &output_dir).unwrap();
+        assert_eq!((saved.len(), method), (4, DetectionMethod::Grid));
+
+        // A broken atlas file falls back to detection instead of failing the sheet
+        fs::write(dir.join("hero.json"), "{ \"frames\": ").unwrap();
+        let (saved, method) = SpritesheetCutter::new(CutterConfig::default())
+            .process_spritesheet(&sheet_path, &output_dir).unwrap();
+        assert_eq!((saved.len(), method), (2, DetectionMethod::Boundaries));
+    }
+
+    #[test]
+    fn test_detect_then_extract_all() {
+        let img = sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]);
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+
+        let mut frames = cutter.detect(&img).unwrap();
+        assert_eq!(frames.len(), 2);
+        // Callers may edit the frames before extracting them
+        frames.pop();
+        let extracted = cutter.extract_all(&img, &frames).unwrap();
+        assert_eq!(extracted.len(), 1);
+        let frame = &frames[0];
+        assert_eq!(extracted[0].dimensions(), (frame.width, frame.height));
+    }
+
+    #[test]
+    fn test_background_opacity_floor() {
+        // A 4x4 bullet in a 16x16 frame, in the color that gets keyed out
+        let img = sheet_with_sprites(16, 16, &[(6, 6, 4, 4)]);
+        let frames = [SpriteFrame::new(0, 0, 16, 16)];
+        let bullet_alpha = |background_opacity_floor| {
+            let config = CutterConfig { background_key: Some(Rgba([200, 60, 60, 255])), background_opacity_floor,
+                                        ..CutterConfig::default() };
+            SpritesheetCutter::new(config).extract_all(&img, &frames).unwrap()[0].to_rgba8().get_pixel(7, 7)[3]
+        };
+
+        assert_eq!(bullet_alpha(None), 0);
+        // Only 1/16 of the frame is opaque, under the floor
+        assert_eq!(bullet_alpha(Some(0.1)), 255);
+        assert_eq!(bullet_alpha(Some(0.05)), 0);
+
+        // Without alpha, the pixels that aren't background count as opaque
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        let input = dir.join("Bullets");
+        fs::create_dir_all(&input).unwrap();
+        let mut opaque = image::RgbImage::from_pixel(16, 16, image::Rgb([255, 255, 255]));
+        for (x, y) in (6..10).flat_map(|x| (6..10).map(move |y| (x, y))) {
+            opaque.put_pixel(x, y, image::Rgb([200, 60, 60]));
+        }
+        let opaque = DynamicImage::ImageRgb8(opaque);
+        let corner_alpha = |background_opacity_floor| {
+            let config = CutterConfig { background_opacity_floor, ..CutterConfig::default() };
+            SpritesheetCutter::new(config).extract_all(&opaque, &frames).unwrap()[0].to_rgba8().get_pixel(0, 0)[3]
+        };
+        assert_eq!(corner_alpha(None), 0);
+        assert_eq!(corner_alpha(Some(0.1)), 255);
+
+        // Kept backgrounds are reported among the sheet's warnings
+        opaque.save(input.join("bullet.png")).unwrap();
+        let config = CutterConfig {
+            input_dirs: vec![input.clone()],
+            output_dir: dir.join("out").to_string_lossy().to_string(),
+            grid: Some(GridSpec::Count { cols: 1, rows: 1 }),
+            background_opacity_floor: Some(0.1),
+            ..CutterConfig::default()
+        };
+        let summary = SpritesheetCutter::new(config).process_directory().unwrap();
+        assert_eq!(summary.warnings.len(), 1);
+        assert!(summary.warnings[0]
+            .ends_with("bullet.png: kept the background of bullet_frame_001.png, as only 6.2% of it is opaque"));
+
+        let config = CutterConfig { background_opacity_floor: Some(1.5), ..CutterConfig::default() };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_extract_iter() {
+        let img = sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 20, 16)]);
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        let detection = cutter.detect_with_method(&img).unwrap();
+
+        let mut streamed = cutter.extract_iter(&img, &detection.frames);
+        let first = streamed.next().unwrap().unwrap();
+        assert_eq!(first.dimensions(), (detection.frames[0].width, detection.frames[0].height));
+        let rest: Vec<_> = streamed.collect::<Result<_>>().unwrap();
+        assert_eq!(rest.len(), detection.frames.len() - 1);
+
+        // The same frames as extracting them all at once
+        let all = cutter.extract_all(&img, &detection.frames).unwrap();
+        assert_eq!(all[0].to_rgba8(), first.to_rgba8());
+        assert_eq!(all[1..].iter().map(|frame| frame.to_rgba8()).collect::<Vec<_>>(),
+                   rest.iter().map(|frame| frame.to_rgba8()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_opaque_background_sheet() {
+        // A flattened export: three sprites on plain white, no alpha channel
+        let mut sheet = image::RgbImage::from_pixel(96, 40, image::Rgb([255, 255, 255]));
+        for (sprite_x, sprite_y, width, height, color) in [(4, 6, 24, 28, [200, 60, 60]), (36, 4, 20, 30, [40, 40, 160]),
+                                                           (64, 8, 28, 24, [30, 150, 30])] {
+            for y in sprite_y..sprite_y + height {
+                for x in sprite_x..sprite_x + width {
+                    sheet.put_pixel(x, y, image::Rgb(color));
+                }
+            }
+        }
+        let img = DynamicImage::ImageRgb8(sheet);
+        let detect = |refine_boundaries| {
+            let config = CutterConfig { refine_boundaries, ..CutterConfig::default() };
+            let detection = SpritesheetCutter::new(config).detect_with_method(&img).unwrap();
+            let rects: Vec<_> = detection.frames.iter().map(|f| (f.x, f.y, f.width, f.height)).collect();
+            (detection.method, rects)
+        };
+
+        // Each frame is tightened to its sprite
+        let sprites = vec![(36, 4, 20, 30), (4, 6, 24, 28), (64, 8, 28, 24)];
+        assert_eq!(detect(false), (DetectionMethod::BackgroundDifference, sprites.clone()));
+        assert_eq!(detect(true).1, sprites);
+
+        // Gutter lines in the configured gutter color split the sheet in the
+        // main pass already, and aren't part of any frame
+        let mut gutters = img.to_rgb8();
+        for y in 0..40 {
+            for x in [31, 32, 59, 60] {
+                gutters.put_pixel(x, y, image::Rgb([255, 0, 255]));
+            }
+        }
+        let config = CutterConfig { gutter_color: Some(Rgba([255, 0, 255, 255])), ..CutterConfig::default() };
+        let detection = SpritesheetCutter::new(config).detect_with_method(&DynamicImage::ImageRgb8(gutters)).unwrap();
+        let rects: Vec<_> = detection.frames.iter().map(|f| (f.x, f.y, f.width, f.height)).collect();
+        assert_eq!(rects, vec![(0, 0, 31, 40), (33, 0, 26, 40), (61, 0, 35, 40)]);
+
+        // The same sheet with an alpha channel keeps to the usual boundaries
+        let with_alpha = SpritesheetCutter::new(CutterConfig::default())
+            .detect_with_method(&DynamicImage::ImageRgba8(img.to_rgba8())).unwrap();
+        assert_ne!(with_alpha.method, DetectionMethod::BackgroundDifference);
+    }
+
+    #[test]
+    fn test_projection_detection() {
+        // Full-height sprites with gutters half covered in stray pixels: too
+        // busy to count as transparent, but clearly sparser than the sprites
+        let mut img = sheet_with_sprites(96, 32, &[(0, 0, 28, 32), (34, 0, 28, 32), (68, 0, 28, 32)]).to_rgba8();
+        for x in (28..34).chain(62..68) {
+            for y in (0..32).filter(|y| (y / 8 + x) % 2 == 0) {
+                img.put_pixel(x, y, Rgba([90, 90, 90, 255]));
+            }
+        }
+        let img = DynamicImage::ImageRgba8(img);
+        let config = CutterConfig { detection_mode: DetectionMode::Projection, ..CutterConfig::default() };
+        let cutter = SpritesheetCutter::new(config);
+
+        let (vertical, horizontal) = cutter.find_boundaries_by_projection(&img);
+        assert_eq!(vertical.len(), 4);
+        assert!((28..34).contains(&vertical[1]) && (62..68).contains(&vertical[2]));
+        assert_eq!(horizontal, vec![0, 32]);
+        let detection = cutter.detect_with_method(&img).unwrap();
+        assert_eq!(detection.method, DetectionMethod::Projection);
+        assert_eq!(detection.frames.len(), 3);
+
+        let boundaries = SpritesheetCutter::new(CutterConfig::default()).detect_with_method(&img).unwrap();
+        assert_ne!(boundaries.frames.len(), 3);
+
+        assert_eq!(projection_valleys(&[0, 0, 5, 6, 1, 0, 0, 7, 0], 0), vec![0, 6, 9]);
+    }
+
+    #[test]
+    fn test_autocrop_sheet() {
+        let img = sheet_with_sprites(160, 120, &[(40, 30, 24, 24), (72, 30, 24, 24)]);
+        let cutter = SpritesheetCutter::new(CutterConfig { autocrop: true, ..CutterConfig::default() });
+
+        let (cropped, x, y) = cutter.autocrop_sheet(&img);
+        assert_eq!((x, y), (40, 30));
+        assert_eq!(cropped.dimensions(), (56, 24));
+
+        // Frames come back in sheet coordinates
+        let frames = cutter.detect_sprite_frames(&img).unwrap();
+        assert_eq!(frames.len(), 2);
+        for (frame, sprite_x) in frames.iter().zip([40, 72]) {
+            assert!(frame.x <= sprite_x && frame.x + frame.width >= sprite_x + 24);
+            assert!(frame.y >= 30 && frame.y + frame.height <= 54);
+        }
+
+        let blank = DynamicImage::ImageRgba8(RgbaImage::new(16, 16));
+        let (whole, x, y) = cutter.autocrop_sheet(&blank);
+        assert_eq!((whole.dimensions(), x, y), ((16, 16), 0, 0));
+    }
+
+    #[test]
+    fn test_autocrop_opaque_sheet() {
+        // Three sprites on a plain blue background, no alpha channel
+        let mut sheet = image::RgbImage::from_pixel(160, 100, image::Rgb([30, 60, 200]));
+        for sprite_x in [40, 72, 104] {
+            for y in 40..64 {
+                for x in sprite_x..sprite_x + 24 {
+                    sheet.put_pixel(x, y, image::Rgb([220, 180, 40]));
+                }
+            }
+        }
+        let img = DynamicImage::ImageRgb8(sheet);
+
+        // The crop's corners are sprite pixels, so it's told apart from the
+        // background with the colors of the whole sheet
+        for detection_mode in [DetectionMode::Boundaries, DetectionMode::Projection, DetectionMode::ConnectedComponents] {
+            let config = CutterConfig { autocrop: true, detection_mode, ..CutterConfig::default() };
+            let frames = SpritesheetCutter::new(config).detect_sprite_frames(&img).unwrap();
+            assert_eq!(frames.len(), 3, "{:?}: {:?}", detection_mode, frames);
+            // Each frame holds one whole sprite and nothing of its neighbours
+            for (frame, sprite_x) in frames.iter().zip([40, 72, 104]) {
+                assert!(frame.x + 8 > sprite_x && frame.x <= sprite_x && frame.x + frame.width >= sprite_x + 24
+                        && frame.x + frame.width < sprite_x + 32, "{:?}: {:?}", detection_mode, frame);
+                assert_eq!((frame.y, frame.height), (40, 24), "{:?}", detection_mode);
+            }
+        }
+    }
+
+    #[test]
+    fn test_detection_method() {
+        let img = sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]);
+        let method = |config: CutterConfig| SpritesheetCutter::new(config).detect_with_method(&img).unwrap().method;
+
+        assert_eq!(method(CutterConfig::default()), DetectionMethod::Boundaries);
+        assert_eq!(method(CutterConfig { grid: Some(GridSpec::Count { cols: 2, rows: 1 }), ..CutterConfig::default() }),
+                   DetectionMethod::Grid);
+        assert_eq!(method(CutterConfig { strip_count: Some(2), ..CutterConfig::default() }), DetectionMethod::Strip);
+        assert_eq!(DetectionMethod::HorizontalFallback.to_string(), "horizontal fallback");
+        assert_eq!(serde_json::json!(DetectionMethod::VerticalFallback), "vertical-fallback");
+    }
+
+    #[test]
+    fn test_bevy_layout() {
+        let frames = vec![SpriteFrame::new(0, 0, 16, 24),
+                          SpriteFrame::new(20, 2, 10, 12)];
+        let irregular = SpritesheetCutter::new(CutterConfig::default()).bevy_layout("hero.png", (32, 24), &frames);
+        assert!(irregular.contains("TextureAtlasLayout::new_empty(UVec2::new(32, 24));"));
+        assert!(irregular.contains("layout.add_texture(URect::new(0, 0, 16, 24));\n    layout.add_texture(URect::new(20, 2, 30, 14));"));
+
+        let config = CutterConfig { grid: Some(GridSpec::CellSize { width: 16, height: 16 }), grid_margin: 1,
+                                    grid_spacing: 2, ..CutterConfig::default() };
+        let grid = SpritesheetCutter::new(config).bevy_layout("tiles.png", (70, 35), &frames);
+        assert!(grid.ends_with("TextureAtlasLayout::from_grid(UVec2::new(16, 16), 3, 1, Some(UVec2::splat(2)), \
+                                Some(UVec2::splat(1)))\n"), "{}", grid);
+
+        let config = CutterConfig { strip_count: Some(4), ..CutterConfig::default() };
+        let strip = SpritesheetCutter::new(config).bevy_layout("run.png", (128, 32), &frames);
+        assert!(strip.ends_with("TextureAtlasLayout::from_grid(UVec2::new(32, 32), 4, 1, None, None)\n"));
+
+        // Pivots become anchors by layout index, centered and with y up
+        let mut standing = saved_frame("hero_frame_001.png", frames[0].clone());
+        standing.pivot = Some((0.5, 1.0));
+        let mut cell = saved_frame("tiles_frame_001.png", SpriteFrame { cell: Some((0, 2)), ..frames[1].clone() });
+        cell.pivot = Some((0.25, 0.5));
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        assert_eq!(cutter.bevy_anchors((32, 24), &[saved_frame("plain.png", frames[0].clone()), standing]),
+                   "// Sprite::anchor of each index, from the frames' pivots\n\
+                    // 1: Anchor::Custom(Vec2::new(0.0, -0.5))\n");
+        let config = CutterConfig { grid: Some(GridSpec::CellSize { width: 16, height: 16 }), grid_margin: 1,
+                                    grid_spacing: 2, ..CutterConfig::default() };
+        assert!(SpritesheetCutter::new(config).bevy_anchors((70, 35), &[cell])
+            .ends_with("// 2: Anchor::Custom(Vec2::new(-0.25, 0.0))\n"));
+        assert!(CutterConfig { pivot: Some(PivotMode::Center), export_bevy: true, ..CutterConfig::default() }
+            .validate().is_ok());
+    }
+
+    #[test]
+    fn test_split_strip() {
+        // Art touching every edge, so empty-space detection has nothing to go on
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(50, 8, Rgba([200, 60, 60, 255])));
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+
+        let frames = cutter.split_strip(&img, 4, Orientation::Horizontal);
+        let rects: Vec<_> = frames.iter().map(|f| (f.x, f.y, f.width, f.height)).collect();
+        assert_eq!(rects, vec![(0, 0, 12, 8), (12, 0, 13, 8), (25, 0, 12, 8), (37, 0, 13, 8)]);
+
+        let frames = cutter.split_strip(&img, 2, Orientation::Vertical);
+        let rects: Vec<_> = frames.iter().map(|f| (f.x, f.y, f.width, f.height)).collect();
+        assert_eq!(rects, vec![(0, 0, 50, 4), (0, 4, 50, 4)]);
+
+        let config = CutterConfig { strip_count: Some(5), ..CutterConfig::default() };
+        assert_eq!(SpritesheetCutter::new(config).detect_sprite_frames(&img).unwrap().len(), 5);
+        let config = CutterConfig { strip_orientation: Orientation::Vertical, ..CutterConfig::default() };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_max_frames_skips_sheet() {
+        let scratch = tempfile::tempdir().unwrap();
+        let output_dir = scratch.path().to_path_buf();
+        let sheet_path = output_dir.join("many.png");
+        sheet_with_sprites(96, 32, &[(2, 2, 28, 28), (34, 2, 28, 28), (66, 2, 28, 28)]).save(&sheet_path).unwrap();
+
+        let config = CutterConfig { max_frames: Some(2), grid: Some(GridSpec::Count { cols: 3, rows: 1 }),
+                                    ..CutterConfig::default() };
+        let err = SpritesheetCutter::new(config).process_spritesheet(&sheet_path, &output_dir).unwrap_err();
+        assert!(matches!(err, CutterError::TooManyFrames { count: 3, max: 2, .. }));
+        let written: Vec<_> = fs::read_dir(&output_dir).unwrap().collect();
+        assert_eq!(written.len(), 1, "only the sheet itself should be there");
+    }
+
+    #[test]
+    fn test_include_exclude_patterns() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        for name in ["hero.png", "hero_backup.png", "enemy.PNG", "title.jpg", "notes.txt"] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        let found = |config: CutterConfig| {
+            let mut names: Vec<String> = SpritesheetCutter::new(config).find_image_files(&dir, &dir.join("out")).unwrap().iter()
+                .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(found(CutterConfig::default()).len(), 4);
+        let config = CutterConfig { include: vec!["*.png".to_string()], exclude: vec!["*_backup.*".to_string()],
+                                    ..CutterConfig::default() };
+        assert_eq!(found(config), vec!["enemy.PNG", "hero.png"]);
+
+        let bad = CutterConfig { exclude: vec!["[unclosed".to_string()], ..CutterConfig::default() };
+        assert!(matches!(bad.validate(), Err(CutterError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_run_summary_json() {
+        let scratch = tempfile::tempdir().unwrap();
+        let path = scratch.path().join("summary.json");
+        let summary = RunSummary { processed: 3, failed: 1, frames_total: 17, frames_deduped: 4, unchanged: 5,
+                                   over_frame_limit: 6, folders_skipped: 2,
+                                   detection_methods: BTreeMap::from([(DetectionMethod::Grid, 2),
+                                                                      (DetectionMethod::RelaxedBoundaries, 1)]),
+                                   files_skipped: 0, frames_kept: 7, sheet_timings: Vec::new(),
+                                   warnings: vec!["hero.png: frame 2 is 1x1".to_string()],
+                                   suggestions: vec!["boss.png: cut into 1000 frames".to_string()],
+                                   frame_changes: vec!["ship.png: 3 frame(s), 2 before".to_string()] };
+        summary.write_json(&path).unwrap();
+
+        let written: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written, serde_json::json!({ "processed": 3, "failed": 1, "frames_total": 17, "frames_deduped": 4,
+                                                "unchanged": 5, "frames_kept": 7, "over_frame_limit": 6,
+                                                "detection_methods": { "grid": 2, "relaxed-boundaries": 1 },
+                                                "warnings": ["hero.png: frame 2 is 1x1"],
+                                                "suggestions": ["boss.png: cut into 1000 frames"],
+                                                "frame_changes": ["ship.png: 3 frame(s), 2 before"] }));
+    }
+
+    #[test]
+    fn test_phase_timings() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        let sheet_path = dir.join("hero.png");
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]).save(&sheet_path).unwrap();
+
+        let cutter = SpritesheetCutter::new(CutterConfig { timings: true, ..CutterConfig::default() });
+        cutter.process_spritesheet(&sheet_path, &dir).unwrap();
+        let timings = cutter.take_timings();
+        assert!(timings.decode > Duration::ZERO && timings.detection > Duration::ZERO);
+        assert!(timings.background_removal > Duration::ZERO && timings.encoding > Duration::ZERO);
+        assert_eq!(cutter.take_timings(), PhaseTimings::default());
+
+        // Nothing is recorded unless asked for
+        let untimed = SpritesheetCutter::new(CutterConfig::default());
+        untimed.process_spritesheet(&sheet_path, &dir).unwrap();
+        assert_eq!(untimed.take_timings(), PhaseTimings::default());
+
+        let ms = Duration::from_millis;
+        let sheet = PhaseTimings { decode: ms(2), detection: ms(5), background_removal: ms(1), encoding: ms(3) };
+        let table = timings_table(&[(PathBuf::from("sheets/hero.png"), sheet), (PathBuf::from("coin.png"), sheet)]);
+        assert_eq!(table, vec![
+            "sheet        decode     detect background     encode total (ms)",
+            "hero.png        2.0        5.0        1.0        3.0       11.0",
+            "coin.png        2.0        5.0        1.0        3.0       11.0",
+            "total           4.0       10.0        2.0        6.0       22.0",
+        ]);
+    }
+
+    #[test]
+    fn test_scale_frame_nearest() {
+        let mut img = RgbaImage::new(2, 1);
+        img.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        img.put_pixel(1, 0, Rgba([0, 0, 255, 255]));
+        let img = DynamicImage::ImageRgba8(img);
+
+        let cutter = SpritesheetCutter::new(CutterConfig { scale: Some(3), ..CutterConfig::default() });
+        let scaled = cutter.scale_frame(img.clone()).to_rgba8();
+        assert_eq!(scaled.dimensions(), (6, 3));
+        // Hard edges: no blended pixels at the seam
+        assert_eq!(*scaled.get_pixel(2, 1), Rgba([255, 0, 0, 255]));
+        assert_eq!(*scaled.get_pixel(3, 1), Rgba([0, 0, 255, 255]));
+
+        let cutter = SpritesheetCutter::new(CutterConfig { scale_down: Some(4), ..CutterConfig::default() });
+        assert_eq!(cutter.scale_frame(img).dimensions(), (1, 1));
+
+        for config in [CutterConfig { scale: Some(0), ..CutterConfig::default() },
+                       CutterConfig { scale: Some(2), scale_down: Some(2), ..CutterConfig::default() },
+                       CutterConfig { scale: Some(2), atlas_format: AtlasFormat::TexturePackerHash,
+                                      ..CutterConfig::default() }] {
+            assert!(config.validate().is_err());
+        }
+    }
+
+    /// Encode `img` as a JPEG carrying an EXIF orientation tag
+    fn jpeg_with_orientation(img: &DynamicImage, orientation: u16) -> Vec<u8> {
+        let mut jpeg = Vec::new();
+        img.write_to(&mut io::Cursor::new(&mut jpeg), image::ImageOutputFormat::Jpeg(90)).unwrap();
+
+        // Big-endian TIFF header with one IFD holding a single SHORT tag
+        let mut tiff = b"MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01".to_vec();
+        tiff.extend_from_slice(&orientation.to_be_bytes());
+        tiff.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+        let mut app1 = b"Exif\0\0".to_vec();
+        app1.extend_from_slice(&tiff);
+
+        let mut out = jpeg[..2].to_vec();
+        out.extend_from_slice(&[0xff, 0xe1]);
+        out.extend_from_slice(&(app1.len() as u16 + 2).to_be_bytes());
+        out.extend_from_slice(&app1);
+        out.extend_from_slice(&jpeg[2..]);
+        out
+    }
+
+    #[test]
+    fn test_open_image_applies_exif_orientation() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        // A wide image whose left half is white; orientation 6 means the
+        // viewer rotates it 90° clockwise, so the white half ends up on top
+        let mut img = RgbaImage::from_pixel(40, 20, Rgba([0, 0, 0, 255]));
+        for y in 0..20 {
+            for x in 0..20 {
+                img.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+            }
+        }
+        let img = DynamicImage::ImageRgba8(img);
+
+        let rotated = dir.join("rotated.jpg");
+        fs::write(&rotated, jpeg_with_orientation(&img, 6)).unwrap();
+        assert_eq!(exif_orientation(&rotated), 6);
+        let opened = open_image(&rotated).unwrap().to_luma8();
+        assert_eq!(opened.dimensions(), (20, 40));
+        assert!(opened.get_pixel(10, 5)[0] > 200);
+        assert!(opened.get_pixel(10, 35)[0] < 50);
+
+        let upright = dir.join("upright.jpg");
+        fs::write(&upright, jpeg_with_orientation(&img, 1)).unwrap();
+        assert_eq!(open_image(&upright).unwrap().dimensions(), (40, 20));
+    }
+
+    #[test]
+    fn test_output_format() {
+        let scratch = tempfile::tempdir().unwrap();
+        let output_dir = scratch.path().to_path_buf();
+        let sheet_path = output_dir.join("hero.png");
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]).save(&sheet_path).unwrap();
+
+        let config = CutterConfig { output_format: OutputFormat::Webp, frame_padding: 2, remove_background: false,
+                                    ..CutterConfig::default() };
+        let (saved, _) = SpritesheetCutter::new(config).process_spritesheet(&sheet_path, &output_dir).unwrap();
+        assert_eq!(saved[0].filename, "hero_frame_001.webp");
+        let frame = image::open(output_dir.join(&saved[0].filename)).unwrap().to_rgba8();
+        // Padding stays transparent through WebP, the sprite stays opaque
+        assert_eq!(frame.get_pixel(0, 0)[3], 0);
+        assert_eq!(frame.get_pixel(10, 10)[3], 255);
+
+        let config = CutterConfig { output_format: OutputFormat::Jpeg, remove_background: false, ..CutterConfig::default() };
+        let (saved, _) = SpritesheetCutter::new(config).process_spritesheet(&sheet_path, &output_dir).unwrap();
+        assert_eq!(saved[1].filename, "hero_frame_002.jpg");
+        assert!(output_dir.join(&saved[1].filename).exists());
+
+        assert!(CutterConfig { output_format: OutputFormat::Jpeg, ..CutterConfig::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_output_quality() {
+        // Noise, which JPEG can only keep by spending bytes on it
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(48, 48, |x, y| {
+            image::Rgb([(x * 37 + y * 11) as u8, (x * y * 7) as u8, (x ^ y).wrapping_mul(29) as u8])
+        }));
+        let encoded_size = |output_format, output_quality| {
+            let config = CutterConfig { output_format, output_quality, remove_background: false, ..CutterConfig::default() };
+            SpritesheetCutter::new(config).encode_image(&img, "noise").unwrap().len()
+        };
+
+        assert!(encoded_size(OutputFormat::Jpeg, Some(20)) < encoded_size(OutputFormat::Jpeg, None));
+        assert!(encoded_size(OutputFormat::Jpeg, None) < encoded_size(OutputFormat::Jpeg, Some(95)));
+        // Lossless formats don't take a quality
+        assert_eq!(encoded_size(OutputFormat::Png, Some(20)), encoded_size(OutputFormat::Png, None));
+
+        for quality in [0, 101] {
+            assert!(CutterConfig { output_quality: Some(quality), ..CutterConfig::default() }.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_infer_grid() {
+        let sprites: Vec<_> = (0..8).map(|i| ((i % 4) * 32 + 4, (i / 4) * 32 + 6, 24, 20)).collect();
+        let img = sheet_with_sprites(128, 64, &sprites);
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        assert_eq!(cutter.infer_grid(&img), Some((4, 2, 32, 32)));
+
+        // Gaps of different widths don't make a grid
+        let uneven = sheet_with_sprites(128, 32, &[(4, 4, 24, 24), (32, 4, 24, 24), (80, 4, 24, 24)]);
+        assert_eq!(cutter.infer_grid(&uneven), None);
+        // Neither does a single sprite
+        assert_eq!(cutter.infer_grid(&sheet_with_sprites(32, 32, &[(4, 4, 24, 24)])), None);
+
+        let auto = SpritesheetCutter::new(CutterConfig { auto_grid: true, ..CutterConfig::default() });
+        let detection = auto.detect_with_method(&img).unwrap();
+        assert_eq!(detection.method, DetectionMethod::InferredGrid);
+        let rects: Vec<_> = detection.frames.iter().map(|frame| (frame.x, frame.y, frame.width, frame.height)).collect();
+        assert_eq!(rects[..5], [(0, 0, 32, 32), (32, 0, 32, 32), (64, 0, 32, 32), (96, 0, 32, 32), (0, 32, 32, 32)]);
+        assert_eq!(auto.detect_with_method(&uneven).unwrap().method, DetectionMethod::Boundaries);
+        // Other detection modes and autocrop don't find the boundaries the grid is inferred from
+        let config = CutterConfig { auto_grid: true, detection_mode: DetectionMode::ConnectedComponents,
+                                    ..CutterConfig::default() };
+        assert!(config.validate().is_err());
+        assert!(CutterConfig { auto_grid: true, autocrop: true, ..CutterConfig::default() }.validate().is_err());
+
+        assert_eq!(even_spacing(&[0, 1, 2, 30, 31, 32, 33, 62, 64], 64), Some((2, 32)));
+        assert_eq!(even_spacing(&[0, 20, 64], 64), None);
+    }
+
+    #[test]
+    fn test_premultiply_alpha() {
+        let cutter = SpritesheetCutter::new(CutterConfig { premultiply_alpha: true, ..CutterConfig::default() });
+        let mut img = RgbaImage::from_pixel(2, 1, Rgba([255, 255, 255, 128]));
+        img.put_pixel(1, 0, Rgba([200, 100, 50, 255]));
+        let premultiplied = cutter.premultiply_alpha(DynamicImage::ImageRgba8(img)).to_rgba8();
+        assert_eq!(premultiplied.get_pixel(0, 0), &Rgba([128, 128, 128, 128]));
+        assert_eq!(premultiplied.get_pixel(1, 0), &Rgba([200, 100, 50, 255]));
+
+        let scratch = tempfile::tempdir().unwrap();
+        let output_dir = scratch.path().to_path_buf();
+        let sheet_path = output_dir.join("hero.png");
+        let mut sheet = sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]).to_rgba8();
+        sheet.put_pixel(4, 4, Rgba([255, 255, 255, 128]));
+        sheet.save(&sheet_path).unwrap();
+        let config = CutterConfig { premultiply_alpha: true, trim: true, frame_padding: 1, ..CutterConfig::default() };
+        let (saved, _) = SpritesheetCutter::new(config).process_spritesheet(&sheet_path, &output_dir).unwrap();
+        // Trimming and padding come first, so the corner pixel sits just inside the padding
+        let frame = image::open(output_dir.join(&saved[0].filename)).unwrap().to_rgba8();
+        assert_eq!(frame.get_pixel(1, 1), &Rgba([128, 128, 128, 128]));
+        assert_eq!(frame.get_pixel(0, 0), &Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_region_of_interest() {
+        // A legend bar across the top that would otherwise be cut as a frame
+        let mut sheet = sheet_with_sprites(64, 48, &[(4, 20, 24, 24), (36, 20, 24, 24)]).to_rgba8();
+        for x in 0..64 {
+            for y in 2..10 {
+                sheet.put_pixel(x, y, Rgba([240, 240, 40, 255]));
+            }
+        }
+        let img = DynamicImage::ImageRgba8(sheet);
+
+        let cutter = SpritesheetCutter::new(CutterConfig { roi: Some((0, 14, 64, 34)), ..CutterConfig::default() });
+        let frames = cutter.detect_sprite_frames(&img).unwrap();
+        assert_eq!(frames.len(), 2);
+        // Frames are back in sheet coordinates and cover the sprites
+        for (frame, sprite_x) in frames.iter().zip([4, 36]) {
+            assert!(frame.y >= 14 && frame.y <= 20 && frame.y + frame.height >= 44);
+            assert!(frame.x <= sprite_x && frame.x + frame.width >= sprite_x + 24);
+        }
+
+        let outside = SpritesheetCutter::new(CutterConfig { roi: Some((32, 14, 64, 34)), ..CutterConfig::default() });
+        assert!(matches!(outside.detect_with_method(&img), Err(CutterError::InvalidConfig(_))));
+        assert!(CutterConfig { roi: Some((0, 0, 0, 10)), ..CutterConfig::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_sample_sheet() {
+        let sheet = sample_sheet();
+        assert_eq!(sheet.dimensions(), (128, 64));
+        assert_eq!(sheet, sample_sheet());
+
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        let frames = cutter.detect_sprite_frames(&DynamicImage::ImageRgba8(sheet)).unwrap();
+        assert_eq!(frames.len(), 8);
+        for (index, frame) in frames.iter().enumerate() {
+            let (col, row) = (index as u32 % 4, index as u32 / 4);
+            assert!(frame.x <= col * 32 + 4 && frame.x + frame.width >= col * 32 + 28, "{:?}", frame);
+            assert!(frame.y <= row * 32 + 4 && frame.y + frame.height >= row * 32 + 28, "{:?}", frame);
+        }
+    }
+
+    #[test]
+    fn test_frame_index_start() {
+        let scratch = tempfile::tempdir().unwrap();
+        let output_dir = scratch.path().to_path_buf();
+        let sheet_path = output_dir.join("hero.png");
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]).save(&sheet_path).unwrap();
+
+        let config = CutterConfig { frame_index_start: 0, ..CutterConfig::default() };
+        let (saved, _) = SpritesheetCutter::new(config).process_spritesheet(&sheet_path, &output_dir).unwrap();
+        let names: Vec<_> = saved.iter().map(|saved| saved.filename.as_str()).collect();
+        assert_eq!(names, vec!["hero_frame_000.png", "hero_frame_001.png"]);
+        assert!(output_dir.join("hero_frame_000.png").is_file());
+
+        let config = CutterConfig { frame_index_start: 10, filename_template: "{name}_{index}".to_string(),
+                                    ..CutterConfig::default() };
+        let (saved, _) = SpritesheetCutter::new(config).process_spritesheet(&sheet_path, &output_dir).unwrap();
+        assert_eq!(saved[0].filename, "hero_10.png");
+    }
+
+    /// White 3x2 sheet of 32px cells holding 20px red sprites, divided by
+    /// magenta lines `line_width` pixels wide
+    fn gutter_sheet(line_width: u32) -> DynamicImage {
+        let pitch = 32 + line_width;
+        let mut sheet = RgbaImage::from_pixel(3 * pitch - line_width, 2 * pitch - line_width, Rgba([255, 255, 255, 255]));
+        for (x, y, pixel) in sheet.enumerate_pixels_mut() {
+            if x % pitch >= 32 || y % pitch >= 32 {
+                *pixel = Rgba([255, 0, 255, 255]);
+            } else if (6..26).contains(&(x % pitch)) && (6..26).contains(&(y % pitch)) {
+                *pixel = Rgba([200, 60, 60, 255]);
+            }
+        }
+        DynamicImage::ImageRgba8(sheet)
+    }
+
+    #[test]
+    fn test_gutter_color() {
+        let magenta = Rgba([255, 0, 255, 255]);
+        let cutter = SpritesheetCutter::new(CutterConfig { detect_gutter: true, ..CutterConfig::default() });
+        let thin = gutter_sheet(2);
+        assert_eq!(cutter.gutter_color(&thin.to_rgba8()), Some(magenta));
+        // Gutters are only looked for when asked
+        assert_eq!(SpritesheetCutter::new(CutterConfig::default()).gutter_color(&thin.to_rgba8()), None);
+
+        let scratch = tempfile::tempdir().unwrap();
+        let output_dir = scratch.path().to_path_buf();
+        let sheet_path = output_dir.join("grid.png");
+        thin.save(&sheet_path).unwrap();
+        let config = CutterConfig { trim: true, detect_gutter: true, ..CutterConfig::default() };
+        let (saved, method) = SpritesheetCutter::new(config).process_spritesheet(&sheet_path, &output_dir).unwrap();
+        assert_eq!(method, DetectionMethod::Boundaries);
+        assert_eq!(saved.len(), 6);
+        for saved in &saved {
+            let frame = image::open(output_dir.join(&saved.filename)).unwrap();
+            assert_eq!(frame.dimensions(), (20, 20), "{}", saved.filename);
+        }
+
+        // Lines too wide to be taken for gutters need the color spelled out
+        let wide = gutter_sheet(6);
+        assert_eq!(cutter.gutter_color(&wide.to_rgba8()), None);
+        let config = CutterConfig { gutter_color: Some(magenta), ..CutterConfig::default() };
+        let frames = SpritesheetCutter::new(config).detect_sprite_frames(&wide).unwrap();
+        assert_eq!(frames.len(), 6);
+        assert!(frames.iter().all(|frame| frame.width >= 32 && frame.height >= 32), "{:?}", frames);
+        assert!(CutterConfig { gutter_color: Some(magenta), detect_gutter: true, ..CutterConfig::default() }
+            .validate().is_err());
+
+        // A detected gutter color only places boundaries: a line that looks
+        // like one is kept whole in the frames of a grid cut
+        let mut sword = sheet_with_sprites(40, 112, &[(4, 4, 32, 28), (4, 42, 32, 28), (4, 80, 32, 28)]).to_rgba8();
+        for y in 0..112 {
+            for x in [19, 20] {
+                sword.put_pixel(x, y, Rgba([255, 255, 0, 255]));
+            }
+        }
+        let sword = DynamicImage::ImageRgba8(sword);
+        let config = CutterConfig { grid: Some(GridSpec::Count { cols: 1, rows: 3 }), detect_gutter: true,
+                                    ..CutterConfig::default() };
+        let cutter = SpritesheetCutter::new(config);
+        let frames = cutter.detect_sprite_frames(&sword).unwrap();
+        let yellow: usize = cutter.extract_iter(&sword, &frames)
+            .map(|frame| frame.unwrap().to_rgba8().pixels().filter(|pixel| **pixel == Rgba([255, 255, 0, 255])).count())
+            .sum();
+        assert_eq!(yellow, 224);
+    }
+
+    #[test]
+    fn test_cut_to_memory() {
+        let scratch = tempfile::tempdir().unwrap();
+        let output_dir = scratch.path().to_path_buf();
+        let sheet_path = output_dir.join("hero.png");
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]).save(&sheet_path).unwrap();
+        let sprite_path = output_dir.join("coin.png");
+        sheet_with_sprites(16, 16, &[]).save(&sprite_path).unwrap();
+
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        let frames = cutter.cut_to_memory(&sheet_path).unwrap();
+        let names: Vec<_> = frames.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["hero_frame_001.png", "hero_frame_002.png"]);
+
+        let sprite_frames = cutter.cut_to_memory(&sprite_path).unwrap();
+        assert_eq!(sprite_frames.len(), 1);
+        assert_eq!(sprite_frames[0].0, "coin.png");
+        // Nothing is written next to the sheets
+        assert_eq!(fs::read_dir(&output_dir).unwrap().count(), 2);
+
+        // Same frames as cutting to disk
+        let frames_dir = output_dir.join("frames");
+        fs::create_dir_all(&frames_dir).unwrap();
+        cutter.process_spritesheet(&sheet_path, &frames_dir).unwrap();
+        for (name, bytes) in &frames {
+            let saved = image::open(frames_dir.join(name)).unwrap();
+            assert_eq!(image::load_from_memory(bytes).unwrap(), saved);
+        }
+
+        // Repeated frames are left out with dedup, as they are on disk
+        let deduping = SpritesheetCutter::new(CutterConfig { dedup: true, ..CutterConfig::default() });
+        let deduped = deduping.cut_to_memory(&sheet_path).unwrap();
+        assert_eq!(deduped.len(), 1);
+        assert_eq!(deduped[0].0, "hero_frame_001.png");
+
+        // A normal map is cut along its diffuse sheet's frames, keeping its background
+        let normal_path = output_dir.join("hero_n.png");
+        RgbaImage::from_pixel(64, 32, Rgba([128, 128, 255, 255])).save(&normal_path).unwrap();
+        let config = CutterConfig { normal_map_suffixes: vec!["_n".to_string()], ..CutterConfig::default() };
+        let normals = SpritesheetCutter::new(config).cut_to_memory(&normal_path).unwrap();
+        let names: Vec<_> = normals.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["hero_n_frame_001.png", "hero_n_frame_002.png"]);
+        let normal = image::load_from_memory(&normals[0].1).unwrap().to_rgba8();
+        assert_eq!(normal.dimensions(), image::load_from_memory(&frames[0].1).unwrap().dimensions());
+        assert!(normal.pixels().all(|pixel| *pixel == Rgba([128, 128, 255, 255])));
+    }
+
+    #[test]
+    fn test_watched_sheets() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        let (hero, ship) = (dir.join("hero.png"), dir.join("ship.png"));
+        sheet_with_sprites(16, 16, &[(2, 2, 8, 8)]).save(&hero).unwrap();
+        let cutter = SpritesheetCutter::new(CutterConfig { input_dirs: vec![dir.clone()], ..CutterConfig::default() });
+
+        let before = cutter.watched_sheets().unwrap();
+        assert_eq!(before.keys().collect::<Vec<_>>(), vec![&hero]);
+        assert!(changed_sheets(&before, &cutter.watched_sheets().unwrap()).is_empty());
+
+        // A rewritten sheet and a new one both count as changed
+        sheet_with_sprites(32, 16, &[(2, 2, 8, 8), (18, 2, 8, 8)]).save(&hero).unwrap();
+        sheet_with_sprites(16, 16, &[(2, 2, 8, 8)]).save(&ship).unwrap();
+        let after = cutter.watched_sheets().unwrap();
+        assert_eq!(changed_sheets(&before, &after), vec![hero.clone(), ship.clone()]);
+
+        // A deleted one doesn't
+        fs::remove_file(&hero).unwrap();
+        assert!(changed_sheets(&after, &cutter.watched_sheets().unwrap()).is_empty());
+
+        // Saving a sheet in a watched folder is noticed without polling
+        let (sender, events) = std::sync::mpsc::channel();
+        let _debouncer = cutter.notify_on_changes(sender).unwrap();
+        sheet_with_sprites(16, 16, &[(2, 2, 8, 8)]).save(&hero).unwrap();
+        let batch = events.recv_timeout(Duration::from_secs(10)).unwrap().unwrap();
+        assert!(batch.iter().any(|event| event.path.ends_with("hero.png")));
+
+        let config = CutterConfig { watch: true, manifest: Some(ManifestFormat::Csv), ..CutterConfig::default() };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_incremental_state() {
+        let scratch = tempfile::tempdir().unwrap();
+        let output_dir = scratch.path().to_path_buf();
+        let sheet_path = output_dir.join("hero.png");
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]).save(&sheet_path).unwrap();
+
+        let config = CutterConfig { incremental: true, atlas_format: AtlasFormat::TexturePackerHash,
+                                    ..CutterConfig::default() };
+        let cutter = SpritesheetCutter::new(config.clone());
+        let fingerprint = cutter.config_fingerprint();
+        let (saved, _) = cutter.process_spritesheet(&sheet_path, &output_dir).unwrap();
+        let mut state = IncrementalState::load(&output_dir, &fingerprint);
+        assert!(!state.is_up_to_date("hero.png", &sheet_path, &output_dir));
+        state.record("hero.png".to_string(), cutter.recorded_files(&sheet_path, &saved));
+        state.save(&output_dir).unwrap();
+
+        // Outputs were written after the sheet, with the same settings
+        let state = IncrementalState::load(&output_dir, &fingerprint);
+        assert!(state.is_up_to_date("hero.png", &sheet_path, &output_dir));
+
+        // Any change in settings invalidates everything recorded
+        let other = SpritesheetCutter::new(CutterConfig { trim: true, ..config.clone() });
+        assert_ne!(other.config_fingerprint(), fingerprint);
+        assert!(!IncrementalState::load(&output_dir, &other.config_fingerprint()).is_up_to_date("hero.png", &sheet_path, &output_dir));
+        // Settings that don't change the output don't count
+        let verbose = SpritesheetCutter::new(CutterConfig { verbose: true, ..config });
+        assert_eq!(verbose.config_fingerprint(), fingerprint);
+        assert_eq!(fnv1a(b"hero"), 0x0a4d_fbcc_5f62_4df9);
+
+        // A missing output means the sheet has to be redone, be it a frame or
+        // a side file like the atlas
+        fs::remove_file(output_dir.join("hero.json")).unwrap();
+        assert!(!state.is_up_to_date("hero.png", &sheet_path, &output_dir));
+        cutter.process_spritesheet(&sheet_path, &output_dir).unwrap();
+        assert!(state.is_up_to_date("hero.png", &sheet_path, &output_dir));
+        fs::remove_file(output_dir.join(&saved[0].filename)).unwrap();
+        assert!(!state.is_up_to_date("hero.png", &sheet_path, &output_dir));
+    }
+
+    #[test]
+    fn test_merge_close_boundaries() {
+        // Distance 0 only sorts, dedups and adds the edges
+        assert_eq!(merge_close_boundaries(&[(7, 0.5), (3, 0.5), (7, 0.9)], 0, 10), vec![0, 3, 7, 10]);
+
+        // A noisy cluster collapses onto its strongest edge
+        let noisy = [(20, 0.3), (21, 0.8), (23, 0.4), (40, 0.5)];
+        assert_eq!(merge_close_boundaries(&noisy, 3, 64), vec![0, 21, 40, 64]);
+
+        // Equally confident runs (a transparent gap) split in the middle, and
+        // candidates next to the edges are absorbed by them
+        let gap = [(1, 1.0), (30, 1.0), (31, 1.0), (32, 1.0), (33, 1.0), (63, 1.0)];
+        assert_eq!(merge_close_boundaries(&gap, 2, 64), vec![0, 32, 64]);
+
+        // The edges survive even when everything merges
+        assert_eq!(merge_close_boundaries(&[(2, 1.0)], 8, 4), vec![0, 4]);
+    }
+
+    #[test]
+    fn test_boundary_merge_distance_removes_slivers() {
+        // Two sprites with a hard vertical stripe inside each; the stripes'
+        // edges produce boundary candidates one pixel apart
+        let mut img = sheet_with_sprites(64, 32, &[(2, 2, 28, 28), (34, 2, 28, 28)]).to_rgba8();
+        for y in 2..30 {
+            for x in [14, 15, 46, 47] {
+                img.put_pixel(x, y, Rgba([250, 250, 250, 255]));
+            }
+        }
+        let gray = DynamicImage::ImageRgba8(img.clone()).to_luma16();
+
+        let config = CutterConfig { color_change_ratio: 0.0, color_change_delta: 10, ..CutterConfig::default() };
+        let noisy = SpritesheetCutter::new(config.clone()).find_vertical_boundaries(&gray, None);
+        let merged = SpritesheetCutter::new(CutterConfig { boundary_merge_distance: 2, ..config })
+            .find_vertical_boundaries(&gray, None);
+        assert!(merged.len() < noisy.len());
+        assert!(merged.windows(2).all(|pair| pair[1] - pair[0] > 2));
+    }
+
+    #[test]
+    fn test_config_file() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+
+        let toml_path = dir.join("cutter.toml");
+        fs::write(&toml_path, "min_sprite_size = 4\nbackground_key = \"ff00ff\"\nframe_order = \"column-major\"\n\
+                               [grid.count]\ncols = 4\nrows = 2\n").unwrap();
+        let config = CutterConfig::from_file(&toml_path).unwrap();
+        assert_eq!(config.min_sprite_size, 4);
+        assert_eq!(config.max_sprite_size, 1024);
+        assert_eq!(config.background_key, Some(Rgba([255, 0, 255, 255])));
+        assert_eq!(config.frame_order, FrameOrder::ColumnMajor);
+        assert_eq!(config.grid, Some(GridSpec::Count { cols: 4, rows: 2 }));
+
+        // What to_toml writes reads back the same
+        let round_trip: CutterConfig = toml::from_str(&config.to_toml()).unwrap();
+        assert_eq!(format!("{:?}", round_trip), format!("{:?}", config));
+
+        let json_path = dir.join("cutter.json");
+        fs::write(&json_path, r#"{ "trim": true, "detection_mode": "connected-components" }"#).unwrap();
+        let config = CutterConfig::from_file(&json_path).unwrap();
+        assert!(config.trim);
+        assert_eq!(config.detection_mode, DetectionMode::ConnectedComponents);
+
+        fs::write(&toml_path, "min_sprite_sise = 4\n").unwrap();
+        assert!(matches!(CutterConfig::from_file(&toml_path), Err(CutterError::ParseConfig { .. })));
+        assert!(matches!(CutterConfig::from_file(&dir.join("missing.toml")), Err(CutterError::ReadConfig { .. })));
+    }
+
+    #[test]
+    fn test_debug_overlay() {
+        let img = sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)]);
+        let frames = vec![SpriteFrame::new(2, 2, 28, 28),
+                          SpriteFrame::new(34, 2, 28, 28)];
+        let overlay = debug_overlay(&img, &frames, 1);
+
+        assert_eq!(overlay.dimensions(), (64, 32));
+        // Outline corners and edges
+        assert_eq!(*overlay.get_pixel(2, 29), OVERLAY_COLOR);
+        assert_eq!(*overlay.get_pixel(61, 15), OVERLAY_COLOR);
+        // The "1" label: black plate with the digit's top stroke in the middle column
+        assert_eq!(*overlay.get_pixel(4, 4), Rgba([0, 0, 0, 255]));
+        assert_eq!(*overlay.get_pixel(5, 4), OVERLAY_COLOR);
+        // The sheet itself shows through elsewhere
+        assert_eq!(*overlay.get_pixel(20, 20), *img.to_rgba8().get_pixel(20, 20));
+    }
+
+    #[test]
+    fn test_dedup_skips_repeated_frames() {
+        let scratch = tempfile::tempdir().unwrap();
+        let output_dir = scratch.path().to_path_buf();
+        let sheet_path = output_dir.join("walk.png");
+        // Frames 1 and 3 are identical red squares; frame 2 is smaller
+        sheet_with_sprites(96, 32, &[(4, 4, 24, 24), (40, 8, 16, 16), (68, 4, 24, 24)])
+            .save(&sheet_path).unwrap();
+
+        let config = CutterConfig { dedup: true, ..CutterConfig::default() };
+        let (saved, _) = SpritesheetCutter::new(config).process_spritesheet(&sheet_path, &output_dir).unwrap();
+        assert_eq!(saved.len(), 3);
+        assert_eq!(saved[2].duplicate_of.as_deref(), Some("walk_frame_001.png"));
+        assert!(output_dir.join("walk_frame_001.png").exists());
+        assert!(!output_dir.join("walk_frame_003.png").exists());
+
+        let duplicates: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(output_dir.join("walk_duplicates.json")).unwrap()).unwrap();
+        assert_eq!(duplicates, serde_json::json!({ "walk_frame_003.png": "walk_frame_001.png" }));
+    }
+
+    #[test]
+    fn test_frame_deduper_tolerance() {
+        let frame = DynamicImage::ImageRgba8(RgbaImage::from_pixel(4, 4, Rgba([100, 100, 100, 255])));
+        let close = DynamicImage::ImageRgba8(RgbaImage::from_pixel(4, 4, Rgba([103, 100, 100, 255])));
+
+        let mut exact = FrameDeduper::new(0);
+        assert_eq!(exact.check(&frame, "a.png"), None);
+        assert_eq!(exact.check(&close, "b.png"), None);
+        assert_eq!(exact.check(&frame, "c.png"), Some(("a.png".to_string(), None)));
+
+        let mut tolerant = FrameDeduper::new(3);
+        assert_eq!(tolerant.check(&frame, "a.png"), None);
+        assert_eq!(tolerant.check(&close, "b.png"), Some(("a.png".to_string(), None)));
+
+        assert!(CutterConfig { dedup_tolerance: 3, ..CutterConfig::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_dedup_transforms() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        let sheet_path = dir.join("arrow.png");
+        // An L-shaped sprite, the same one rotated clockwise, and it mirrored
+        let mut sprite = RgbaImage::new(10, 16);
+        for (x, y, pixel) in sprite.enumerate_pixels_mut() {
+            if x < 4 || y >= 12 {
+                *pixel = Rgba([200, 60, 60, 255]);
+            }
+        }
+        let mut sheet = RgbaImage::new(96, 32);
+        image::imageops::replace(&mut sheet, &sprite, 8, 8);
+        image::imageops::replace(&mut sheet, &image::imageops::rotate90(&sprite), 36, 8);
+        image::imageops::replace(&mut sheet, &image::imageops::flip_horizontal(&sprite), 72, 8);
+        DynamicImage::ImageRgba8(sheet).save(&sheet_path).unwrap();
+
+        let config = CutterConfig { dedup: true, trim: true, grid: Some(GridSpec::Count { cols: 3, rows: 1 }),
+                                    ..CutterConfig::default() };
+        let (saved, _) = SpritesheetCutter::new(config.clone()).process_spritesheet(&sheet_path, &dir).unwrap();
+        assert_eq!(saved.len(), 3);
+        assert!(saved.iter().all(|saved| saved.duplicate_of.is_none()));
+
+        let config = CutterConfig { dedup_transforms: true, ..config };
+        config.validate().unwrap();
+        let (saved, _) = SpritesheetCutter::new(config).process_spritesheet(&sheet_path, &dir).unwrap();
+        let duplicates: Vec<_> = saved.iter()
+            .map(|saved| (saved.duplicate_of.as_deref(), saved.duplicate_transform))
+            .collect();
+        assert_eq!(duplicates, [(None, None),
+                                (Some("arrow_frame_001.png"), Some(FrameTransform::Rotate90)),
+                                (Some("arrow_frame_001.png"), Some(FrameTransform::FlipHorizontal))]);
+        let written: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(dir.join("arrow_duplicates.json")).unwrap()).unwrap();
+        assert_eq!(written["arrow_frame_003.png"],
+                   serde_json::json!({ "frame": "arrow_frame_001.png", "transform": "flip-horizontal" }));
+
+        let packed = CutterConfig { dedup: true, dedup_transforms: true, pack_mode: PackMode::Shelf,
+                                    ..CutterConfig::default() };
+        assert!(packed.validate().is_err());
+        assert!(CutterConfig { dedup_transforms: true, ..CutterConfig::default() }.validate().is_err());
+    }
+
+    /// Write a 4x1 indexed PNG using `palette` (RGB triples) and `pixels`
+    /// (palette indices)
+    fn write_indexed_png(path: &Path, palette: &[u8], pixels: &[u8]) {
+        let file = fs::File::create(path).unwrap();
+        let mut encoder = png::Encoder::new(io::BufWriter::new(file), pixels.len() as u32, 1);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(palette.to_vec());
+        encoder.write_header().unwrap().write_image_data(pixels).unwrap();
+    }
+
+    #[test]
+    fn test_background_index() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        let sheet_path = dir.join("indexed.png");
+        // Slot 1 is a near-magenta that tolerance-based keying would also remove
+        write_indexed_png(&sheet_path, &[255, 0, 255, 250, 0, 250, 0, 0, 255], &[0, 1, 2, 0]);
+
+        assert_eq!(palette_color(&sheet_path, 1).unwrap(), Rgba([250, 0, 250, 255]));
+        let err = palette_color(&sheet_path, 3).unwrap_err();
+        assert!(matches!(err, CutterError::BackgroundIndex { index: 3, .. }));
+
+        let config = CutterConfig { background_index: Some(0), ..CutterConfig::default() };
+        let keyed = SpritesheetCutter::new(config).palette_keyed(&sheet_path).unwrap().unwrap();
+        let cleaned = keyed.remove_background(&image::open(&sheet_path).unwrap()).unwrap().to_rgba8();
+        let alphas: Vec<u8> = cleaned.pixels().map(|pixel| pixel[3]).collect();
+        assert_eq!(alphas, vec![0, 255, 255, 0]);
+
+        let rgb_path = dir.join("rgb.png");
+        sheet_with_sprites(4, 4, &[]).save(&rgb_path).unwrap();
+        assert!(palette_color(&rgb_path, 0).is_err());
+    }
+
+    #[test]
+    fn test_single_sprite_fallback() {
+        // A dot smaller than a sprite on an opaque magenta sheet, so no component is kept
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        let mut sheet = RgbaImage::from_pixel(40, 40, Rgba([255, 0, 255, 255]));
+        for y in 10..13 {
+            for x in 20..23 {
+                sheet.put_pixel(x, y, Rgba([200, 60, 60, 255]));
+            }
+        }
+        DynamicImage::ImageRgba8(sheet).to_rgb8().save(dir.join("dot.png")).unwrap();
+
+        let config = CutterConfig {
+            input_dirs: vec![dir.clone()],
+            output_dir: dir.join("out").to_string_lossy().to_string(),
+            detection_mode: DetectionMode::ConnectedComponents,
+            trim: true,
+            frame_padding: 1,
+            manifest: Some(ManifestFormat::Json),
+            ..CutterConfig::default()
+        };
+        let summary = SpritesheetCutter::new(config).process_directory().unwrap();
+        assert_eq!(summary.detection_methods.get(&DetectionMethod::SingleSprite), Some(&1));
+
+        // Cleaned, trimmed and padded just like a detected frame would be
+        let saved = image::open(dir.join("out").join(dir.file_name().unwrap()).join("dot.png")).unwrap().to_rgba8();
+        assert_eq!(saved.dimensions(), (5, 5));
+        assert_eq!(saved.get_pixel(0, 0)[3], 0);
+        assert_eq!(saved.get_pixel(2, 2), &Rgba([200, 60, 60, 255]));
+        assert!(saved.pixels().all(|pixel| pixel[3] == 0 || pixel[0] == 200));
+
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(dir.join("out").join("manifest.json")).unwrap()).unwrap();
+        assert_eq!((&manifest[0]["trimmed_x"], &manifest[0]["trimmed_y"]), (&serde_json::json!(20), &serde_json::json!(10)));
+    }
+
+    #[test]
+    #[cfg(feature = "tga")]
+    fn test_tga_sheets() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        // Upper case extensions are found too
+        sheet_with_sprites(64, 32, &[(4, 4, 24, 24), (36, 4, 24, 24)])
+            .save_with_format(dir.join("HERO.TGA"), image::ImageFormat::Tga).unwrap();
+
+        let cutter = SpritesheetCutter::new(CutterConfig::default());
+        let found = cutter.find_image_files(&dir, &dir.join("out")).unwrap();
+        assert_eq!(found, vec![dir.join("HERO.TGA")]);
+        let (saved, _) = cutter.process_spritesheet(&found[0], &dir).unwrap();
+        assert_eq!(saved.len(), 2);
+    }
+
+    #[test]
+    fn test_unsupported_format() {
+        // QOI's decoder isn't part of this build
+        let scratch = tempfile::tempdir().unwrap();
+        let path = scratch.path().join("unsupported.qoi");
+        fs::write(&path, b"qoif").unwrap();
+        let err = open_image(&path).unwrap_err();
+        assert!(matches!(err, CutterError::UnsupportedFormat { ref format, .. } if format == "qoi"));
+        assert!(err.to_string().ends_with("compiled without qoi support"));
+    }
+
+    #[test]
+    fn test_copy_keeps_grayscale() {
+        let scratch = tempfile::tempdir().unwrap();
+        let dir = scratch.path().to_path_buf();
+        let sprite_path = dir.join("gray.png");
+        image::GrayImage::from_pixel(6, 6, image::Luma([90])).save(&sprite_path).unwrap();
+
+        let output_dir = dir.join("out");
+        fs::create_dir_all(&output_dir).unwrap();
+        let config = CutterConfig { remove_background: false, ..CutterConfig::default() };
+        let saved = SpritesheetCutter::new(config).copy_single_sprite(&sprite_path, &output_dir).unwrap();
+        assert_eq!(image::open(output_dir.join(saved.filename)).unwrap().color(), image::ColorType::L8);
+
+        // 16-bit survives in PNG; formats without it get 8 bits of the same color type
+        let deep = DynamicImage::ImageLuma16(image::ImageBuffer::from_pixel(2, 2, image::Luma([4000u16])));
+        assert_eq!(OutputFormat::Png.encodable(&deep).color(), image::ColorType::L16);
+        assert_eq!(OutputFormat::Jpeg.encodable(&deep).color(), image::ColorType::L8);
+        let rgba = DynamicImage::ImageRgba8(RgbaImage::new(2, 2));
+        assert_eq!(OutputFormat::Jpeg.encodable(&rgba).color(), image::ColorType::Rgb8);
+    }
+
+    #[test]
+    fn test_background_match_alpha() {
+        // A faint anti-aliased edge in the magenta of an opaque background
+        let background = Rgba([255, 0, 255, 255]);
+        let edge = Rgba([255, 0, 255, 90]);
+        let cutter = |background_match_alpha| SpritesheetCutter::new(CutterConfig {
+            background_match_alpha,
+            ..CutterConfig::default()
+        });
+
+        assert!(cutter(false).is_background_pixel(&edge, &[background]));
+        assert!(!cutter(true).is_background_pixel(&edge, &[background]));
+        assert!(cutter(true).is_background_pixel(&Rgba([250, 4, 255, 252]), &[background]));
+
+        let mut sheet = RgbaImage::from_pixel(8, 8, background);
+        sheet.put_pixel(3, 3, edge);
+        let cleaned = cutter(true).remove_background(&DynamicImage::ImageRgba8(sheet)).unwrap().to_rgba8();
+        assert_eq!(cleaned.get_pixel(0, 0)[3], 0);
+        assert_eq!(cleaned.get_pixel(3, 3), &edge);
+    }
+
+    #[test]
+    fn test_color_distances_on_dark_edges() {
+        // A dark olive sprite edge on a near-black background: no single channel
+        // differs by more than the tolerance, but the color clearly does
+        let background = Rgba([8, 8, 8, 255]);
+        let edge = Rgba([28, 28, 8, 255]);
+        let matches = |color_distance| {
+            SpritesheetCutter::new(CutterConfig { color_distance, ..CutterConfig::default() })
+                .is_background_pixel(&edge, &[background])
+        };
+
+        assert!(matches(ColorMetric::PerChannel));
+        assert!(!matches(ColorMetric::RgbEuclidean));
+        assert!(!matches(ColorMetric::CielabDeltaE));
+        let delta_e = CutterConfig { color_distance: ColorMetric::CielabDeltaE, ..CutterConfig::default() };
+        assert!(SpritesheetCutter::new(delta_e).is_background_pixel(&Rgba([10, 9, 8, 255]), &[background]));
+
+        let white = srgb_to_lab(&Rgba([255, 255, 255, 255]));
+        assert!((white[0] - 100.0).abs() < 0.1 && white[1].abs() < 0.1 && white[2].abs() < 0.1);
+    }
+
+    #[test]
+    fn test_background_strategies() {
+        let corners = background_samples(30, 12, BackgroundStrategy::Corners);
+        assert_eq!(corners.len(), 20 * 12);
+        assert!(corners.contains(&(29, 11)) && !corners.contains(&(15, 0)));
+        let edges = background_samples(5, 4, BackgroundStrategy::EdgePixels);
+        assert_eq!(edges.len(), 14);
+        assert_eq!(background_samples(1, 1, BackgroundStrategy::EdgePixels), vec![(0, 0)]);
+
+        // Sprite art fills the corners, but the edges between them are background
+        let mut img = RgbaImage::from_pixel(40, 40, Rgba([0, 0, 255, 255]));
+        for &(x0, y0) in &[(0, 0), (32, 0), (0, 32), (32, 32)] {
+            for y in y0..y0 + 8 {
+                for x in x0..x0 + 8 {
+                    img.put_pixel(x, y, Rgba([200, 60, 60, 255]));
+                }
+            }
+        }
+        let detect = |strategy| SpritesheetCutter::new(CutterConfig { background_strategy: Some(strategy),
+                                                                      ..CutterConfig::default() })
+            .detect_background_colors(&img)[0];
+        assert_eq!(detect(BackgroundStrategy::Corners), Rgba([200, 60, 60, 255]));
+        assert_eq!(detect(BackgroundStrategy::EdgePixels), Rgba([0, 0, 255, 255]));
+        assert_eq!(detect(BackgroundStrategy::GlobalMode), Rgba([0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn test_checkerboard_background() {
+        // Two-tone sprites on a two-tone checkerboard
+        let mut sheet = RgbaImage::from_fn(96, 48, |x, y| {
+            if (x / 4 + y / 4) % 2 == 0 { Rgba([200, 200, 200, 255]) } else { Rgba([150, 150, 150, 255]) }
+        });
+        for &x0 in &[16, 56] {
+            for y in 12..36 {
+                for x in x0..x0 + 24 {
+                    let color = if y < 24 { Rgba([200, 60, 60, 255]) } else { Rgba([60, 60, 200, 255]) };
+                    sheet.put_pixel(x, y, color);
+                }
+            }
+        }
+        let img = DynamicImage::ImageRgba8(sheet);
+        let opaque = |background_color_count| {
+            let config = CutterConfig { background_color_count, ..CutterConfig::default() };
+            let cleared = SpritesheetCutter::new(config).remove_background(&img).unwrap().to_rgba8();
+            cleared.pixels().filter(|pixel| pixel[3] > 0).count()
+        };
+
+        // One color leaves half the checkerboard behind
+        assert!(opaque(1) > 2 * 24 * 24);
+        assert_eq!(opaque(2), 2 * 24 * 24);
+
+        // A sprite poking into one corner isn't a second background color
+        let mut corner = RgbaImage::from_pixel(40, 40, Rgba([255, 255, 255, 255]));
+        for (x, y) in (0..4).flat_map(|x| (0..4).map(move |y| (x, y))) {
+            corner.put_pixel(x, y, Rgba([200, 60, 60, 255]));
+        }
+        let cutter = SpritesheetCutter::new(CutterConfig { background_color_count: 2, ..CutterConfig::default() });
+        assert_eq!(cutter.detect_background_colors(&corner), vec![Rgba([255, 255, 255, 255])]);
+
+        // Equally common colors are ranked lowest first, however they hash
+        let split = RgbaImage::from_fn(20, 20, |x, _| {
+            if x < 10 { Rgba([250, 0, 0, 255]) } else { Rgba([5, 0, 0, 255]) }
+        });
+        assert_eq!(most_common_pixels(&split, BackgroundStrategy::Corners, 2),
+                   vec![Rgba([5, 0, 0, 255]), Rgba([250, 0, 0, 255])]);
+    }
+
+    #[test]
+    fn test_background_key() {
+        // Magenta-keyed sheet whose corners are covered by a large white sprite
+        let mut sheet = RgbaImage::from_pixel(20, 20, Rgba([255, 0, 255, 255]));
+        for y in 0..12 {
+            for x in 0..12 {
+                sheet.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+            }
+        }
+        let img = DynamicImage::ImageRgba8(sheet);
+
+        let cutter = SpritesheetCutter::new(CutterConfig {
+            background_key: Some(Rgba([255, 0, 255, 255])),
+            ..CutterConfig::default()
+        });
+        let cleaned = cutter.remove_background(&img).unwrap().to_rgba8();
+        assert_eq!(cleaned.get_pixel(0, 0), &Rgba([255, 255, 255, 255]));
+        assert_eq!(cleaned.get_pixel(15, 15)[3], 0);
+    }
+
+    #[test]
+    fn test_background_replacement() {
+        // Magenta sheet with a blue sprite in the middle
+        let mut img = RgbaImage::from_pixel(16, 16, Rgba([255, 0, 255, 255]));
+        for y in 4..12 {
+            for x in 4..12 {
+                img.put_pixel(x, y, Rgba([0, 0, 255, 255]));
+            }
+        }
+        let img = DynamicImage::ImageRgba8(img);
+        let gray = Rgba([40, 40, 40, 255]);
+
+        for mode in [BackgroundMode::ColorMatch, BackgroundMode::FloodFill] {
+            let cutter = SpritesheetCutter::new(CutterConfig {
+                background_replacement: Some(gray),
+                background_mode: mode,
+                ..CutterConfig::default()
+            });
+            let cleaned = cutter.remove_background(&img).unwrap().to_rgba8();
+            assert_eq!(*cleaned.get_pixel(0, 0), gray);
+            assert_eq!(*cleaned.get_pixel(8, 8), Rgba([0, 0, 255, 255]));
+        }
+
+        // An opaque replacement makes background removal usable with JPEG
+        let jpeg = CutterConfig { output_format: OutputFormat::Jpeg, ..CutterConfig::default() };
+        assert!(jpeg.validate().is_err());
+        assert!(CutterConfig { background_replacement: Some(gray), ..jpeg.clone() }.validate().is_ok());
+        assert!(CutterConfig { background_replacement: Some(Rgba([40, 40, 40, 128])), ..jpeg }.validate().is_err());
+    }
+
+    #[test]
+    fn test_flood_fill_keeps_enclosed_background() {
+        // White sheet with two red rings. The left ring is closed, so its white
+        // middle is enclosed; the right ring has a notch that opens it up.
+        let mut sheet = RgbaImage::from_pixel(40, 20, Rgba([255, 255, 255, 255]));
+        for &(x0, notched) in &[(2, false), (22, true)] {
+            for y in 2..18 {
+                for x in x0..x0 + 16 {
+                    let on_ring = x < x0 + 4 || x >= x0 + 12 || !(6..14).contains(&y);
+                    let in_notch = notched && x >= x0 + 12 && (9..11).contains(&y);
+                    if on_ring && !in_notch {
+                        sheet.put_pixel(x, y, Rgba([200, 0, 0, 255]));
+                    }
+                }
+            }
+        }
+        let img = DynamicImage::ImageRgba8(sheet);
+
+        let flood = SpritesheetCutter::new(CutterConfig {
+            background_mode: BackgroundMode::FloodFill,
+            ..CutterConfig::default()
+        });
+        let cleaned = flood.remove_background(&img).unwrap().to_rgba8();
+        assert_eq!(cleaned.get_pixel(0, 0)[3], 0);
+        assert_eq!(cleaned.get_pixel(3, 3), &Rgba([200, 0, 0, 255]));
+        // Closed ring: the enclosed middle is left alone
+        assert_eq!(cleaned.get_pixel(10, 10), &Rgba([255, 255, 255, 255]));
+        // Notched ring: the middle is reachable from outside, so it is cleared
+        assert_eq!(cleaned.get_pixel(30, 10)[3], 0);
+
+        let color_match = SpritesheetCutter::new(CutterConfig::default());
+        let cleaned = color_match.remove_background(&img).unwrap().to_rgba8();
+        assert_eq!(cleaned.get_pixel(10, 10)[3], 0);
+    }
+}
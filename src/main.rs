@@ -1,747 +1,1026 @@
-use anyhow::{Context, Result};
-use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
-use imageproc::definitions::Image;
-use std::collections::HashSet;
-use std::fs;
+use anyhow::{bail, ensure, Result};
+use base64::Engine;
+use clap::Parser;
+use image::Rgba;
+use log::{info, warn, Level, LevelFilter, Log, Metadata, Record};
+use spritesheet_cutter::{
+    parse_hex_color, AtlasFormat, BackgroundMode, BackgroundStrategy, ColorMetric, CutterConfig,
+    DetectionMode, FrameOrder, GridSpec, LumaSource, ManifestFormat, Orientation, OutputFormat,
+    OverwritePolicy, PackMode, PivotMode, SpritesheetCutter, SAMPLE_SHEET_FILE, SUMMARY_LOG_TARGET,
+};
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
-
-/// Configuration for the spritesheet cutter
-#[derive(Debug, Clone)]
-struct CutterConfig {
-    /// Minimum width/height for a sprite frame
-    min_sprite_size: u32,
-    /// Maximum width/height for a sprite frame
-    max_sprite_size: u32,
-    /// Tolerance for background color detection
-    background_tolerance: u8,
-    /// Whether to remove backgrounds
+
+/// Config files picked up from the current directory when --config isn't given
+const DEFAULT_CONFIG_FILES: [&str; 2] = ["cutter.toml", "cutter.json"];
+
+/// Command-line arguments
+#[derive(Parser, Debug)]
+#[command(name = "spritesheet-cutter", about = "Automatic sprite frame extraction")]
+struct Cli {
+    /// Folders to process, or single sheets to cut [default: Base Ships Space]
+    #[arg(value_name = "PATH")]
+    inputs: Vec<PathBuf>,
+    /// Process exactly the images listed in this file, one path per line ('-' for stdin), instead of walking folders
+    #[arg(long, value_name = "PATH", conflicts_with = "inputs")]
+    file_list: Option<PathBuf>,
+    /// Read settings from this TOML or JSON file [default: cutter.toml or cutter.json if present]
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Print the effective settings as TOML and exit
+    #[arg(long)]
+    print_config: bool,
+    /// Write a small sample spritesheet to try the cutter on and exit
+    #[arg(long)]
+    generate_sample: bool,
+    /// Let --generate-sample replace an existing sample sheet
+    #[arg(long, requires = "generate_sample")]
+    force: bool,
+    /// Cut a single sheet and print its frames to stdout as a JSON array of base64 strings instead of saving them
+    #[arg(long)]
+    stdout: bool,
+    /// Only process images whose file name matches this glob, e.g. '*.png' (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+    /// Skip images whose file name matches this glob, e.g. '*_backup.*' (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+    /// Minimum width/height for a sprite frame [default: 8]
+    #[arg(long = "min-size", value_name = "PIXELS")]
+    min_sprite_size: Option<u32>,
+    /// Maximum width/height for a sprite frame [default: 1024]
+    #[arg(long = "max-size", value_name = "PIXELS")]
+    max_sprite_size: Option<u32>,
+    /// Background color tolerance: absolute (0-255) or a percentage such as `8%` [default: 20]
+    #[arg(long = "bg-tolerance", visible_alias = "background-tolerance",
+          value_name = "TOLERANCE", value_parser = parse_tolerance)]
+    background_tolerance: Option<u8>,
+    /// Remove this exact background color (hex RRGGBB, e.g. ff00ff) instead of detecting it
+    #[arg(long, value_name = "HEX", value_parser = parse_hex_color)]
+    bg_key: Option<Rgba<u8>>,
+    /// Remove the color in this palette slot of indexed PNGs (or this gray level of 8-bit grayscale sheets) exactly
+    #[arg(long, value_name = "INDEX", conflicts_with = "bg_key")]
+    bg_index: Option<u8>,
+    /// Treat sheets whose names end in one of these (e.g. _n,_normal) as normal maps: no background removal or trimming
+    #[arg(long, value_name = "SUFFIXES", value_delimiter = ',')]
+    skip_bg_suffix: Vec<String>,
+    /// Paint removed background pixels this color (hex RRGGBB) instead of making them transparent
+    #[arg(long, value_name = "HEX", value_parser = parse_hex_color)]
+    bg_replace: Option<Rgba<u8>>,
+    /// Color of the lines between frames (hex RRGGBB) when it differs from the background, or auto to detect it
+    #[arg(long, value_name = "HEX|auto", value_parser = parse_gutter_color)]
+    gutter_color: Option<GutterColor>,
+    /// Which background-colored pixels to remove [default: color-match]
+    #[arg(long, value_enum)]
+    bg_mode: Option<BackgroundMode>,
+    /// Detect this many background colors, e.g. 2 for checkerboards [default: 1]
+    #[arg(long, value_name = "K")]
+    bg_colors: Option<usize>,
+    /// How color distance to the background is measured against the tolerance [default: per-channel]
+    #[arg(long, value_enum)]
+    color_distance: Option<ColorMetric>,
+    /// Only treat pixels as background when their alpha also matches the background's
+    #[arg(long)]
+    bg_match_alpha: bool,
+    /// Treat pixels as background whatever their alpha, overriding the config file
+    #[arg(long, conflicts_with = "bg_match_alpha")]
+    no_bg_match_alpha: bool,
+    /// Where to sample the background color [default: corners, or global-mode for the fallback pass]
+    #[arg(long, value_enum)]
+    bg_strategy: Option<BackgroundStrategy>,
+    /// Keep the original background instead of making it transparent
+    #[arg(long)]
+    no_remove_background: bool,
+    /// Make the background transparent, overriding the config file
+    #[arg(long, conflicts_with = "no_remove_background")]
     remove_background: bool,
-    /// Output directory name
-    output_dir: String,
+    /// Keep the background of frames where less than this fraction (0-1) of pixels is opaque
+    #[arg(long, value_name = "RATIO")]
+    bg_opacity_floor: Option<f32>,
+    /// Detect frames even when a TexturePacker or Aseprite <sheet>.json sits next to the sheet
+    #[arg(long)]
+    no_atlas_files: bool,
+    /// Cut sheets along their atlas files, overriding the config file
+    #[arg(long, conflicts_with = "no_atlas_files")]
+    atlas_files: bool,
+    /// Retry with relaxed thresholds when boundary detection finds at most one frame
+    #[arg(long)]
+    adaptive_retry: bool,
+    /// Don't retry boundary detection with relaxed thresholds, overriding the config file
+    #[arg(long, conflicts_with = "adaptive_retry")]
+    no_adaptive_retry: bool,
+    /// Slice a uniform grid of COLSxROWS cells instead of detecting boundaries
+    #[arg(long, value_name = "COLSxROWS", value_parser = parse_dimensions, conflicts_with = "grid_cell")]
+    grid: Option<(u32, u32)>,
+    /// Slice a uniform grid of WIDTHxHEIGHT pixel cells instead of detecting boundaries
+    #[arg(long, value_name = "WxH", value_parser = parse_dimensions)]
+    grid_cell: Option<(u32, u32)>,
+    /// Cut each sheet into exactly N equal frames along a strip instead of detecting them
+    #[arg(long, value_name = "N", conflicts_with_all = ["grid", "grid_cell"])]
+    strip_count: Option<u32>,
+    /// Direction the frames of a --strip-count strip run in [default: horizontal]
+    #[arg(long, value_enum)]
+    strip_orientation: Option<Orientation>,
+    /// Only detect frames inside this rectangle of each sheet, e.g. to skip a header
+    #[arg(long, value_name = "X,Y,W,H", value_parser = parse_rect)]
+    roi: Option<(u32, u32, u32, u32)>,
+    /// Pixels around the whole grid that belong to no cell [default: 0]
+    #[arg(long, value_name = "PIXELS")]
+    grid_margin: Option<u32>,
+    /// Pixels between neighbouring grid cells [default: 0]
+    #[arg(long, value_name = "PIXELS")]
+    grid_spacing: Option<u32>,
+    /// Fraction (0-1) of a candidate frame that must be non-transparent to keep it [default: 0.02]
+    #[arg(long, value_name = "RATIO")]
+    content_threshold: Option<f32>,
+    /// Keep frames by their number of non-transparent pixels instead of --min-size
+    #[arg(long, value_name = "PIXELS")]
+    min_content_pixels: Option<u32>,
+    /// Alpha (0-255) at or below which a pixel counts as transparent [default: 10]
+    #[arg(long, value_name = "ALPHA")]
+    alpha_threshold: Option<u8>,
+    /// Frame detection algorithm [default: boundaries]
+    #[arg(long, value_enum)]
+    detection_mode: Option<DetectionMode>,
+    /// With connected components or clustered detection, merge blobs whose boxes overlap by more than this fraction (0-1)
+    #[arg(long, value_name = "FRACTION")]
+    merge_overlap: Option<f32>,
+    /// Merge detected frames that touch with content running across the seam, for sprites split into pieces
+    #[arg(long)]
+    merge_adjacent: bool,
+    /// Don't merge frames that touch, overriding the config file
+    #[arg(long, conflicts_with = "merge_adjacent")]
+    no_merge_adjacent: bool,
+    /// Transparent border in pixels added around each saved frame, against texture bleeding [default: 0]
+    #[arg(long, value_name = "PIXELS")]
+    padding: Option<u32>,
+    /// Center every saved frame on a transparent SIZExSIZE canvas, e.g. 64 for GPU-friendly power-of-two frames
+    #[arg(long, value_name = "SIZE")]
+    uniform_size: Option<u32>,
+    /// Save frames with premultiplied alpha (color multiplied by alpha)
+    #[arg(long)]
+    premultiply: bool,
+    /// Save frames with straight alpha, overriding the config file
+    #[arg(long, conflicts_with = "premultiply")]
+    no_premultiply: bool,
+    /// Crop transparent borders from each frame (offsets go into the atlas file)
+    #[arg(long)]
+    trim: bool,
+    /// Don't crop transparent borders from frames, overriding the config file
+    #[arg(long, conflicts_with = "trim")]
+    no_trim: bool,
+    /// Keep this many pixels of the original border around the content when trimming
+    #[arg(long, value_name = "PIXELS")]
+    trim_margin: Option<u32>,
+    /// Crop the empty border around the whole sheet before detecting frames
+    #[arg(long)]
+    autocrop: bool,
+    /// Don't crop the empty border around the sheet, overriding the config file
+    #[arg(long, conflicts_with = "autocrop")]
+    no_autocrop: bool,
+    /// Slice sheets whose frames are evenly spaced as that grid
+    #[arg(long)]
+    auto_grid: bool,
+    /// Don't slice evenly spaced frames as a grid, overriding the config file
+    #[arg(long, conflicts_with = "auto_grid")]
+    no_auto_grid: bool,
+    /// Keep sprites touching the sheet's edge whole instead of treating the edge as a gap
+    #[arg(long)]
+    include_edge_frames: bool,
+    /// Treat the sheet's edge as a gap, overriding the config file
+    #[arg(long, conflicts_with = "include_edge_frames")]
+    no_include_edge_frames: bool,
+    /// Tighten boundary-detected frames to the first and last row and column with content
+    #[arg(long)]
+    refine_boundaries: bool,
+    /// Don't tighten boundary-detected frames, overriding the config file
+    #[arg(long, conflicts_with = "refine_boundaries")]
+    no_refine_boundaries: bool,
+    /// Collapse detected boundaries closer than this many pixels into the strongest one [default: 0]
+    #[arg(long, value_name = "PIXELS")]
+    boundary_merge_distance: Option<u32>,
+    /// Fraction (0-1) of a row/column that must be transparent to split frames there [default: 0.6]
+    #[arg(long, value_name = "RATIO")]
+    transparent_ratio: Option<f32>,
+    /// Luma jump between neighbouring pixels that counts as an edge [default: 30]
+    #[arg(long, value_name = "DELTA")]
+    color_change_delta: Option<u8>,
+    /// Fraction (0-1) of a row/column that must be edges to split frames there [default: 0.2]
+    #[arg(long, value_name = "RATIO")]
+    color_change_ratio: Option<f32>,
+    /// Channel that boundary detection compares pixels by [default: luminance]
+    #[arg(long, value_enum)]
+    luma_source: Option<LumaSource>,
+    /// Frame file name without extension; placeholders: {name} {index} {x} {y} {w} {h} {row} {col}, e.g. {index:03}
+    #[arg(long, value_name = "TEMPLATE")]
+    filename_template: Option<String>,
+    /// Number of the first frame of each sheet, e.g. 0 for zero-based names [default: 1]
+    #[arg(long, value_name = "N")]
+    index_start: Option<u32>,
+    /// Order in which frames are numbered [default: row-major]
+    #[arg(long, value_enum)]
+    frame_order: Option<FrameOrder>,
+    /// Also write an animated <sheet>_preview.gif of each sheet's frames
+    #[arg(long)]
+    preview_gif: bool,
+    /// Don't write preview GIFs, overriding the config file
+    #[arg(long, conflicts_with = "preview_gif")]
+    no_preview_gif: bool,
+    /// Frames per second for --preview-gif [default: 10]
+    #[arg(long, value_name = "FPS")]
+    gif_fps: Option<u32>,
+    /// Also write all of each sheet's frames in one grid image, <sheet>_contact.png
+    #[arg(long)]
+    contact_sheet: bool,
+    /// Don't write contact sheets, overriding the config file
+    #[arg(long, conflicts_with = "contact_sheet")]
+    no_contact_sheet: bool,
+    /// Frames per row of the contact sheet [default: a near-square grid]
+    #[arg(long, value_name = "N")]
+    contact_sheet_columns: Option<u32>,
+    /// Write a per-sheet atlas file describing the extracted frames [default: none]
+    #[arg(long, value_enum)]
+    atlas_format: Option<AtlasFormat>,
+    /// Anchor point to write for each frame of the atlas file or Bevy layout
+    #[arg(long, value_enum)]
+    pivot: Option<PivotMode>,
+    /// Write each frame's average and dominant color into the JSON atlas file and manifest
+    #[arg(long)]
+    emit_colors: bool,
+    /// Don't write frame colors, overriding the config file
+    #[arg(long, conflicts_with = "emit_colors")]
+    no_emit_colors: bool,
+    /// Write a manifest of every saved frame and its source rect into the output directory
+    #[arg(long, value_enum)]
+    manifest: Option<ManifestFormat>,
+    /// Compare the frames cut with a manifest.json from an earlier run and list the sheets whose frames changed
+    #[arg(long, value_name = "MANIFEST")]
+    compare: Option<PathBuf>,
+    /// Re-pack each sheet's frames into one <sheet>_packed.png plus <sheet>_packed.json instead of one PNG per frame
+    #[arg(long, value_enum, value_name = "MODE", num_args = 0..=1, require_equals = true,
+          default_missing_value = "shelf")]
+    pack: Option<PackMode>,
+    /// Largest width/height of a packed atlas in pixels [default: 2048]
+    #[arg(long, value_name = "PIXELS")]
+    atlas_size: Option<u32>,
+    /// Don't save frames that repeat an earlier frame of the same sheet; the
+    /// mapping goes to <sheet>_duplicates.json
+    #[arg(long)]
+    dedup: bool,
+    /// Save frames that repeat an earlier frame, overriding the config file
+    #[arg(long, conflicts_with = "dedup")]
+    no_dedup: bool,
+    /// Largest per-channel difference (0-255) at which frames still count as duplicates [default: 0]
+    #[arg(long, value_name = "DELTA")]
+    dedup_tolerance: Option<u8>,
+    /// Also count frames that are an earlier frame rotated or flipped as duplicates
+    #[arg(long)]
+    dedup_transforms: bool,
+    /// Only count identical frames as duplicates, not rotated or flipped ones, overriding the config file
+    #[arg(long, conflicts_with = "dedup_transforms")]
+    no_dedup_transforms: bool,
+    /// Image format of saved frames; jpeg has no transparency and needs --no-remove-background [default: png]
+    #[arg(long, value_enum)]
+    output_format: Option<OutputFormat>,
+    /// Quality (1-100) of JPEG frames; higher is larger and sharper. Ignored for webp, png and bmp, which are
+    /// always saved lossless [default: 75]
+    #[arg(long, value_name = "QUALITY")]
+    quality: Option<u8>,
+    /// Enlarge saved frames by this integer factor, keeping hard pixel edges
+    #[arg(long, value_name = "FACTOR", conflicts_with = "scale_down")]
+    scale: Option<u32>,
+    /// Shrink saved frames by this integer divisor, keeping hard pixel edges
+    #[arg(long, value_name = "DIVISOR")]
+    scale_down: Option<u32>,
+    /// Skip sheets whose outputs from an earlier --incremental run with the same settings are newer than the sheet
+    #[arg(long)]
+    incremental: bool,
+    /// Cut every sheet, even unchanged ones, overriding the config file
+    #[arg(long, conflicts_with = "incremental")]
+    no_incremental: bool,
+    /// Keep running and cut sheets again whenever they are saved or added
+    #[arg(long, conflicts_with = "stdout")]
+    watch: bool,
+    /// Exit after cutting everything once, overriding the config file
+    #[arg(long, conflicts_with = "watch")]
+    no_watch: bool,
+    /// Skip sheets where more than this many frames are detected, instead of writing them all
+    #[arg(long, value_name = "COUNT")]
+    max_frames: Option<usize>,
+    /// Write {processed, failed, frames_total, frames_deduped, unchanged, frames_kept, over_frame_limit,
+    /// detection_methods, warnings, suggestions, frame_changes} as JSON to this file when the run ends
+    #[arg(long, value_name = "PATH")]
+    summary_json: Option<PathBuf>,
+    /// Time decoding, detection, background removal and encoding per sheet and print a table at the end
+    #[arg(long)]
+    timings: bool,
+    /// Don't time the processing phases, overriding the config file
+    #[arg(long, conflicts_with = "timings")]
+    no_timings: bool,
+    /// Output directory name [default: assets2]
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<String>,
+    /// Resolve a relative --output-dir against each input folder instead of the current directory
+    #[arg(long)]
+    output_relative_to_input: bool,
+    /// Resolve a relative --output-dir against the current directory, overriding the config file
+    #[arg(long, conflicts_with = "output_relative_to_input")]
+    no_output_relative_to_input: bool,
+    /// What to do with output files that already exist [default: overwrite]
+    #[arg(long, value_enum, value_name = "POLICY")]
+    overwrite: Option<OverwritePolicy>,
+    /// Keep output files that already exist, e.g. ones edited by hand (same as --overwrite skip)
+    #[arg(long, conflicts_with = "overwrite")]
+    no_overwrite: bool,
+    /// Run this command on every frame file written, with {file} replaced by its path
+    #[arg(long, value_name = "COMMAND")]
+    exec: Option<String>,
+    /// Also look for sheets in subfolders of the input folders
+    #[arg(short, long)]
+    recursive: bool,
+    /// Only look for sheets directly in the input folders, overriding the config file
+    #[arg(long, conflicts_with = "recursive")]
+    no_recursive: bool,
+    /// Write sheets from subfolders straight into the output folder, prefixing their names with the subfolders
+    #[arg(long)]
+    flatten_output: bool,
+    /// Keep the input's subfolders in the output, overriding the config file
+    #[arg(long, conflicts_with = "flatten_output")]
+    no_flatten_output: bool,
+    /// Write each sheet's outputs into its own folder, e.g. assets2/Ships/hero/frame_001.png
+    #[arg(long)]
+    subfolder_per_sheet: bool,
+    /// Write sheets' outputs straight into the output folder, overriding the config file
+    #[arg(long, conflicts_with = "subfolder_per_sheet")]
+    no_subfolder_per_sheet: bool,
+    /// Write a montage.txt per output folder for rebuilding contact sheets with ImageMagick
+    #[arg(long)]
+    montage: bool,
+    /// Don't write montage.txt files, overriding the config file
+    #[arg(long, conflicts_with = "montage")]
+    no_montage: bool,
+    /// Also write <sheet>_bevy.rs, a Bevy TextureAtlasLayout for the source sheet
+    #[arg(long)]
+    export_bevy: bool,
+    /// Don't write Bevy layouts, overriding the config file
+    #[arg(long, conflicts_with = "export_bevy")]
+    no_export_bevy: bool,
+    /// Also write <sheet>_debug.png with the detected frames outlined and numbered
+    #[arg(long)]
+    debug_overlay: bool,
+    /// Don't write debug overlays, overriding the config file
+    #[arg(long, conflicts_with = "debug_overlay")]
+    no_debug_overlay: bool,
+    /// Log each image and detection step line by line instead of showing a progress bar
+    #[arg(short, long)]
+    verbose: bool,
+    /// Show a progress bar instead of logging each step, overriding the config file
+    #[arg(long, conflicts_with = "verbose")]
+    no_verbose: bool,
+    /// Only report errors, then one summary line; CUTTER_LOG (e.g. warn, debug) overrides the levels
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Most worker threads to use [default: one per logical CPU]
+    #[arg(short, long, value_name = "N")]
+    jobs: Option<usize>,
+    /// Most sheets to decode and cut at once, to bound memory on large sheets [default: one per thread]
+    #[arg(long, value_name = "N")]
+    max_inflight: Option<usize>,
 }
 
-impl Default for CutterConfig {
-    fn default() -> Self {
-        Self {
-            min_sprite_size: 8,  // Reduced from 16 to catch smaller sprites
-            max_sprite_size: 1024,  // Increased from 512 to handle larger sprites
-            background_tolerance: 20,  // Increased from 10 for better background detection
-            remove_background: true,
-            output_dir: "assets2".to_string(),
+impl Cli {
+    /// Build a cutter configuration: flags given on the command line win over
+    /// the config file, which wins over the defaults
+    fn into_config(self) -> Result<CutterConfig> {
+        let mut config = match self.config_file() {
+            Some(path) => CutterConfig::from_file(&path)?,
+            None => CutterConfig::default(),
+        };
+
+        if !self.inputs.is_empty() {
+            config.input_dirs = self.inputs;
+        }
+        if let Some(path) = self.file_list {
+            config.file_list = Some(path);
+        }
+        if !self.include.is_empty() {
+            config.include = self.include;
+        }
+        if !self.exclude.is_empty() {
+            config.exclude = self.exclude;
+        }
+        if let Some(min_size) = self.min_sprite_size {
+            config.min_sprite_size = min_size;
+        }
+        if let Some(max_size) = self.max_sprite_size {
+            config.max_sprite_size = max_size;
+        }
+        if let Some(tolerance) = self.background_tolerance {
+            config.background_tolerance = tolerance;
+        }
+        if let Some(key) = self.bg_key {
+            config.background_key = Some(key);
+        }
+        if let Some(index) = self.bg_index {
+            config.background_index = Some(index);
+        }
+        if !self.skip_bg_suffix.is_empty() {
+            config.normal_map_suffixes = self.skip_bg_suffix;
+        }
+        if let Some(color) = self.bg_replace {
+            config.background_replacement = Some(color);
+        }
+        match self.gutter_color {
+            Some(GutterColor::Auto) => config.detect_gutter = true,
+            Some(GutterColor::Hex(color)) => config.gutter_color = Some(color),
+            None => {}
+        }
+        if let Some(count) = self.bg_colors {
+            config.background_color_count = count;
+        }
+        if let Some(metric) = self.color_distance {
+            config.color_distance = metric;
+        }
+        if self.bg_match_alpha || self.no_bg_match_alpha {
+            config.background_match_alpha = self.bg_match_alpha;
+        }
+        if let Some(strategy) = self.bg_strategy {
+            config.background_strategy = Some(strategy);
+        }
+        if let Some(mode) = self.bg_mode {
+            config.background_mode = mode;
+        }
+        if self.remove_background || self.no_remove_background {
+            config.remove_background = self.remove_background;
+        }
+        if let Some(floor) = self.bg_opacity_floor {
+            config.background_opacity_floor = Some(floor);
+        }
+        if self.atlas_files || self.no_atlas_files {
+            config.read_atlas_files = self.atlas_files;
+        }
+        if self.adaptive_retry || self.no_adaptive_retry {
+            config.adaptive_retry = self.adaptive_retry;
+        }
+        if let Some(output_dir) = self.output_dir {
+            config.output_dir = output_dir;
+        }
+        if self.output_relative_to_input || self.no_output_relative_to_input {
+            config.output_relative_to_input = self.output_relative_to_input;
+        }
+        if let Some(policy) = self.overwrite {
+            config.overwrite = policy;
+        }
+        if self.no_overwrite {
+            config.overwrite = OverwritePolicy::Skip;
+        }
+        if let Some(command) = self.exec {
+            config.exec = Some(command);
+        }
+        if self.recursive || self.no_recursive {
+            config.recursive = self.recursive;
+        }
+        if self.flatten_output || self.no_flatten_output {
+            config.flatten_output = self.flatten_output;
+        }
+        if self.subfolder_per_sheet || self.no_subfolder_per_sheet {
+            config.subfolder_per_sheet = self.subfolder_per_sheet;
+        }
+        if self.montage || self.no_montage {
+            config.emit_montage = self.montage;
+        }
+        if self.verbose || self.no_verbose {
+            config.verbose = self.verbose;
+        }
+        if let Some(jobs) = self.jobs {
+            config.jobs = Some(jobs);
+        }
+        if let Some(max) = self.max_inflight {
+            config.max_inflight = Some(max);
+        }
+        if self.export_bevy || self.no_export_bevy {
+            config.export_bevy = self.export_bevy;
+        }
+        if self.debug_overlay || self.no_debug_overlay {
+            config.debug_overlay = self.debug_overlay;
+        }
+        if let Some(max) = self.max_frames {
+            config.max_frames = Some(max);
+        }
+        if let Some(path) = self.summary_json {
+            config.summary_json = Some(path);
+        }
+        if self.timings || self.no_timings {
+            config.timings = self.timings;
+        }
+        if let Some(threshold) = self.content_threshold {
+            config.content_threshold = threshold;
+        }
+        if let Some(pixels) = self.min_content_pixels {
+            config.min_content_pixels = Some(pixels);
+        }
+        if let Some(threshold) = self.alpha_threshold {
+            config.alpha_threshold = threshold;
+        }
+        if let Some(mode) = self.detection_mode {
+            config.detection_mode = mode;
+        }
+        if let Some(overlap) = self.merge_overlap {
+            config.merge_overlap = Some(overlap);
+        }
+        if self.merge_adjacent || self.no_merge_adjacent {
+            config.merge_adjacent = self.merge_adjacent;
+        }
+        if let Some(format) = self.atlas_format {
+            config.atlas_format = format;
+        }
+        if let Some(pivot) = self.pivot {
+            config.pivot = Some(pivot);
+        }
+        if self.emit_colors || self.no_emit_colors {
+            config.emit_colors = self.emit_colors;
+        }
+        if let Some(format) = self.manifest {
+            config.manifest = Some(format);
+        }
+        if let Some(path) = self.compare {
+            config.compare = Some(path);
+        }
+        if let Some(pack) = self.pack {
+            config.pack_mode = pack;
+        }
+        if let Some(size) = self.atlas_size {
+            config.atlas_size = size;
+        }
+        if self.dedup || self.no_dedup {
+            config.dedup = self.dedup;
+        }
+        if let Some(tolerance) = self.dedup_tolerance {
+            config.dedup_tolerance = tolerance;
+        }
+        if self.dedup_transforms || self.no_dedup_transforms {
+            config.dedup_transforms = self.dedup_transforms;
+        }
+        if let Some(format) = self.output_format {
+            config.output_format = format;
+        }
+        if let Some(quality) = self.quality {
+            config.output_quality = Some(quality);
+        }
+        if self.incremental || self.no_incremental {
+            config.incremental = self.incremental;
+        }
+        if self.watch || self.no_watch {
+            config.watch = self.watch;
+        }
+        if let Some(factor) = self.scale {
+            config.scale = Some(factor);
+        }
+        if let Some(divisor) = self.scale_down {
+            config.scale_down = Some(divisor);
+        }
+        if self.trim || self.no_trim {
+            config.trim = self.trim;
+        }
+        if let Some(margin) = self.trim_margin {
+            config.trim_margin = margin;
+        }
+        if self.autocrop || self.no_autocrop {
+            config.autocrop = self.autocrop;
+        }
+        if self.auto_grid || self.no_auto_grid {
+            config.auto_grid = self.auto_grid;
+        }
+        if self.include_edge_frames || self.no_include_edge_frames {
+            config.include_edge_frames = self.include_edge_frames;
+        }
+        if self.refine_boundaries || self.no_refine_boundaries {
+            config.refine_boundaries = self.refine_boundaries;
+        }
+        if let Some(padding) = self.padding {
+            config.frame_padding = padding;
+        }
+        if let Some(size) = self.uniform_size {
+            config.uniform_frame_size = Some(size);
+        }
+        if self.premultiply || self.no_premultiply {
+            config.premultiply_alpha = self.premultiply;
+        }
+        if let Some(order) = self.frame_order {
+            config.frame_order = order;
+        }
+        if self.preview_gif || self.no_preview_gif {
+            config.preview_gif = self.preview_gif;
+        }
+        if let Some(fps) = self.gif_fps {
+            config.gif_fps = fps;
+        }
+        if self.contact_sheet || self.no_contact_sheet {
+            config.contact_sheet = self.contact_sheet;
+        }
+        if let Some(columns) = self.contact_sheet_columns {
+            config.contact_sheet_columns = Some(columns);
+        }
+        if let Some(template) = self.filename_template {
+            config.filename_template = template;
+        }
+        if let Some(start) = self.index_start {
+            config.frame_index_start = start;
+        }
+        if let Some(distance) = self.boundary_merge_distance {
+            config.boundary_merge_distance = distance;
+        }
+        if let Some(ratio) = self.transparent_ratio {
+            config.transparent_column_ratio = ratio;
+        }
+        if let Some(delta) = self.color_change_delta {
+            config.color_change_delta = delta;
+        }
+        if let Some(ratio) = self.color_change_ratio {
+            config.color_change_ratio = ratio;
+        }
+        if let Some(source) = self.luma_source {
+            config.luma_source = source;
+        }
+        if let Some((cols, rows)) = self.grid {
+            config.grid = Some(GridSpec::Count { cols, rows });
         }
+        if let Some((width, height)) = self.grid_cell {
+            config.grid = Some(GridSpec::CellSize { width, height });
+        }
+        if let Some(rect) = self.roi {
+            config.roi = Some(rect);
+        }
+        if let Some(count) = self.strip_count {
+            config.strip_count = Some(count);
+        }
+        if let Some(orientation) = self.strip_orientation {
+            config.strip_orientation = orientation;
+        }
+        if let Some(margin) = self.grid_margin {
+            config.grid_margin = margin;
+        }
+        if let Some(spacing) = self.grid_spacing {
+            config.grid_spacing = spacing;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// The config file to read: --config, else the first default file present
+    fn config_file(&self) -> Option<PathBuf> {
+        self.config.clone().or_else(|| {
+            DEFAULT_CONFIG_FILES.iter()
+                .map(PathBuf::from)
+                .find(|path| Path::new(path).is_file())
+        })
     }
 }
 
-/// Represents a detected sprite frame
-#[derive(Debug, Clone)]
-struct SpriteFrame {
-    x: u32,
-    y: u32,
-    width: u32,
-    height: u32,
+/// Parse a tolerance given either as an absolute value or as a percentage.
+///
+/// Percentages map linearly onto the 0-255 channel range (`100%` = 255) and are
+/// rounded to the nearest step, so `8%` becomes 20. Values above the range
+/// (`300`, `150%`) are clamped to 255; negative or non-numeric input is rejected.
+fn parse_tolerance(value: &str) -> Result<u8, String> {
+    let value = value.trim();
+
+    if let Some(percent) = value.strip_suffix('%') {
+        let percent: f32 = percent.trim().parse()
+            .map_err(|_| format!("invalid percentage '{}'", value))?;
+        if !percent.is_finite() || percent < 0.0 {
+            return Err(format!("percentage must be between 0% and 100%, got '{}'", value));
+        }
+        Ok((percent.min(100.0) / 100.0 * 255.0).round() as u8)
+    } else {
+        let absolute: u32 = value.parse()
+            .map_err(|_| format!("expected a value from 0-255 or a percentage, got '{}'", value))?;
+        Ok(absolute.min(255) as u8)
+    }
 }
 
-/// Main spritesheet cutter structure
-struct SpritesheetCutter {
-    config: CutterConfig,
+/// A `--gutter-color` value
+#[derive(Debug, Clone, Copy)]
+enum GutterColor {
+    /// Detect the color of thin lines crossing each sheet
+    Auto,
+    /// Lines of this color
+    Hex(Rgba<u8>),
 }
 
-impl SpritesheetCutter {
-    fn new(config: CutterConfig) -> Self {
-        Self { config }
+/// Parse a hex color, or `auto` to have gutters detected
+fn parse_gutter_color(value: &str) -> Result<GutterColor, String> {
+    if value.trim().eq_ignore_ascii_case("auto") {
+        return Ok(GutterColor::Auto);
     }
+    parse_hex_color(value).map(GutterColor::Hex)
+}
 
-    /// Process all image files in the Base, Ships, and Space directories
-    fn process_directory(&self) -> Result<()> {
-        let current_dir = std::env::current_dir()
-            .context("Failed to get current directory")?;
-        
-        let folders_to_process = ["Base", "Ships", "Space"];
-        let mut total_processed = 0;
-
-        for folder_name in &folders_to_process {
-            let folder_path = current_dir.join(folder_name);
-            
-            if !folder_path.exists() {
-                println!("Folder '{}' not found, skipping...", folder_name);
-                continue;
-            }
-
-            println!("\n=== Processing {} folder ===", folder_name);
-            
-            // Create output directory for this folder
-            let output_path = current_dir.join(&self.config.output_dir).join(folder_name);
-            fs::create_dir_all(&output_path)
-                .context("Failed to create output directory")?;
-
-            // Find all image files in this folder
-            let image_files = self.find_image_files(&folder_path)?;
-            
-            if image_files.is_empty() {
-                println!("No image files found in the {} directory.", folder_name);
-                continue;
-            }
-
-            println!("Found {} image files to process in {}", image_files.len(), folder_name);
-
-            for (index, image_path) in image_files.iter().enumerate() {
-                println!("Processing {}/{}: {}", index + 1, image_files.len(), 
-                        image_path.file_name().unwrap().to_string_lossy());
-                
-                match self.process_spritesheet(image_path, &output_path) {
-                    Ok(frames_extracted) => {
-                        if frames_extracted == 0 {
-                            // If no frames were detected, copy the original image as a single sprite
-                            self.copy_single_sprite(image_path, &output_path)?;
-                            println!("  → Copied as single sprite");
-                        } else {
-                            println!("  → Extracted {} frames", frames_extracted);
-                        }
-                        total_processed += 1;
-                    }
-                    Err(e) => {
-                        eprintln!("Error processing {}: {}", 
-                                 image_path.file_name().unwrap().to_string_lossy(), e);
-                    }
-                }
-            }
-        }
-
-        println!("\n=== Processing Complete! ===");
-        println!("Successfully processed {} images across all folders.", total_processed);
-        println!("Check the '{}' directory for results.", self.config.output_dir);
-        Ok(())
-    }
+/// Parse a `WIDTHxHEIGHT` (or `COLSxROWS`) pair such as `4x2`
+fn parse_dimensions(value: &str) -> Result<(u32, u32), String> {
+    let (first, second) = value.trim().split_once(['x', 'X'])
+        .ok_or_else(|| format!("expected two numbers separated by 'x', got '{}'", value))?;
+    let parse = |part: &str| part.trim().parse::<u32>()
+        .map_err(|_| format!("invalid number '{}' in '{}'", part, value));
+    Ok((parse(first)?, parse(second)?))
+}
 
-    /// Find all image files in the directory
-    fn find_image_files(&self, dir: &Path) -> Result<Vec<PathBuf>> {
-        let mut image_files = Vec::new();
-        let supported_extensions: HashSet<&str> = 
-            ["png", "jpg", "jpeg", "bmp", "gif", "tiff", "webp"].iter().cloned().collect();
-
-        for entry in WalkDir::new(dir)
-            .max_depth(1)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            if entry.file_type().is_file() {
-                if let Some(extension) = entry.path().extension() {
-                    if let Some(ext_str) = extension.to_str() {
-                        if supported_extensions.contains(ext_str.to_lowercase().as_str()) {
-                            image_files.push(entry.path().to_path_buf());
-                        }
-                    }
-                }
-            }
-        }
-
-        Ok(image_files)
+/// Parse an `X,Y,W,H` rectangle such as `0,32,256,224`
+fn parse_rect(value: &str) -> Result<(u32, u32, u32, u32), String> {
+    let numbers = value.split(',')
+        .map(|part| part.trim().parse::<u32>().map_err(|_| format!("invalid number '{}' in '{}'", part, value)))
+        .collect::<Result<Vec<_>, _>>()?;
+    match numbers[..] {
+        [x, y, width, height] => Ok((x, y, width, height)),
+        _ => Err(format!("expected four numbers X,Y,W,H, got '{}'", value)),
     }
+}
 
-    /// Process a single spritesheet
-    fn process_spritesheet(&self, image_path: &Path, output_dir: &Path) -> Result<usize> {
-        let img = image::open(image_path)
-            .context("Failed to open image")?;
+/// Writes log lines as plain text: errors and warnings to stderr, everything
+/// else to stdout unless stdout is taken by `--stdout`
+struct TerminalLogger {
+    filter: env_logger::filter::Filter,
+    all_to_stderr: bool,
+}
+
+impl Log for TerminalLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata)
+    }
 
-        let frames = self.detect_sprite_frames(&img)?;
-        
-        if frames.is_empty() {
-            return Ok(0); // Return 0 frames detected
+    fn log(&self, record: &Record) {
+        if !self.filter.matches(record) {
+            return;
         }
+        // A closed pipe shouldn't take the run down with it
+        let _ = match record.level() {
+            _ if self.all_to_stderr => writeln!(std::io::stderr(), "{}", record.args()),
+            Level::Error | Level::Warn => writeln!(std::io::stderr(), "{}", record.args()),
+            _ => writeln!(std::io::stdout(), "{}", record.args()),
+        };
+    }
 
-        println!("  → Detected {} frames", frames.len());
+    fn flush(&self) {}
+}
 
-        // Extract and save each frame
-        let base_name = image_path.file_stem()
-            .unwrap()
-            .to_string_lossy()
-            .to_string();
+/// Which log lines are shown. The cutter's own lines go up to `level`, and
+/// its one-line summary only shows when `quiet` is set, since the full
+/// report covers it otherwise. `env` (from CUTTER_LOG) takes the usual
+/// env_logger syntax and overrides these defaults.
+fn log_filter(level: LevelFilter, quiet: bool, env: Option<&str>) -> env_logger::filter::Filter {
+    let mut builder = env_logger::filter::Builder::new();
+    builder.filter_module(SUMMARY_LOG_TARGET, if quiet { LevelFilter::Info } else { LevelFilter::Off });
+    match env {
+        Some(spec) => builder.parse(spec),
+        None => builder.filter_module("spritesheet_cutter", level),
+    };
+    builder.build()
+}
 
-        for (frame_index, frame) in frames.iter().enumerate() {
-            let cropped = self.extract_frame(&img, frame)?;
-            let processed = if self.config.remove_background {
-                self.remove_background(&cropped)?
-            } else {
-                cropped
-            };
+/// Write the sample sheet to `path`, unless a file is already there and
+/// `force` isn't set
+fn write_sample_sheet(path: &Path, force: bool) -> Result<()> {
+    ensure!(force || !path.exists(), "{} already exists; pass --force to replace it", path.display());
+    spritesheet_cutter::sample_sheet().save(path)?;
+    Ok(())
+}
 
-            let filename = format!("{}_frame_{:03}.png", base_name, frame_index + 1);
-            let output_path = output_dir.join(filename);
-            
-            processed.save(&output_path)
-                .context("Failed to save frame")?;
-        }
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    if cli.generate_sample {
+        write_sample_sheet(Path::new(SAMPLE_SHEET_FILE), cli.force)?;
+        println!("Wrote {}, a 4x2 grid of shapes. Cut it with: {} {}",
+                 SAMPLE_SHEET_FILE, env!("CARGO_BIN_NAME"), SAMPLE_SHEET_FILE);
+        return Ok(());
+    }
+    let print_config = cli.print_config;
+    let quiet = cli.quiet;
+    let to_stdout = cli.stdout;
+    let config = cli.into_config()?;
+    if print_config {
+        print!("{}", config.to_toml());
+        return Ok(());
+    }
 
-        Ok(frames.len())
+    let level = if quiet {
+        LevelFilter::Error
+    } else if config.verbose {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Info
+    };
+    let filter = log_filter(level, quiet, std::env::var("CUTTER_LOG").ok().as_deref());
+    log::set_max_level(filter.filter());
+    log::set_boxed_logger(Box::new(TerminalLogger { filter, all_to_stderr: to_stdout }))?;
+    if config.output_quality.is_some() && !config.output_format.takes_quality() {
+        warn!("--quality is ignored, as {} frames are saved lossless", config.output_format.extension());
     }
 
-    /// Copy a single sprite image to the output directory
-    fn copy_single_sprite(&self, image_path: &Path, output_dir: &Path) -> Result<()> {
-        let img = image::open(image_path)
-            .context("Failed to open image")?;
+    if to_stdout {
+        return print_frames(&config);
+    }
+    
+    info!("Spritesheet Cutter - Automatic Sprite Frame Extraction");
+    info!("=====================================================");
+    
+    let watch = config.watch;
+    let cutter = SpritesheetCutter::new(config);
+    
+    if watch {
+        cutter.watch()?;
+    } else {
+        cutter.process_directory()?;
+    }
+    
+    Ok(())
+}
 
-        let processed = if self.config.remove_background {
-            self.remove_background(&img)?
-        } else {
-            img
-        };
+/// Cut the one sheet given and print its frames, in frame order, as a JSON
+/// array of base64-encoded images
+fn print_frames(config: &CutterConfig) -> Result<()> {
+    let [sheet] = config.input_dirs.as_slice() else {
+        bail!("--stdout takes exactly one sheet, got {}", config.input_dirs.len());
+    };
+    ensure!(sheet.is_file(), "--stdout needs a single image file, not {}", sheet.display());
 
-        let filename = image_path.file_name()
-            .unwrap()
-            .to_string_lossy()
-            .to_string();
-        let output_path = output_dir.join(filename);
-        
-        processed.save(&output_path)
-            .context("Failed to save single sprite")?;
+    let frames = SpritesheetCutter::new(config.clone()).cut_to_memory(sheet)?;
+    let encoded: Vec<String> = frames.iter()
+        .map(|(_, bytes)| base64::engine::general_purpose::STANDARD.encode(bytes))
+        .collect();
+    let mut stdout = std::io::stdout().lock();
+    serde_json::to_writer(&mut stdout, &encoded)?;
+    writeln!(stdout)?;
+    Ok(())
+}
 
-        Ok(())
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    /// Detect sprite frames in the image using intelligent boundary detection
-    fn detect_sprite_frames(&self, img: &DynamicImage) -> Result<Vec<SpriteFrame>> {
-        let (_width, _height) = img.dimensions();
-        let mut frames = Vec::new();
-
-        // Convert to grayscale for analysis
-        let gray_img = img.to_luma8();
-        
-        // Find vertical and horizontal boundaries
-        let vertical_boundaries = self.find_vertical_boundaries(&gray_img);
-        let horizontal_boundaries = self.find_horizontal_boundaries(&gray_img);
-
-        // Generate frames from boundaries
-        for i in 0..vertical_boundaries.len().saturating_sub(1) {
-            for j in 0..horizontal_boundaries.len().saturating_sub(1) {
-                let x = vertical_boundaries[i];
-                let y = horizontal_boundaries[j];
-                let frame_width = vertical_boundaries[i + 1] - x;
-                let frame_height = horizontal_boundaries[j + 1] - y;
-
-                // Validate frame size
-                if frame_width >= self.config.min_sprite_size 
-                    && frame_height >= self.config.min_sprite_size
-                    && frame_width <= self.config.max_sprite_size 
-                    && frame_height <= self.config.max_sprite_size {
-                    
-                    // Check if frame contains non-transparent content
-                    if self.frame_has_content(img, x, y, frame_width, frame_height) {
-                        frames.push(SpriteFrame {
-                            x,
-                            y,
-                            width: frame_width,
-                            height: frame_height,
-                        });
-                    }
-                }
-            }
-        }
-
-        // If no frames were detected, try fallback detection
-        if frames.is_empty() {
-            println!("  → No frames detected with main algorithm, trying fallback...");
-            frames = self.fallback_detection(img)?;
-            if !frames.is_empty() {
-                println!("  → Fallback detection found {} frames", frames.len());
-            }
-        }
-
-        Ok(frames)
-    }
+    #[test]
+    fn test_cli_overrides_defaults() {
+        let cli = Cli::parse_from(["spritesheet-cutter", "--min-size", "4", "--no-remove-background",
+                                   "--output-dir", "frames"]);
+        let config = cli.into_config().unwrap();
+        assert_eq!(config.min_sprite_size, 4);
+        assert_eq!(config.max_sprite_size, 1024);
+        assert!(!config.remove_background);
+        assert_eq!(config.output_dir, "frames");
+        assert_eq!(config.overwrite, OverwritePolicy::Overwrite);
 
-    /// Fallback detection method for spritesheets that the main algorithm misses
-    fn fallback_detection(&self, img: &DynamicImage) -> Result<Vec<SpriteFrame>> {
-        let (width, height) = img.dimensions();
-        let mut frames = Vec::new();
-
-        // Try to detect horizontal spritesheets by finding actual empty space boundaries
-        let vertical_boundaries = self.find_empty_space_boundaries_horizontal(img)?;
-        println!("    → Found {} vertical boundaries: {:?}", vertical_boundaries.len(), vertical_boundaries);
-        
-        if vertical_boundaries.len() > 1 {
-            for i in 0..vertical_boundaries.len().saturating_sub(1) {
-                let x = vertical_boundaries[i];
-                let frame_width = vertical_boundaries[i + 1] - x;
-                
-                // Validate frame size
-                if frame_width >= self.config.min_sprite_size 
-                    && frame_width <= self.config.max_sprite_size {
-                    
-                    // Check if frame contains content
-                    if self.frame_has_content(img, x, 0, frame_width, height) {
-                        frames.push(SpriteFrame {
-                            x,
-                            y: 0,
-                            width: frame_width,
-                            height,
-                        });
-                    }
-                }
-            }
-        }
-
-        // If still no frames, try vertical spritesheets
-        if frames.is_empty() {
-            let horizontal_boundaries = self.find_empty_space_boundaries_vertical(img)?;
-            println!("    → Found {} horizontal boundaries: {:?}", horizontal_boundaries.len(), horizontal_boundaries);
-            
-            if horizontal_boundaries.len() > 1 {
-                for i in 0..horizontal_boundaries.len().saturating_sub(1) {
-                    let y = horizontal_boundaries[i];
-                    let frame_height = horizontal_boundaries[i + 1] - y;
-                    
-                    // Validate frame size
-                    if frame_height >= self.config.min_sprite_size 
-                        && frame_height <= self.config.max_sprite_size {
-                        
-                        // Check if frame contains content
-                        if self.frame_has_content(img, 0, y, width, frame_height) {
-                            frames.push(SpriteFrame {
-                                x: 0,
-                                y,
-                                width,
-                                height: frame_height,
-                            });
-                        }
-                    }
-                }
-            }
-        }
-
-        Ok(frames)
-    }
+        let config = Cli::parse_from(["spritesheet-cutter", "--no-overwrite"]).into_config().unwrap();
+        assert_eq!(config.overwrite, OverwritePolicy::Skip);
+        let config = Cli::parse_from(["spritesheet-cutter", "--overwrite", "error"]).into_config().unwrap();
+        assert_eq!(config.overwrite, OverwritePolicy::Error);
+        let config = Cli::parse_from(["spritesheet-cutter", "--output-format", "jpeg", "--quality", "85",
+                                      "--no-remove-background"]).into_config().unwrap();
+        assert_eq!((config.output_format, config.output_quality), (OutputFormat::Jpeg, Some(85)));
 
-    /// Find vertical boundaries by detecting empty space columns
-    fn find_empty_space_boundaries_horizontal(&self, img: &DynamicImage) -> Result<Vec<u32>> {
-        let (width, height) = img.dimensions();
-        let gray_img = img.to_luma8();
-        let mut boundaries = vec![0]; // Start with left edge
-        
-        // Detect the most common background color
-        let background_color = self.detect_most_common_color(&gray_img);
-        
-        for x in 1..width.saturating_sub(1) {
-            let mut empty_pixels = 0;
-            
-            // Check if this column is mostly empty/background
-            for y in 0..height {
-                let pixel = gray_img.get_pixel(x, y);
-                if (pixel[0] as i32 - background_color as i32).abs() <= 15 {
-                    empty_pixels += 1;
-                }
-            }
-            
-            // If more than 85% of the column is background, it's a boundary
-            if empty_pixels as f32 / height as f32 > 0.85 {
-                boundaries.push(x);
-            }
-        }
-        
-        boundaries.push(width); // End with right edge
-        boundaries.sort();
-        boundaries.dedup();
-        
-        // Remove boundaries that are too close together (less than min_sprite_size)
-        let mut filtered_boundaries = Vec::new();
-        let mut last_boundary = 0;
-        
-        for &boundary in &boundaries {
-            if boundary - last_boundary >= self.config.min_sprite_size || boundary == width {
-                filtered_boundaries.push(boundary);
-                last_boundary = boundary;
-            }
-        }
-        
-        Ok(filtered_boundaries)
+        let config = Cli::parse_from(["spritesheet-cutter", "--gutter-color", "auto"]).into_config().unwrap();
+        assert!(config.detect_gutter && config.gutter_color.is_none());
+        let config = Cli::parse_from(["spritesheet-cutter", "--gutter-color", "ff00ff"]).into_config().unwrap();
+        assert_eq!((config.detect_gutter, config.gutter_color), (false, Some(Rgba([255, 0, 255, 255]))));
     }
 
-    /// Find horizontal boundaries by detecting empty space rows
-    fn find_empty_space_boundaries_vertical(&self, img: &DynamicImage) -> Result<Vec<u32>> {
-        let (width, height) = img.dimensions();
-        let gray_img = img.to_luma8();
-        let mut boundaries = vec![0]; // Start with top edge
-        
-        // Detect the most common background color
-        let background_color = self.detect_most_common_color(&gray_img);
-        
-        for y in 1..height.saturating_sub(1) {
-            let mut empty_pixels = 0;
-            
-            // Check if this row is mostly empty/background
-            for x in 0..width {
-                let pixel = gray_img.get_pixel(x, y);
-                if (pixel[0] as i32 - background_color as i32).abs() <= 15 {
-                    empty_pixels += 1;
-                }
-            }
-            
-            // If more than 85% of the row is background, it's a boundary
-            if empty_pixels as f32 / width as f32 > 0.85 {
-                boundaries.push(y);
-            }
-        }
-        
-        boundaries.push(height); // End with bottom edge
-        boundaries.sort();
-        boundaries.dedup();
-        
-        // Remove boundaries that are too close together (less than min_sprite_size)
-        let mut filtered_boundaries = Vec::new();
-        let mut last_boundary = 0;
-        
-        for &boundary in &boundaries {
-            if boundary - last_boundary >= self.config.min_sprite_size || boundary == height {
-                filtered_boundaries.push(boundary);
-                last_boundary = boundary;
-            }
-        }
-        
-        Ok(filtered_boundaries)
-    }
+    #[test]
+    fn test_log_filter() {
+        let enabled = |filter: &env_logger::filter::Filter, level, target| {
+            filter.enabled(&Metadata::builder().level(level).target(target).build())
+        };
 
-    /// Estimate sprite width by finding the first significant content region
-    fn estimate_sprite_width(&self, img: &DynamicImage) -> Result<u32> {
-        let (width, height) = img.dimensions();
-        let gray_img = img.to_luma8();
-        
-        // Find the first column with significant content
-        let mut first_content_x = None;
-        for x in 0..width {
-            let mut content_pixels = 0;
-            for y in 0..height {
-                let pixel = gray_img.get_pixel(x, y);
-                if pixel[0] > 20 { // Not very dark/transparent
-                    content_pixels += 1;
-                }
-            }
-            if content_pixels as f32 / height as f32 > 0.1 { // More than 10% content
-                first_content_x = Some(x);
-                break;
-            }
-        }
-
-        if let Some(start_x) = first_content_x {
-            // Find the end of the first sprite
-            for x in start_x + 1..width {
-                let mut empty_pixels = 0;
-                for y in 0..height {
-                    let pixel = gray_img.get_pixel(x, y);
-                    if pixel[0] <= 20 { // Very dark/transparent
-                        empty_pixels += 1;
-                    }
-                }
-                if empty_pixels as f32 / height as f32 > 0.8 { // More than 80% empty
-                    return Ok(x - start_x);
-                }
-            }
-        }
-
-        // If the above method fails, try a different approach for spritesheets with uniform backgrounds
-        // Look for the most common color (likely background) and find sprite boundaries
-        let background_color = self.detect_most_common_color(&gray_img);
-        println!("    → Most common color: {}", background_color);
-        
-        // Find first non-background column
-        let mut first_sprite_x = None;
-        for x in 0..width {
-            let mut non_bg_pixels = 0;
-            for y in 0..height {
-                let pixel = gray_img.get_pixel(x, y);
-                if (pixel[0] as i32 - background_color as i32).abs() > 10 {
-                    non_bg_pixels += 1;
-                }
-            }
-            if non_bg_pixels as f32 / height as f32 > 0.05 { // More than 5% non-background
-                first_sprite_x = Some(x);
-                break;
-            }
-        }
-
-        if let Some(start_x) = first_sprite_x {
-            // Find the end of the first sprite
-            for x in start_x + 1..width {
-                let mut bg_pixels = 0;
-                for y in 0..height {
-                    let pixel = gray_img.get_pixel(x, y);
-                    if (pixel[0] as i32 - background_color as i32).abs() <= 10 {
-                        bg_pixels += 1;
-                    }
-                }
-                if bg_pixels as f32 / height as f32 > 0.7 { // More than 70% background
-                    return Ok(x - start_x);
-                }
-            }
-        }
-
-        Ok(0)
-    }
+        let quiet = log_filter(LevelFilter::Error, true, None);
+        assert!(enabled(&quiet, Level::Error, "spritesheet_cutter"));
+        assert!(!enabled(&quiet, Level::Info, "spritesheet_cutter"));
+        assert!(enabled(&quiet, Level::Info, SUMMARY_LOG_TARGET));
 
-    /// Detect the most common color in the image (likely background)
-    fn detect_most_common_color(&self, gray_img: &Image<image::Luma<u8>>) -> u8 {
-        let (width, height) = gray_img.dimensions();
-        let mut color_counts = std::collections::HashMap::new();
-        
-        // Sample every 4th pixel to speed up detection
-        for y in (0..height).step_by(4) {
-            for x in (0..width).step_by(4) {
-                let pixel = gray_img.get_pixel(x, y);
-                *color_counts.entry(pixel[0]).or_insert(0) += 1;
-            }
-        }
-        
-        color_counts.into_iter()
-            .max_by_key(|(_, count)| *count)
-            .map(|(color, _)| color)
-            .unwrap_or(0)
-    }
+        let normal = log_filter(LevelFilter::Info, false, None);
+        assert!(enabled(&normal, Level::Info, "spritesheet_cutter"));
+        assert!(!enabled(&normal, Level::Debug, "spritesheet_cutter"));
+        assert!(!enabled(&normal, Level::Info, SUMMARY_LOG_TARGET));
+        assert!(!enabled(&normal, Level::Info, "png"));
 
-    /// Estimate sprite height by finding the first significant content region
-    fn estimate_sprite_height(&self, img: &DynamicImage) -> Result<u32> {
-        let (width, height) = img.dimensions();
-        let gray_img = img.to_luma8();
-        
-        // Find the first row with significant content
-        let mut first_content_y = None;
-        for y in 0..height {
-            let mut content_pixels = 0;
-            for x in 0..width {
-                let pixel = gray_img.get_pixel(x, y);
-                if pixel[0] > 20 { // Not very dark/transparent
-                    content_pixels += 1;
-                }
-            }
-            if content_pixels as f32 / width as f32 > 0.1 { // More than 10% content
-                first_content_y = Some(y);
-                break;
-            }
-        }
-
-        if let Some(start_y) = first_content_y {
-            // Find the end of the first sprite
-            for y in start_y + 1..height {
-                let mut empty_pixels = 0;
-                for x in 0..width {
-                    let pixel = gray_img.get_pixel(x, y);
-                    if pixel[0] <= 20 { // Very dark/transparent
-                        empty_pixels += 1;
-                    }
-                }
-                if empty_pixels as f32 / width as f32 > 0.8 { // More than 80% empty
-                    return Ok(y - start_y);
-                }
-            }
-        }
-
-        Ok(0)
+        let env = log_filter(LevelFilter::Error, true, Some("debug"));
+        assert!(enabled(&env, Level::Debug, "spritesheet_cutter"));
+        assert!(enabled(&env, Level::Info, SUMMARY_LOG_TARGET));
     }
 
-    /// Find vertical boundaries (column separators)
-    fn find_vertical_boundaries(&self, gray_img: &Image<image::Luma<u8>>) -> Vec<u32> {
-        let (width, height) = gray_img.dimensions();
-        let mut boundaries = vec![0]; // Start with left edge
-        
-        for x in 1..width.saturating_sub(1) {
-            let _is_boundary = true;
-            let mut transparent_count = 0;
-            
-            // Check if this column is mostly transparent or uniform
-            for y in 0..height {
-                let pixel = gray_img.get_pixel(x, y);
-                if pixel[0] < 10 { // Very dark/transparent
-                    transparent_count += 1;
-                }
-            }
-            
-            // If more than 60% of the column is transparent, it's likely a boundary (reduced from 80%)
-            if transparent_count as f32 / height as f32 > 0.6 {
-                boundaries.push(x);
-            } else {
-                // Check for sudden color changes (edge detection) - more sensitive
-                let mut color_changes = 0;
-                for y in 0..height.saturating_sub(1) {
-                    let current = gray_img.get_pixel(x, y)[0] as i32;
-                    let next = gray_img.get_pixel(x, y + 1)[0] as i32;
-                    if (current - next).abs() > 30 { // Reduced threshold from 50 to 30
-                        color_changes += 1;
-                    }
-                }
-                
-                if color_changes as f32 / height as f32 > 0.2 { // Reduced from 0.3 to 0.2
-                    boundaries.push(x);
-                }
-            }
-        }
-        
-        boundaries.push(width); // End with right edge
-        boundaries.sort();
-        boundaries.dedup();
-        boundaries
-    }
+    #[test]
+    fn test_cli_input_dirs() {
+        let cli = Cli::parse_from(["spritesheet-cutter", "sheets/hero", "sheets/enemies"]);
+        let config = cli.into_config().unwrap();
+        assert_eq!(config.input_dirs, vec![PathBuf::from("sheets/hero"), PathBuf::from("sheets/enemies")]);
 
-    /// Find horizontal boundaries (row separators)
-    fn find_horizontal_boundaries(&self, gray_img: &Image<image::Luma<u8>>) -> Vec<u32> {
-        let (width, height) = gray_img.dimensions();
-        let mut boundaries = vec![0]; // Start with top edge
-        
-        for y in 1..height.saturating_sub(1) {
-            let mut transparent_count = 0;
-            
-            // Check if this row is mostly transparent
-            for x in 0..width {
-                let pixel = gray_img.get_pixel(x, y);
-                if pixel[0] < 10 { // Very dark/transparent
-                    transparent_count += 1;
-                }
-            }
-            
-            // If more than 60% of the row is transparent, it's likely a boundary (reduced from 80%)
-            if transparent_count as f32 / width as f32 > 0.6 {
-                boundaries.push(y);
-            } else {
-                // Check for sudden color changes - more sensitive
-                let mut color_changes = 0;
-                for x in 0..width.saturating_sub(1) {
-                    let current = gray_img.get_pixel(x, y)[0] as i32;
-                    let next = gray_img.get_pixel(x + 1, y)[0] as i32;
-                    if (current - next).abs() > 30 { // Reduced threshold from 50 to 30
-                        color_changes += 1;
-                    }
-                }
-                
-                if color_changes as f32 / width as f32 > 0.2 { // Reduced from 0.3 to 0.2
-                    boundaries.push(y);
-                }
-            }
-        }
-        
-        boundaries.push(height); // End with bottom edge
-        boundaries.sort();
-        boundaries.dedup();
-        boundaries
+        let config = Cli::parse_from(["spritesheet-cutter"]).into_config().unwrap();
+        assert!(config.input_dirs.is_empty());
     }
 
-    /// Check if a frame contains meaningful content
-    fn frame_has_content(&self, img: &DynamicImage, x: u32, y: u32, width: u32, height: u32) -> bool {
-        let mut non_transparent_pixels = 0;
-        let total_pixels = width * height;
-        
-        for py in y..y + height {
-            for px in x..x + width {
-                if px < img.width() && py < img.height() {
-                    let pixel = img.get_pixel(px, py);
-                    match pixel {
-                        image::Rgba([_r, _g, _b, a]) => {
-                            if a > 10 { // Not fully transparent
-                                non_transparent_pixels += 1;
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        
-        // Frame has content if more than 2% of pixels are non-transparent (reduced from 5%)
-        non_transparent_pixels as f32 / total_pixels as f32 > 0.02
+    #[test]
+    fn test_cli_rejects_min_above_max() {
+        let cli = Cli::parse_from(["spritesheet-cutter", "--min-size", "64", "--max-size", "32"]);
+        assert!(cli.into_config().is_err());
     }
 
-    /// Extract a frame from the image
-    fn extract_frame(&self, img: &DynamicImage, frame: &SpriteFrame) -> Result<DynamicImage> {
-        let cropped = img.crop_imm(frame.x, frame.y, frame.width, frame.height);
-        Ok(cropped)
-    }
+    #[test]
+    fn test_cli_rejects_invalid_values() {
+        let cli = Cli::parse_from(["spritesheet-cutter", "--transparent-ratio", "1.5"]);
+        assert!(cli.into_config().is_err());
 
-    /// Remove background from the image
-    fn remove_background(&self, img: &DynamicImage) -> Result<DynamicImage> {
-        let mut rgba_img = img.to_rgba8();
-        let (width, height) = rgba_img.dimensions();
-        
-        // Detect background color (most common color in corners)
-        let background_color = self.detect_background_color(&rgba_img);
-        
-        // Make background transparent
-        for y in 0..height {
-            for x in 0..width {
-                let pixel = rgba_img.get_pixel(x, y);
-                if self.is_background_pixel(pixel, &background_color) {
-                    rgba_img.put_pixel(x, y, Rgba([0, 0, 0, 0])); // Transparent
-                }
-            }
-        }
-        
-        Ok(DynamicImage::ImageRgba8(rgba_img))
+        let cli = Cli::parse_from(["spritesheet-cutter", "--filename-template", "{name}_{idx}"]);
+        assert!(cli.into_config().is_err());
+
+        let cli = Cli::parse_from(["spritesheet-cutter", "-j", "0"]);
+        assert!(cli.into_config().is_err());
+        let config = Cli::parse_from(["spritesheet-cutter", "-j", "2"]).into_config().unwrap();
+        assert_eq!(config.jobs, Some(2));
     }
 
-    /// Detect the background color by analyzing corner pixels
-    fn detect_background_color(&self, img: &RgbaImage) -> Rgba<u8> {
-        let (width, height) = img.dimensions();
-        let mut color_counts = std::collections::HashMap::new();
-        
-        // Sample corner regions
-        let sample_size = 10;
-        for y in 0..sample_size.min(height) {
-            for x in 0..sample_size.min(width) {
-                let pixel = img.get_pixel(x, y);
-                *color_counts.entry(pixel).or_insert(0) += 1;
-            }
-        }
-        
-        // Find most common color
-        color_counts.into_iter()
-            .max_by_key(|(_, count)| *count)
-            .map(|(color, _)| color)
-            .unwrap_or(&Rgba([255, 255, 255, 255]))
-            .clone()
+    #[test]
+    fn test_cli_overrides_config_file() {
+        let scratch = tempfile::tempdir().unwrap();
+        let path = scratch.path().join("cli-config.toml");
+        std::fs::write(&path, "min_sprite_size = 4\ntrim = true\ndetection_mode = \"connected-components\"\n").unwrap();
+        let config_arg = path.to_str().unwrap();
+
+        let config = Cli::parse_from(["spritesheet-cutter", "--config", config_arg]).into_config().unwrap();
+        assert_eq!(config.min_sprite_size, 4);
+        assert!(config.trim);
+        assert_eq!(config.detection_mode, DetectionMode::ConnectedComponents);
+
+        let config = Cli::parse_from(["spritesheet-cutter", "--config", config_arg, "--min-size", "6",
+                                      "--detection-mode", "boundaries"]).into_config().unwrap();
+        assert_eq!(config.min_sprite_size, 6);
+        assert!(config.trim);
+        assert_eq!(config.detection_mode, DetectionMode::Boundaries);
+
+        // Booleans turned on by the file can be turned off again, and the other way round
+        std::fs::write(&path, "trim = true\nremove_background = false\n").unwrap();
+        let config = Cli::parse_from(["spritesheet-cutter", "--config", config_arg, "--no-trim", "--remove-background"])
+            .into_config().unwrap();
+        assert!(!config.trim);
+        assert!(config.remove_background);
+        assert!(Cli::try_parse_from(["spritesheet-cutter", "--trim", "--no-trim"]).is_err());
+
+        // Settings that need a switch are accepted when the file turns it on
+        std::fs::write(&path, "dedup = true\n").unwrap();
+        let config = Cli::parse_from(["spritesheet-cutter", "--config", config_arg, "--dedup-tolerance", "3",
+                                      "--dedup-transforms"]).into_config().unwrap();
+        assert_eq!((config.dedup_tolerance, config.dedup_transforms), (3, true));
+        let cli = Cli::parse_from(["spritesheet-cutter", "--config", config_arg, "--no-dedup", "--dedup-transforms"]);
+        assert!(cli.into_config().is_err());
+        std::fs::write(&path, "strip_count = 4\n").unwrap();
+        let config = Cli::parse_from(["spritesheet-cutter", "--config", config_arg, "--strip-orientation", "vertical"])
+            .into_config().unwrap();
+        assert_eq!(config.strip_orientation, Orientation::Vertical);
     }
 
-    /// Check if a pixel matches the background color
-    fn is_background_pixel(&self, pixel: &Rgba<u8>, background: &Rgba<u8>) -> bool {
-        let tolerance = self.config.background_tolerance as i32;
-        
-        (pixel[0] as i32 - background[0] as i32).abs() <= tolerance &&
-        (pixel[1] as i32 - background[1] as i32).abs() <= tolerance &&
-        (pixel[2] as i32 - background[2] as i32).abs() <= tolerance
+    #[test]
+    fn test_write_sample_sheet() {
+        let scratch = tempfile::tempdir().unwrap();
+        let path = scratch.path().join("sample.png");
+        std::fs::write(&path, "not a sheet").unwrap();
+        assert!(write_sample_sheet(&path, false).is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"not a sheet");
+        write_sample_sheet(&path, true).unwrap();
+        assert_eq!(image::image_dimensions(&path).unwrap(), spritesheet_cutter::sample_sheet().dimensions());
+
+        assert!(Cli::try_parse_from(["spritesheet-cutter", "--force"]).is_err());
     }
-}
 
-fn main() -> Result<()> {
-    env_logger::init();
-    
-    println!("Spritesheet Cutter - Automatic Sprite Frame Extraction");
-    println!("=====================================================");
-    
-    let config = CutterConfig::default();
-    let cutter = SpritesheetCutter::new(config);
-    
-    cutter.process_directory()?;
-    
-    Ok(())
-}
+    #[test]
+    fn test_parse_hex_color() {
+        assert_eq!(parse_hex_color("ff00ff"), Ok(Rgba([255, 0, 255, 255])));
+        assert_eq!(parse_hex_color("#00FF0080"), Ok(Rgba([0, 255, 0, 128])));
+        assert!(parse_hex_color("ff00f").is_err());
+        assert!(parse_hex_color("gg00ff").is_err());
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_parse_dimensions() {
+        assert_eq!(parse_dimensions("4x2"), Ok((4, 2)));
+        assert_eq!(parse_dimensions("32X48"), Ok((32, 48)));
+        assert!(parse_dimensions("4").is_err());
+        assert!(parse_dimensions("ax2").is_err());
+    }
 
     #[test]
-    fn test_config_default() {
-        let config = CutterConfig::default();
-        assert_eq!(config.min_sprite_size, 16);
-        assert_eq!(config.max_sprite_size, 512);
-        assert_eq!(config.background_tolerance, 10);
-        assert!(config.remove_background);
-        assert_eq!(config.output_dir, "assets2");
+    fn test_parse_rect() {
+        assert_eq!(parse_rect("0,32,256,224"), Ok((0, 32, 256, 224)));
+        assert_eq!(parse_rect(" 1, 2 ,3,4"), Ok((1, 2, 3, 4)));
+        assert!(parse_rect("1,2,3").is_err());
+        assert!(parse_rect("1,2,3,x").is_err());
     }
 
     #[test]
-    fn test_background_pixel_detection() {
-        let config = CutterConfig::default();
-        let cutter = SpritesheetCutter::new(config);
-        
-        let background = Rgba([255, 255, 255, 255]);
-        let similar_pixel = Rgba([250, 250, 250, 255]);
-        let different_pixel = Rgba([100, 100, 100, 255]);
-        
-        assert!(cutter.is_background_pixel(&similar_pixel, &background));
-        assert!(!cutter.is_background_pixel(&different_pixel, &background));
+    fn test_parse_tolerance() {
+        assert_eq!(parse_tolerance("10"), Ok(10));
+        assert_eq!(parse_tolerance("300"), Ok(255));
+        assert_eq!(parse_tolerance("8%"), Ok(20));
+        assert_eq!(parse_tolerance("100%"), Ok(255));
+        assert_eq!(parse_tolerance("150%"), Ok(255));
+        assert_eq!(parse_tolerance("0%"), Ok(0));
+        assert!(parse_tolerance("-5%").is_err());
+        assert!(parse_tolerance("-5").is_err());
+        assert!(parse_tolerance("abc").is_err());
     }
 }